@@ -1,39 +1,61 @@
 
 (scene 
     (camera 
-        (vector3 0 0 0) 
-        (view_port 1 1 1)) 
+        (vector3 0 0 0) (view_port 1 1 1)) 
     (union (list 
-        (sphere 
-            (vector3 0 -5001 0)
-            5000
+        (plane 
+            (vector3 0 -1 0)
+            (vector3 0 1 0)
             (material 
                 (color 1 1 0)
-                1000)) 
+                1000
+                0.5
+                0
+                1
+                (color 0 0 0))) 
         (sphere 
             (vector3 0 -1 3)
             1
             (material 
                 (color 1 0 0)
-                500)) 
+                500
+                0.2
+                0
+                1
+                (color 0 0 0))) 
         (sphere 
             (vector3 2 0 4)
             1
             (material 
                 (color 0 0 1)
-                500)) 
+                500
+                0.3
+                0
+                1
+                (color 0 0 0))) 
         (sphere 
             (vector3 -2 0 4)
             1
             (material 
                 (color 0 1 0)
-                10)))) 
+                10
+                0.4
+                0
+                1
+                (color 0 0 0))))) 
     (list 
         (ambiant
-            0.2) 
+            0.2
+            (color 1 1 1)) 
         (omnidirectional
             0.6
-            (vector3 2 1 0)) 
+            (color 1 1 1)
+            (vector3 2 1 0)
+            true) 
         (directional
             0.2
-            (vector3 1 4 4))))
\ No newline at end of file
+            (color 1 1 1)
+            (vector3 1 4 4)
+            true))
+    3
+    (display 0 clamp true))
\ No newline at end of file
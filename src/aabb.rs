@@ -0,0 +1,158 @@
+use crate::{math::Vector3, ray::Ray};
+
+/**
+ * @brief An axis-aligned bounding box
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    /**
+     * @brief A box containing nothing, neutral element of the union
+     */
+    pub const EMPTY: Aabb = Aabb {
+        min: Vector3 {
+            x: f32::INFINITY,
+            y: f32::INFINITY,
+            z: f32::INFINITY,
+        },
+        max: Vector3 {
+            x: f32::NEG_INFINITY,
+            y: f32::NEG_INFINITY,
+            z: f32::NEG_INFINITY,
+        },
+    };
+
+    /**
+     * @brief A box containing everything, for unbounded nodes
+     */
+    pub const INFINITE: Aabb = Aabb {
+        min: Vector3 {
+            x: f32::NEG_INFINITY,
+            y: f32::NEG_INFINITY,
+            z: f32::NEG_INFINITY,
+        },
+        max: Vector3 {
+            x: f32::INFINITY,
+            y: f32::INFINITY,
+            z: f32::INFINITY,
+        },
+    };
+
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Self { min, max }
+    }
+
+    /**
+     * @brief The smallest box containing the box and an another box
+     */
+    pub fn union(self, o: Self) -> Self {
+        Self::new(self.min.min(o.min), self.max.max(o.max))
+    }
+
+    /**
+     * @brief The largest box contained in both the box and an another box
+     */
+    pub fn intersection(self, o: Self) -> Self {
+        Self::new(self.min.max(o.min), self.max.min(o.max))
+    }
+
+    /**
+     * @brief The smallest box containing the box and a point
+     */
+    pub fn grow(self, point: Vector3) -> Self {
+        Self::new(self.min.min(point), self.max.max(point))
+    }
+
+    /**
+     * @brief The center of the box
+     */
+    pub fn centroid(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /**
+     * @brief The area of the surface of the box, 0 for an empty box
+     */
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /**
+     * @brief Check if the box has a finite size
+     */
+    pub fn is_finite(&self) -> bool {
+        [self.min, self.max]
+            .iter()
+            .all(|v| v.x.is_finite() && v.y.is_finite() && v.z.is_finite())
+    }
+
+    /**
+     * @brief Intersect the box with a ray using the slab method
+     *
+     * @param ray the ray
+     * @param max_distance ignore the intersections farther than this distance
+     *
+     * @return the distance at which the ray enters the box, 0 if the origin is inside
+     */
+    pub fn hit(&self, ray: &Ray, max_distance: f32) -> Option<f32> {
+        self.hit_range(ray, 0.0, max_distance)
+    }
+
+    /**
+     * @brief Intersect the box with a segment of a ray
+     *
+     * @param ray the ray
+     * @param min_distance the start of the segment, may be negative
+     * @param max_distance the end of the segment
+     *
+     * @return the distance at which the segment enters the box
+     */
+    pub fn hit_range(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<f32> {
+        self.clip(ray, min_distance, max_distance)
+            .map(|(t_min, _)| t_min)
+    }
+
+    /**
+     * @brief Clip a segment of a ray to the box
+     *
+     * @param ray the ray
+     * @param min_distance the start of the segment, may be negative
+     * @param max_distance the end of the segment
+     *
+     * @return the distances at which the segment enters and leaves the box
+     */
+    pub fn clip(&self, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<(f32, f32)> {
+        let mut t_min = min_distance;
+        let mut t_max = max_distance;
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+            if inverse_direction < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // Written to ignore the NaNs of the degenerated cases
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+
+        Some((t_min, t_max))
+    }
+}
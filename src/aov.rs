@@ -0,0 +1,125 @@
+use crate::{
+    canvas::{Canvas, FrameBufferCanvas, LayeredCanvas},
+    color::Color,
+    ray::{Hit, Ray},
+    render::render_tiles,
+    scene::Scene,
+};
+
+/**
+ * @brief An arbitrary output variable, a pass rendered alongside the image
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    /**
+     * @brief Distance from the camera, infinite for the background
+     */
+    Depth,
+
+    /**
+     * @brief Outward normal in world space
+     */
+    Normal,
+
+    /**
+     * @brief Position in world space
+     */
+    Position,
+
+    /**
+     * @brief Base color of the material
+     */
+    Albedo,
+
+    MaterialId,
+    ObjectId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 6] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Position,
+        Aov::Albedo,
+        Aov::MaterialId,
+        Aov::ObjectId,
+    ];
+
+    /**
+     * @brief Get the pass from its name on the command line
+     */
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Depth => "depth",
+            Self::Normal => "normal",
+            Self::Position => "position",
+            Self::Albedo => "albedo",
+            Self::MaterialId => "material_id",
+            Self::ObjectId => "object_id",
+        }
+    }
+
+    /**
+     * @brief Get the value of the pass for a camera ray
+     *
+     * The scalar passes are stored in the three channels. The identifiers
+     * are stored as is, 0 being the background.
+     *
+     * @param ray the camera ray
+     * @param hit the nearest hit of the ray, if any
+     */
+    pub fn evaluate(&self, ray: &Ray, hit: Option<&Hit>) -> Color {
+        let hit = match hit {
+            Some(hit) => hit,
+            None if *self == Self::Depth => {
+                return Color::new(f32::INFINITY, f32::INFINITY, f32::INFINITY)
+            }
+            None => return Color::BLACK,
+        };
+        let gray = |x: f32| Color::new(x, x, x);
+
+        match self {
+            Self::Depth => gray(hit.distance * ray.direction.length()),
+            Self::Normal => Color::new(hit.normal.x, hit.normal.y, hit.normal.z),
+            Self::Position => Color::new(hit.position.x, hit.position.y, hit.position.z),
+            Self::Albedo => match &hit.material.bsdf {
+                Some(model) => model.as_bsdf().albedo(),
+                None => hit.material.color,
+            },
+            Self::MaterialId => gray(hit.material.id as f32),
+            Self::ObjectId => gray(hit.material.object_id as f32),
+        }
+    }
+}
+
+/**
+ * @brief Render a pass with one ray through the center of each pixel
+ *
+ * The passes aren't filtered, so that the identifiers aren't blended.
+ */
+pub fn render_aov(scene: &Scene, aov: Aov, canvas: &mut dyn Canvas, threads: usize) {
+    let (width, height) = (canvas.width(), canvas.height());
+
+    render_tiles(canvas, threads, |u, v| {
+        let ray = scene
+            .camera
+            .ray(u as f32 + 0.5, v as f32 + 0.5, width, height);
+        let hit = scene.root.hit(&ray).map(|hit| scene.texture_hit(&ray, hit));
+        aov.evaluate(&ray, hit.as_ref())
+    });
+}
+
+/**
+ * @brief Render several passes to the named layers of a canvas
+ */
+pub fn render_aovs(scene: &Scene, aovs: &[Aov], layers: &mut LayeredCanvas, threads: usize) {
+    for &aov in aovs {
+        let mut canvas = FrameBufferCanvas::new(layers.width, layers.height);
+        render_aov(scene, aov, &mut canvas, threads);
+        layers.insert(aov.name(), canvas);
+    }
+}
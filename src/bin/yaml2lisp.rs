@@ -1,11 +1,21 @@
-use std::{env, path::Path};
+use std::{env, path::Path, process};
 
 use raytracer::loader::{yaml::YamlLoader, Loader};
 use raytracer::saver::{lisp::LispSaver, Saver};
 
 pub fn main() {
     let args: Vec<String> = env::args().collect();
-    let scene = YamlLoader {}.load_scene_from_file(Path::new(&args[1]));
+    let scene = YamlLoader::default()
+        .load_scene_from_file(Path::new(&args[1]))
+        .unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
 
-    LispSaver {}.save_scene_to_file(&scene, Path::new(&args[2]));
+    LispSaver::default()
+        .save_scene_to_file(&scene, Path::new(&args[2]))
+        .unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
 }
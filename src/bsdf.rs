@@ -0,0 +1,634 @@
+use std::f32::consts::PI;
+
+use crate::{color::Color, math::Vector3, sampling::Random};
+
+/**
+ * @brief A direction sampled from a BSDF
+ */
+pub struct BsdfSample {
+    /**
+     * @brief The normalized direction of the incoming light
+     */
+    pub direction: Vector3,
+
+    /**
+     * @brief The BSDF times the cosine of the direction, divided by the density
+     */
+    pub weight: Color,
+}
+
+/**
+ * @brief A bidirectional scattering distribution function
+ *
+ * The directions are normalized and point away from the surface. The normal
+ * is the outward normal of the surface, the functions handle the rays
+ * coming from inside.
+ */
+pub trait Bsdf {
+    /**
+     * @brief Evaluate the BSDF times the cosine of the incoming direction
+     *
+     * @param normal the outward normal
+     * @param wo the direction toward the viewer
+     * @param wi the direction toward the light
+     */
+    fn evaluate(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> Color;
+
+    /**
+     * @brief Get the density of the directions returned by sample
+     */
+    fn pdf(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> f32;
+
+    /**
+     * @brief Sample an incoming direction
+     *
+     * @return the sample, None if the sampled direction is below the surface
+     */
+    fn sample(&self, normal: Vector3, wo: Vector3, random: &mut Random) -> Option<BsdfSample>;
+
+    /**
+     * @brief Get the overall color of the surface, used for the ambient light
+     */
+    fn albedo(&self) -> Color;
+
+    /**
+     * @brief Get the weights of the mirror reflection and of the refraction
+     *
+     * Used by the Whitted-style renderers, which can only trace the perfectly
+     * specular directions. The rough reflections fade with the roughness.
+     *
+     * @return the weights of the reflected and of the refracted rays
+     */
+    fn specular_weights(&self, normal: Vector3, wo: Vector3) -> (Color, Color);
+
+    /**
+     * @brief Get the refractive index of the medium below the surface
+     */
+    fn refractive_index(&self) -> f32 {
+        1.0
+    }
+}
+
+/**
+ * @brief Turn the normal toward a direction
+ */
+fn facing(normal: Vector3, direction: Vector3) -> Vector3 {
+    if normal.dot(direction) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/**
+ * @brief Reflect a direction pointing away from the surface around a normal
+ */
+fn mirror(direction: Vector3, normal: Vector3) -> Vector3 {
+    normal * (2.0 * direction.dot(normal)) - direction
+}
+
+/**
+ * @brief The Schlick's approximation of the Fresnel reflectance of a conductor
+ *
+ * @param f0 the reflectance at normal incidence
+ * @param cos the cosine of the angle with the normal
+ */
+pub fn fresnel_schlick(f0: Color, cos: f32) -> Color {
+    let k = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+
+    Color::new(
+        f0.r + (1.0 - f0.r) * k,
+        f0.g + (1.0 - f0.g) * k,
+        f0.b + (1.0 - f0.b) * k,
+    )
+}
+
+/**
+ * @brief The exact Fresnel reflectance of a dielectric interface
+ *
+ * @param cos_i the cosine of the incident angle
+ * @param eta_i the refractive index of the incident medium
+ * @param eta_t the refractive index of the transmitted medium
+ */
+pub fn fresnel_dielectric(cos_i: f32, eta_i: f32, eta_t: f32) -> f32 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin_t = eta_i / eta_t * (1.0 - cos_i * cos_i).sqrt();
+    if sin_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin_t * sin_t).sqrt();
+    let parallel = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    let perpendicular = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+
+    (parallel * parallel + perpendicular * perpendicular) * 0.5
+}
+
+/**
+ * @brief The GGX (Trowbridge-Reitz) distribution of microfacets
+ */
+struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    fn new(roughness: f32) -> Self {
+        Self {
+            alpha: (roughness * roughness).max(1e-3),
+        }
+    }
+
+    /**
+     * @brief The density of the microfacets oriented along a half vector
+     */
+    fn distribution(&self, n_dot_h: f32) -> f32 {
+        let alpha2 = self.alpha * self.alpha;
+        let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+
+        alpha2 / (PI * d * d)
+    }
+
+    /**
+     * @brief The Smith masking of a direction
+     */
+    fn masking(&self, n_dot_v: f32) -> f32 {
+        let n_dot_v = n_dot_v.abs();
+        let alpha2 = self.alpha * self.alpha;
+
+        2.0 * n_dot_v / (n_dot_v + (alpha2 + (1.0 - alpha2) * n_dot_v * n_dot_v).sqrt())
+    }
+
+    fn shadowing(&self, n_dot_o: f32, n_dot_i: f32) -> f32 {
+        self.masking(n_dot_o) * self.masking(n_dot_i)
+    }
+
+    /**
+     * @brief Sample a half vector proportionally to the distribution times its cosine
+     */
+    fn sample_half_vector(&self, normal: Vector3, random: &mut Random) -> Vector3 {
+        let (u, v) = (random.next_f32(), random.next_f32());
+        let tan2 = self.alpha * self.alpha * u / (1.0 - u).max(1e-7);
+        let cos = 1.0 / (1.0 + tan2).sqrt();
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let angle = 2.0 * PI * v;
+        let (tangent, bitangent) = normal.orthonormal_basis();
+
+        tangent * (sin * angle.cos()) + bitangent * (sin * angle.sin()) + normal * cos
+    }
+
+    /**
+     * @brief The common weight of the reflections and refractions sampled
+     * from the distribution
+     */
+    fn sample_weight(&self, normal: Vector3, wo: Vector3, wi: Vector3, half: Vector3) -> f32 {
+        let n_dot_o = normal.dot(wo).abs();
+        let n_dot_h = normal.dot(half).abs();
+        if n_dot_o <= 0.0 || n_dot_h <= 0.0 {
+            return 0.0;
+        }
+
+        self.shadowing(n_dot_o, normal.dot(wi)) * wo.dot(half).abs() / (n_dot_o * n_dot_h)
+    }
+}
+
+/**
+ * @brief A perfectly diffuse surface
+ */
+#[derive(Clone, Copy)]
+pub struct Lambertian {
+    pub color: Color,
+}
+
+impl Bsdf for Lambertian {
+    fn evaluate(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> Color {
+        let normal = facing(normal, wo);
+        let cos = normal.dot(wi);
+        if cos <= 0.0 {
+            return Color::BLACK;
+        }
+
+        self.color * (cos / PI)
+    }
+
+    fn pdf(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> f32 {
+        facing(normal, wo).dot(wi).max(0.0) / PI
+    }
+
+    fn sample(&self, normal: Vector3, wo: Vector3, random: &mut Random) -> Option<BsdfSample> {
+        Some(BsdfSample {
+            direction: random.cosine_hemisphere(facing(normal, wo)),
+            weight: self.color,
+        })
+    }
+
+    fn albedo(&self) -> Color {
+        self.color
+    }
+
+    fn specular_weights(&self, _normal: Vector3, _wo: Vector3) -> (Color, Color) {
+        (Color::BLACK, Color::BLACK)
+    }
+}
+
+/**
+ * @brief A metal, reflecting the light on GGX microfacets
+ */
+#[derive(Clone, Copy)]
+pub struct Conductor {
+    /**
+     * @brief The reflectance at normal incidence
+     */
+    pub color: Color,
+
+    /**
+     * @brief The perceptual roughness, from 0 for a mirror to 1
+     */
+    pub roughness: f32,
+}
+
+impl Bsdf for Conductor {
+    fn evaluate(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> Color {
+        let normal = facing(normal, wo);
+        let (n_dot_o, n_dot_i) = (normal.dot(wo), normal.dot(wi));
+        if n_dot_o <= 0.0 || n_dot_i <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let ggx = Ggx::new(self.roughness);
+        let half = (wo + wi).normalize();
+        let fresnel = fresnel_schlick(self.color, wo.dot(half));
+
+        fresnel
+            * (ggx.distribution(normal.dot(half)) * ggx.shadowing(n_dot_o, n_dot_i)
+                / (4.0 * n_dot_o))
+    }
+
+    fn pdf(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> f32 {
+        let normal = facing(normal, wo);
+        if normal.dot(wi) <= 0.0 {
+            return 0.0;
+        }
+
+        let half = (wo + wi).normalize();
+        let n_dot_h = normal.dot(half);
+
+        Ggx::new(self.roughness).distribution(n_dot_h) * n_dot_h / (4.0 * wo.dot(half).abs())
+    }
+
+    fn sample(&self, normal: Vector3, wo: Vector3, random: &mut Random) -> Option<BsdfSample> {
+        let normal = facing(normal, wo);
+        let ggx = Ggx::new(self.roughness);
+        let half = ggx.sample_half_vector(normal, random);
+        let direction = mirror(wo, half);
+        if normal.dot(direction) <= 0.0 || wo.dot(half) <= 0.0 {
+            return None;
+        }
+
+        let fresnel = fresnel_schlick(self.color, wo.dot(half));
+        Some(BsdfSample {
+            direction,
+            weight: fresnel * ggx.sample_weight(normal, wo, direction, half),
+        })
+    }
+
+    fn albedo(&self) -> Color {
+        self.color
+    }
+
+    fn specular_weights(&self, normal: Vector3, wo: Vector3) -> (Color, Color) {
+        let fresnel = fresnel_schlick(self.color, normal.dot(wo).abs());
+
+        (fresnel * (1.0 - self.roughness), Color::BLACK)
+    }
+}
+
+/**
+ * @brief A transparent surface, reflecting and refracting the light on GGX microfacets
+ */
+#[derive(Clone, Copy)]
+pub struct Dielectric {
+    /**
+     * @brief The tint of the refracted light
+     */
+    pub color: Color,
+
+    /**
+     * @brief The perceptual roughness, from 0 for a polished surface to 1
+     */
+    pub roughness: f32,
+
+    pub refractive_index: f32,
+}
+
+impl Dielectric {
+    /**
+     * @brief Get the refractive indices of the side of a direction and of the other side
+     */
+    fn indices(&self, normal: Vector3, wo: Vector3) -> (f32, f32) {
+        if normal.dot(wo) > 0.0 {
+            (1.0, self.refractive_index)
+        } else {
+            (self.refractive_index, 1.0)
+        }
+    }
+
+    /**
+     * @brief Get the half vector of a refraction, facing the normal
+     */
+    fn refraction_half_vector(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> Vector3 {
+        let (eta_o, eta_i) = self.indices(normal, wo);
+
+        facing(-(wo * eta_o + wi * eta_i).normalize(), normal)
+    }
+}
+
+impl Bsdf for Dielectric {
+    fn evaluate(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> Color {
+        let normal = facing(normal, wo);
+        let (eta_o, eta_i) = self.indices(normal, wo);
+        let ggx = Ggx::new(self.roughness);
+        let (n_dot_o, n_dot_i) = (normal.dot(wo), normal.dot(wi));
+
+        if n_dot_i > 0.0 {
+            let half = (wo + wi).normalize();
+            let fresnel = fresnel_dielectric(wo.dot(half), eta_o, eta_i);
+            let value =
+                fresnel * ggx.distribution(normal.dot(half)) * ggx.shadowing(n_dot_o, n_dot_i)
+                    / (4.0 * n_dot_o);
+            return Color::WHITE * value;
+        }
+
+        let half = self.refraction_half_vector(normal, wo, wi);
+        let (o_dot_h, i_dot_h) = (wo.dot(half), wi.dot(half));
+        let denominator = eta_o * o_dot_h + eta_i * i_dot_h;
+        if o_dot_h <= 0.0 || i_dot_h >= 0.0 || denominator.abs() < 1e-7 {
+            return Color::BLACK;
+        }
+
+        let fresnel = fresnel_dielectric(o_dot_h, eta_o, eta_i);
+        let value = (1.0 - fresnel)
+            * ggx.distribution(normal.dot(half))
+            * ggx.shadowing(n_dot_o, n_dot_i)
+            * eta_i
+            * eta_i
+            * i_dot_h.abs()
+            * o_dot_h
+            / (n_dot_o * denominator * denominator);
+
+        self.color * value
+    }
+
+    fn pdf(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> f32 {
+        let normal = facing(normal, wo);
+        let (eta_o, eta_i) = self.indices(normal, wo);
+        let ggx = Ggx::new(self.roughness);
+
+        if normal.dot(wi) > 0.0 {
+            let half = (wo + wi).normalize();
+            let fresnel = fresnel_dielectric(wo.dot(half), eta_o, eta_i);
+            let n_dot_h = normal.dot(half);
+            return fresnel * ggx.distribution(n_dot_h) * n_dot_h / (4.0 * wo.dot(half).abs());
+        }
+
+        let half = self.refraction_half_vector(normal, wo, wi);
+        let (o_dot_h, i_dot_h) = (wo.dot(half), wi.dot(half));
+        let denominator = eta_o * o_dot_h + eta_i * i_dot_h;
+        if o_dot_h <= 0.0 || i_dot_h >= 0.0 || denominator.abs() < 1e-7 {
+            return 0.0;
+        }
+
+        let fresnel = fresnel_dielectric(o_dot_h, eta_o, eta_i);
+        let n_dot_h = normal.dot(half);
+
+        (1.0 - fresnel) * ggx.distribution(n_dot_h) * n_dot_h * eta_i * eta_i * i_dot_h.abs()
+            / (denominator * denominator)
+    }
+
+    fn sample(&self, normal: Vector3, wo: Vector3, random: &mut Random) -> Option<BsdfSample> {
+        let normal = facing(normal, wo);
+        let (eta_o, eta_i) = self.indices(normal, wo);
+        let ggx = Ggx::new(self.roughness);
+        let half = ggx.sample_half_vector(normal, random);
+        let o_dot_h = wo.dot(half);
+        if o_dot_h <= 0.0 {
+            return None;
+        }
+
+        // Choose between the reflection and the refraction with the Fresnel reflectance
+        let fresnel = fresnel_dielectric(o_dot_h, eta_o, eta_i);
+        if random.next_f32() < fresnel {
+            let direction = mirror(wo, half);
+            if normal.dot(direction) <= 0.0 {
+                return None;
+            }
+
+            return Some(BsdfSample {
+                direction,
+                weight: Color::WHITE * ggx.sample_weight(normal, wo, direction, half),
+            });
+        }
+
+        let direction = (-wo).refract(half, eta_o / eta_i)?.normalize();
+        if normal.dot(direction) >= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            direction,
+            weight: self.color * ggx.sample_weight(normal, wo, direction, half),
+        })
+    }
+
+    fn albedo(&self) -> Color {
+        self.color
+    }
+
+    fn specular_weights(&self, normal: Vector3, wo: Vector3) -> (Color, Color) {
+        let (eta_o, eta_i) = self.indices(normal, wo);
+        let fresnel = fresnel_dielectric(normal.dot(wo).abs(), eta_o, eta_i);
+
+        (Color::WHITE * fresnel, self.color * (1.0 - fresnel))
+    }
+
+    fn refractive_index(&self) -> f32 {
+        self.refractive_index
+    }
+}
+
+/**
+ * @brief A layered material driven by a few artist-friendly parameters
+ *
+ * Blends a metal, a rough glass, and a diffuse base under a specular coat.
+ */
+#[derive(Clone, Copy)]
+pub struct Principled {
+    pub color: Color,
+
+    /**
+     * @brief The fraction of metal, from 0 to 1
+     */
+    pub metallic: f32,
+
+    pub roughness: f32,
+
+    /**
+     * @brief The strength of the coat, 1 for a reflectance of 8% at normal incidence
+     */
+    pub specular: f32,
+
+    /**
+     * @brief The fraction of the non metallic part letting the light through
+     */
+    pub transmission: f32,
+
+    pub refractive_index: f32,
+}
+
+impl Principled {
+    fn metal(&self) -> Conductor {
+        Conductor {
+            color: self.color,
+            roughness: self.roughness,
+        }
+    }
+
+    fn glass(&self) -> Dielectric {
+        Dielectric {
+            color: self.color,
+            roughness: self.roughness,
+            refractive_index: self.refractive_index,
+        }
+    }
+
+    fn coat(&self) -> Conductor {
+        let f0 = 0.08 * self.specular;
+
+        Conductor {
+            color: Color::new(f0, f0, f0),
+            roughness: self.roughness,
+        }
+    }
+
+    /**
+     * @brief The diffuse base, receiving the light not reflected by the coat
+     */
+    fn base(&self) -> Lambertian {
+        Lambertian {
+            color: self.color * (1.0 - 0.08 * self.specular),
+        }
+    }
+
+    /**
+     * @brief Get the weights of the metal, glass, coat and base layers
+     */
+    fn weights(&self) -> [f32; 4] {
+        let dielectric = 1.0 - self.metallic;
+        let opaque = dielectric * (1.0 - self.transmission);
+
+        [
+            self.metallic,
+            dielectric * self.transmission,
+            opaque,
+            opaque,
+        ]
+    }
+
+    /**
+     * @brief Get the probabilities to sample the metal, glass, coat and base layers
+     */
+    fn probabilities(&self) -> [f32; 4] {
+        let [metal, glass, coat, base] = self.weights();
+
+        [metal, glass, coat * 0.5, base * 0.5]
+    }
+}
+
+impl Bsdf for Principled {
+    fn evaluate(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> Color {
+        let [metal, glass, coat, base] = self.weights();
+
+        self.metal().evaluate(normal, wo, wi) * metal
+            + self.glass().evaluate(normal, wo, wi) * glass
+            + self.coat().evaluate(normal, wo, wi) * coat
+            + self.base().evaluate(normal, wo, wi) * base
+    }
+
+    fn pdf(&self, normal: Vector3, wo: Vector3, wi: Vector3) -> f32 {
+        let [metal, glass, coat, base] = self.probabilities();
+
+        metal * self.metal().pdf(normal, wo, wi)
+            + glass * self.glass().pdf(normal, wo, wi)
+            + coat * self.coat().pdf(normal, wo, wi)
+            + base * self.base().pdf(normal, wo, wi)
+    }
+
+    /**
+     * @brief Sample a layer, then weight the direction with all the layers
+     */
+    fn sample(&self, normal: Vector3, wo: Vector3, random: &mut Random) -> Option<BsdfSample> {
+        let [metal, glass, coat, _] = self.probabilities();
+        let choice = random.next_f32();
+        let sample = if choice < metal {
+            self.metal().sample(normal, wo, random)
+        } else if choice < metal + glass {
+            self.glass().sample(normal, wo, random)
+        } else if choice < metal + glass + coat {
+            self.coat().sample(normal, wo, random)
+        } else {
+            self.base().sample(normal, wo, random)
+        }?;
+
+        let pdf = self.pdf(normal, wo, sample.direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            direction: sample.direction,
+            weight: self.evaluate(normal, wo, sample.direction) * (1.0 / pdf),
+        })
+    }
+
+    fn albedo(&self) -> Color {
+        self.color
+    }
+
+    fn specular_weights(&self, normal: Vector3, wo: Vector3) -> (Color, Color) {
+        let [metal, glass, coat, _] = self.weights();
+        let (metal_reflection, _) = self.metal().specular_weights(normal, wo);
+        let (glass_reflection, glass_refraction) = self.glass().specular_weights(normal, wo);
+        let (coat_reflection, _) = self.coat().specular_weights(normal, wo);
+
+        (
+            metal_reflection * metal + glass_reflection * glass + coat_reflection * coat,
+            glass_refraction * glass,
+        )
+    }
+
+    fn refractive_index(&self) -> f32 {
+        self.refractive_index
+    }
+}
+
+/**
+ * @brief One of the BSDFs, stored by value in the materials
+ */
+#[derive(Clone, Copy)]
+pub enum BsdfModel {
+    Lambertian(Lambertian),
+    Conductor(Conductor),
+    Dielectric(Dielectric),
+    Principled(Principled),
+}
+
+impl BsdfModel {
+    pub fn as_bsdf(&self) -> &dyn Bsdf {
+        match self {
+            Self::Lambertian(bsdf) => bsdf,
+            Self::Conductor(bsdf) => bsdf,
+            Self::Dielectric(bsdf) => bsdf,
+            Self::Principled(bsdf) => bsdf,
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use std::any::Any;
+
+use crate::{
+    aabb::Aabb,
+    csg::union_intervals,
+    material::Material,
+    math::Vector3,
+    ray::{Hit, Interval, Ray},
+    sdf::{nearest_distance, nearest_material, Node, SdfNode},
+    stats,
+};
+
+/**
+ * @brief Maximal number of primitives in a leaf of the hierarchy
+ */
+const MAX_LEAF_SIZE: usize = 4;
+
+/**
+ * @brief Number of buckets used to evaluate the surface area heuristic
+ */
+const SAH_BUCKETS: usize = 12;
+
+/**
+ * @brief Relative cost of a ray/box test compared to a ray/primitive test
+ */
+const TRAVERSAL_COST: f32 = 0.125;
+
+enum BvhEntryKind {
+    /**
+     * @brief A leaf referencing the range [first, first + count) of the primitives
+     */
+    Leaf { first: usize, count: usize },
+
+    /**
+     * @brief An interior node, the first child is the next entry
+     */
+    Interior { second_child: usize, axis: usize },
+}
+
+struct BvhEntry {
+    bounds: Aabb,
+    kind: BvhEntryKind,
+}
+
+/**
+ * @brief A primitive being sorted during the build
+ */
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vector3,
+}
+
+/**
+ * @brief A bounding volume hierarchy over a list of primitives
+ *
+ * The hierarchy is built with the surface area heuristic and stored
+ * flattened in depth-first order. It only knows the bounding boxes of
+ * the primitives, the owner intersects the primitives themselves.
+ */
+pub struct Bvh {
+    entries: Vec<BvhEntry>,
+}
+
+impl Bvh {
+    /**
+     * @brief Build the hierarchy over the bounding boxes of primitives
+     *
+     * @param bounds the finite bounding boxes of the primitives
+     *
+     * @return the hierarchy and the order in which the owner must store its
+     * primitives, as indices in bounds
+     */
+    pub fn build(bounds: &[Aabb]) -> (Self, Vec<usize>) {
+        let mut items: Vec<BuildItem> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, bounds)| BuildItem {
+                index,
+                bounds: *bounds,
+                centroid: bounds.centroid(),
+            })
+            .collect();
+
+        let mut entries = Vec::new();
+        if !items.is_empty() {
+            Self::build_recursive(&mut items, 0, &mut entries);
+        }
+
+        (
+            Self { entries },
+            items.into_iter().map(|item| item.index).collect(),
+        )
+    }
+
+    /**
+     * @brief The bounding box of all the primitives
+     */
+    pub fn bounds(&self) -> Aabb {
+        self.entries
+            .first()
+            .map_or(Aabb::EMPTY, |entry| entry.bounds)
+    }
+
+    /**
+     * @brief Recursively build the hierarchy
+     *
+     * @param items the primitives to split, reordered in place
+     * @param first the index of the first item in the final list of primitives
+     * @param entries the flattened hierarchy
+     */
+    fn build_recursive(items: &mut [BuildItem], first: usize, entries: &mut Vec<BvhEntry>) {
+        let bounds = items
+            .iter()
+            .map(|item| item.bounds)
+            .fold(Aabb::EMPTY, Aabb::union);
+        let index = entries.len();
+        entries.push(BvhEntry {
+            bounds,
+            kind: BvhEntryKind::Leaf {
+                first,
+                count: items.len(),
+            },
+        });
+        if items.len() <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        // Split along the widest axis of the centroids
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::EMPTY, |acc, item| acc.grow(item.centroid));
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis = if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        };
+        if extent[axis] <= 0.0 {
+            return;
+        }
+
+        // Find the cheapest split with the surface area heuristic
+        let bucket_of = |item: &BuildItem| {
+            let offset = (item.centroid[axis] - centroid_bounds.min[axis]) / extent[axis];
+            ((offset * SAH_BUCKETS as f32) as usize).min(SAH_BUCKETS - 1)
+        };
+        let mut buckets = [(0usize, Aabb::EMPTY); SAH_BUCKETS];
+        for item in items.iter() {
+            let bucket = &mut buckets[bucket_of(item)];
+            bucket.0 += 1;
+            bucket.1 = bucket.1.union(item.bounds);
+        }
+        let (mut best_split, mut best_cost) = (0, f32::INFINITY);
+        for split in 1..SAH_BUCKETS {
+            let (left, right) = buckets.split_at(split);
+            let (left_count, left_bounds) = left
+                .iter()
+                .fold((0, Aabb::EMPTY), |acc, b| (acc.0 + b.0, acc.1.union(b.1)));
+            let (right_count, right_bounds) = right
+                .iter()
+                .fold((0, Aabb::EMPTY), |acc, b| (acc.0 + b.0, acc.1.union(b.1)));
+            let cost = TRAVERSAL_COST
+                + (left_count as f32 * left_bounds.surface_area()
+                    + right_count as f32 * right_bounds.surface_area())
+                    / bounds.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        // Keep a leaf if splitting isn't worth it
+        if best_cost >= items.len() as f32 && items.len() <= MAX_LEAF_SIZE * 4 {
+            return;
+        }
+
+        // Partition the items, fallback to the median if a side is empty
+        items.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        let mut middle = items.partition_point(|item| bucket_of(item) < best_split);
+        if middle == 0 || middle == items.len() {
+            middle = items.len() / 2;
+        }
+
+        let (left, right) = items.split_at_mut(middle);
+        Self::build_recursive(left, first, entries);
+        let second_child = entries.len();
+        Self::build_recursive(right, first + middle, entries);
+        entries[index].kind = BvhEntryKind::Interior { second_child, axis };
+    }
+
+    /**
+     * @brief Find the nearest hit of a ray
+     *
+     * @param ray the ray
+     * @param nearest the nearest hit already known, the farther primitives are skipped
+     * @param hit_primitive intersect the ray with the primitive of the given index
+     */
+    pub fn hit<F>(&self, ray: &Ray, mut nearest: Option<Hit>, mut hit_primitive: F) -> Option<Hit>
+    where
+        F: FnMut(usize) -> Option<Hit>,
+    {
+        if self.entries.is_empty() {
+            return nearest;
+        }
+
+        // Traverse the hierarchy front to back
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let entry = &self.entries[index];
+            let max_distance = nearest.as_ref().map_or(f32::INFINITY, |hit| hit.distance);
+            stats::count_ray_box_test();
+            if entry.bounds.hit(ray, max_distance).is_none() {
+                continue;
+            }
+
+            match entry.kind {
+                BvhEntryKind::Leaf { first, count } => {
+                    for primitive in first..first + count {
+                        if let Some(hit) = hit_primitive(primitive) {
+                            if nearest.as_ref().is_none_or(|n| hit.distance < n.distance) {
+                                nearest = Some(hit);
+                            }
+                        }
+                    }
+                }
+                BvhEntryKind::Interior { second_child, axis } => {
+                    // Push the far child first to visit the near one first
+                    if ray.direction[axis] < 0.0 {
+                        stack.push(index + 1);
+                        stack.push(second_child);
+                    } else {
+                        stack.push(second_child);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+
+        nearest
+    }
+
+    /**
+     * @brief Visit all the primitives whose bounding box crosses the line of a ray
+     *
+     * @param ray the ray, extended in both directions
+     * @param visit called with the index of each candidate primitive
+     */
+    pub fn visit_line<F>(&self, ray: &Ray, mut visit: F)
+    where
+        F: FnMut(usize),
+    {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let entry = &self.entries[index];
+            stats::count_ray_box_test();
+            if entry
+                .bounds
+                .hit_range(ray, f32::NEG_INFINITY, f32::INFINITY)
+                .is_none()
+            {
+                continue;
+            }
+
+            match entry.kind {
+                BvhEntryKind::Leaf { first, count } => (first..first + count).for_each(&mut visit),
+                BvhEntryKind::Interior { second_child, .. } => {
+                    stack.push(second_child);
+                    stack.push(index + 1);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * @brief A bounding volume hierarchy over a list of nodes
+ *
+ * Behave like an UnionNode, but only tests the nodes whose bounding
+ * box is hit by the ray.
+ */
+pub struct BvhNode {
+    /**
+     * @brief The bounded nodes, ordered by leaf
+     */
+    pub nodes: Vec<Box<dyn Node>>,
+
+    /**
+     * @brief The nodes without a finite bounding box, tested for every ray
+     */
+    pub unbounded_nodes: Vec<Box<dyn Node>>,
+
+    bvh: Bvh,
+    bounds: Aabb,
+}
+
+impl BvhNode {
+    /**
+     * @brief Build the hierarchy over a list of nodes
+     *
+     * @param nodes the nodes, in any order
+     */
+    pub fn new(nodes: Vec<Box<dyn Node>>) -> Self {
+        let (bounded_nodes, unbounded_nodes): (Vec<_>, Vec<_>) = nodes
+            .into_iter()
+            .partition(|node| node.bounding_box().is_finite());
+
+        let bounds: Vec<Aabb> = bounded_nodes
+            .iter()
+            .map(|node| node.bounding_box())
+            .collect();
+        let (bvh, order) = Bvh::build(&bounds);
+        let mut bounded_nodes: Vec<Option<Box<dyn Node>>> =
+            bounded_nodes.into_iter().map(Some).collect();
+        let nodes: Vec<Box<dyn Node>> = order
+            .into_iter()
+            .map(|index| bounded_nodes[index].take().unwrap())
+            .collect();
+
+        let bounds = unbounded_nodes
+            .iter()
+            .map(|node| node.bounding_box())
+            .fold(bvh.bounds(), Aabb::union);
+
+        Self {
+            nodes,
+            unbounded_nodes,
+            bvh,
+            bounds,
+        }
+    }
+}
+
+impl Node for BvhNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let nearest = self
+            .unbounded_nodes
+            .iter()
+            .filter_map(|node| node.hit(ray))
+            .reduce(|acc, e| if acc.distance < e.distance { acc } else { e });
+
+        self.bvh
+            .hit(ray, nearest, |index| self.nodes[index].hit(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut intervals: Vec<Interval> = self
+            .unbounded_nodes
+            .iter()
+            .flat_map(|node| node.intervals(ray))
+            .collect();
+        self.bvh.visit_line(ray, |index| {
+            intervals.extend(self.nodes[index].intervals(ray))
+        });
+
+        union_intervals(intervals)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+/**
+ * @brief The field of the union, evaluated over all the nodes
+ */
+impl SdfNode for BvhNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        nearest_distance(self.nodes.iter().chain(&self.unbounded_nodes), point)
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        nearest_material(self.nodes.iter().chain(&self.unbounded_nodes), point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::sdf::{SphereNode, UnionNode};
+
+    /**
+     * @brief A linear congruential generator, enough to scatter the spheres
+     */
+    fn random_numbers(seed: u64) -> impl FnMut() -> f32 {
+        let mut state = seed;
+        move || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 40) as f32 / (1u64 << 24) as f32
+        }
+    }
+
+    fn random_point(random: &mut impl FnMut() -> f32, size: f32) -> Vector3 {
+        Vector3::new(random() - 0.5, random() - 0.5, random() - 0.5) * size
+    }
+
+    fn random_spheres(count: usize) -> Vec<Box<dyn Node>> {
+        let mut random = random_numbers(7);
+        (0..count)
+            .map(|_| {
+                Box::new(SphereNode {
+                    position: random_point(&mut random, 20.0),
+                    radius: 0.2 + random(),
+                    material: Material::DEBUG_MATERIAL,
+                }) as Box<dyn Node>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bvh_hits_like_a_linear_union() {
+        let union = UnionNode {
+            nodes: random_spheres(200),
+        };
+        let bvh = BvhNode::new(random_spheres(200));
+
+        let mut random = random_numbers(11);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = random_point(&mut random, 40.0);
+            let target = random_point(&mut random, 20.0);
+            let ray = Ray {
+                origin,
+                direction: (target - origin).normalize(),
+                spread: 0.0,
+            };
+
+            match (union.hit(&ray), bvh.hit(&ray)) {
+                (Some(expected), Some(hit)) => {
+                    assert!((expected.distance - hit.distance).abs() < 1e-4);
+                    assert!((expected.position - hit.position).length() < 1e-3);
+                    hits += 1;
+                }
+                (None, None) => {}
+                (expected, hit) => panic!(
+                    "the union hits {}, the BVH hits {}",
+                    expected.is_some(),
+                    hit.is_some()
+                ),
+            }
+        }
+
+        // Enough rays hit a sphere for the test to mean something
+        assert!(hits > 200, "{} hits", hits);
+    }
+}
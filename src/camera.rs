@@ -1,21 +1,188 @@
-use crate::math::Vector3;
+use crate::{math::Vector3, ray::Ray};
 
 pub struct Camera {
     pub position: Vector3,
-    pub view_port: Vector3,
+    pub direction: Vector3,
+    pub up: Vector3,
+    pub fov: f32,
+    pub roll: f32,
+
+    /**
+     * @brief The legacy view port and its distance, None to frame with the field of view
+     *
+     * The rays go through the view port like in the first versions of the
+     * renderer: both axes are divided by the width of the canvas, so a canvas
+     * wider than high only shows the bottom of the view port, and the rays go
+     * through the top left corners of the pixels.
+     */
+    pub view_port: Option<Vector3>,
 }
 
 impl Camera {
-    pub fn new(position: Vector3, view_port: Vector3) -> Self {
+    /**
+     * @brief Vertical field of view of the legacy 1x1 view port at a distance of 1
+     */
+    pub const DEFAULT_FOV: f32 = 53.130_1;
+
+    /**
+     * @brief Create a new camera
+     *
+     * @param position the position of the camera
+     * @param direction the direction the camera is looking at
+     * @param up the up vector of the camera, doesn't need to be orthogonal to the direction
+     * @param fov the vertical field of view, in degrees
+     * @param roll the rotation of the camera around its direction, in degrees
+     */
+    pub fn new(position: Vector3, direction: Vector3, up: Vector3, fov: f32, roll: f32) -> Self {
         Self {
             position,
-            view_port,
+            direction,
+            up,
+            fov,
+            roll,
+            view_port: None,
+        }
+    }
+
+    /**
+     * @brief Create a camera looking at a target
+     *
+     * @param position the position of the camera
+     * @param target the point the camera is looking at
+     * @param up the up vector of the camera
+     * @param fov the vertical field of view, in degrees
+     * @param roll the rotation of the camera around its direction, in degrees
+     */
+    pub fn look_at(position: Vector3, target: Vector3, up: Vector3, fov: f32, roll: f32) -> Self {
+        Self::new(position, target - position, up, fov, roll)
+    }
+
+    /**
+     * @brief Create a camera from Euler angles
+     *
+     * A camera with a null yaw and pitch looks toward +Z
+     *
+     * @param position the position of the camera
+     * @param yaw the rotation around the Y axis, in degrees
+     * @param pitch the rotation around the X axis, in degrees
+     * @param roll the rotation of the camera around its direction, in degrees
+     * @param fov the vertical field of view, in degrees
+     */
+    pub fn from_angles(position: Vector3, yaw: f32, pitch: f32, roll: f32, fov: f32) -> Self {
+        let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+        let direction = Vector3::new(
+            yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            yaw.cos() * pitch.cos(),
+        );
+
+        Self::new(position, direction, Vector3::new(0.0, 1.0, 0.0), fov, roll)
+    }
+
+    /**
+     * @brief Create a camera looking toward +Z from a legacy view port
+     *
+     * The view port replaces the field of view, which is left to its default.
+     *
+     * @param position the position of the camera
+     * @param view_port the size of the view port and its distance to the camera
+     */
+    pub fn from_view_port(position: Vector3, view_port: Vector3) -> Self {
+        Self {
+            view_port: Some(view_port),
+            ..Self::new(
+                position,
+                Vector3::new(0.0, 0.0, 1.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Self::DEFAULT_FOV,
+                0.0,
+            )
+        }
+    }
+
+    /**
+     * @brief Compute the orthonormal basis of the camera
+     *
+     * @return the right, up and forward vectors
+     */
+    pub fn basis(&self) -> (Vector3, Vector3, Vector3) {
+        let forward = self.direction.normalize();
+        let right = self.up.cross(forward);
+        // Any up vector works when looking along the up vector
+        let right = if right.length() > 1e-6 * self.up.length() {
+            right.normalize()
+        } else {
+            forward.orthonormal_basis().0
+        };
+        let up = forward.cross(right);
+
+        // Apply the roll
+        let (sin, cos) = self.roll.to_radians().sin_cos();
+        (right * cos + up * sin, up * cos - right * sin, forward)
+    }
+
+    /**
+     * @brief Build the ray going through a point of the canvas
+     *
+     * @param x X coordinate on the canvas, in pixels
+     * @param y Y coordinate on the canvas, in pixels
+     * @param width the width of the canvas
+     * @param height the height of the canvas
+     */
+    pub fn ray(&self, x: f32, y: f32, width: u32, height: u32) -> Ray {
+        let (right, up, forward) = self.basis();
+
+        if let Some(view_port) = self.view_port {
+            // The mapping of the first versions, from the corner of the pixel
+            let dx = ((x - 0.5) / width as f32 - 0.5) * view_port.x / view_port.z;
+            let dy = ((y - 0.5) / width as f32 - 0.5) * view_port.y / view_port.z;
+
+            return Ray {
+                origin: self.position,
+                direction: forward + right * dx + up * dy,
+                spread: view_port.y / view_port.z / width as f32,
+            };
+        }
+
+        let half_height = (self.fov.to_radians() / 2.0).tan();
+        let half_width = half_height * width as f32 / height as f32;
+        let ndc_x = 2.0 * x / width as f32 - 1.0;
+        let ndc_y = 2.0 * y / height as f32 - 1.0;
+
+        Ray {
+            origin: self.position,
+            direction: forward + right * (ndc_x * half_width) + up * (ndc_y * half_height),
+            spread: 2.0 * half_height / height as f32,
         }
     }
 }
 
 impl Default for Camera {
     fn default() -> Self {
-        Self::new(Vector3::default(), Vector3::new(1.0, 1.0, 1.0))
+        Self::from_view_port(Vector3::default(), Vector3::new(1.0, 1.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_port_maps_the_pixels_like_the_first_versions() {
+        let view_port = Vector3::new(2.0, 1.5, 1.0);
+        let camera = Camera::from_view_port(Vector3::default(), view_port);
+        let (width, height) = (64, 48);
+
+        for (u, v) in [(0, 0), (63, 0), (32, 24), (10, 47)] {
+            let ray = camera.ray(u as f32 + 0.5, v as f32 + 0.5, width, height);
+            let expected = Vector3::new(
+                u as f32 / width as f32 - 0.5,
+                v as f32 / width as f32 - 0.5,
+                1.0,
+            ) * view_port;
+
+            let direction = ray.direction.normalize();
+            assert!((direction - expected.normalize()).length() < 1e-5);
+        }
     }
 }
@@ -1,5 +1,4 @@
 use crate::color::Color;
-use std::iter::repeat;
 
 /**
  * @brief Interface for a canvas, a surface on which we can draw
@@ -38,6 +37,64 @@ pub trait Canvas {
      * @return the height of the canvas
      */
     fn height(&self) -> u32;
+
+    /**
+     * @brief Draw a rendered tile on the canvas
+     *
+     * @param tile the tile to draw
+     */
+    fn set_tile(&mut self, tile: &Tile) {
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                self.set_pixel(tile.x + x, tile.y + y, tile.get_pixel(x, y));
+            }
+        }
+    }
+}
+
+/**
+ * @brief A rectangular part of a canvas, rendered independently
+ */
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Tile {
+    /**
+     * @brief Create a new tile
+     *
+     * @param x X coordinate of the bottom left corner of the tile in the canvas
+     * @param y Y coordinate of the bottom left corner of the tile in the canvas
+     * @param width the width of the tile
+     * @param height the height of the tile
+     */
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            pixels: vec![Color::MAGENTA; (width * height) as usize],
+        }
+    }
+
+    /**
+     * @brief Get a pixel of the tile, relatively to the tile
+     */
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /**
+     * @brief Set a pixel of the tile, relatively to the tile
+     */
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
 }
 
 pub struct FrameBufferCanvas {
@@ -49,11 +106,9 @@ pub struct FrameBufferCanvas {
 impl FrameBufferCanvas {
     pub fn new(width: u32, height: u32) -> Self {
         Self {
-            width: width,
-            height: height,
-            pixels: repeat(Color::MAGENTA)
-                .take((width * height) as usize)
-                .collect(),
+            width,
+            height,
+            pixels: vec![Color::MAGENTA; (width * height) as usize],
         }
     }
 }
@@ -76,4 +131,54 @@ impl Canvas for FrameBufferCanvas {
     fn height(&self) -> u32 {
         self.height
     }
+
+    fn set_tile(&mut self, tile: &Tile) {
+        for y in 0..tile.height {
+            let offset = ((tile.y + y) * self.width + tile.x) as usize;
+            let row = &tile.pixels[(y * tile.width) as usize..((y + 1) * tile.width) as usize];
+            self.pixels[offset..offset + tile.width as usize].copy_from_slice(row);
+        }
+    }
+}
+
+/**
+ * @brief Named canvases of the same size, like the passes of a render
+ */
+pub struct LayeredCanvas {
+    pub width: u32,
+    pub height: u32,
+    pub layers: Vec<(String, FrameBufferCanvas)>,
+}
+
+impl LayeredCanvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            layers: Vec::new(),
+        }
+    }
+
+    /**
+     * @brief Add a layer, or replace the layer with the same name
+     */
+    pub fn insert(&mut self, name: &str, canvas: FrameBufferCanvas) {
+        assert!(canvas.width() == self.width && canvas.height() == self.height);
+
+        match self
+            .layers
+            .iter_mut()
+            .find(|(layer_name, _)| layer_name == name)
+        {
+            Some((_, layer)) => *layer = canvas,
+            None => self.layers.push((name.to_string(), canvas)),
+        }
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&FrameBufferCanvas> {
+        self.layers
+            .iter()
+            .find(|(layer_name, _)| layer_name == name)
+            .map(|(_, layer)| layer)
+    }
 }
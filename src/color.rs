@@ -1,3 +1,6 @@
+use crate::display::srgb_decode;
+use std::iter::Sum;
+use std::ops::Add;
 use std::ops::Mul;
 
 /**
@@ -54,6 +57,60 @@ impl Color {
     pub fn new(r: f32, g: f32, b: f32) -> Self {
         Self { r, g, b }
     }
+
+    /**
+     * @brief Clamp each component of the color
+     */
+    pub fn clamp(self, min: f32, max: f32) -> Self {
+        Self::new(
+            self.r.clamp(min, max),
+            self.g.clamp(min, max),
+            self.b.clamp(min, max),
+        )
+    }
+
+    /**
+     * @brief Get the perceived brightness of a linear color, with the Rec. 709 weights
+     */
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /**
+     * @brief Parse an hexadecimal sRGB color, like #ff8800 or #f80, to a linear color
+     */
+    pub fn from_hex(text: &str) -> Option<Self> {
+        let digits = text.strip_prefix('#')?;
+        // The sign accepted by from_str_radix isn't a digit
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+        let [r, g, b] = match digits.len() {
+            3 => [0, 1, 2].map(|i| channel(&digits[i..i + 1]).map(|x| x * 17)),
+            6 => [0, 2, 4].map(|i| channel(&digits[i..i + 2])),
+            _ => return None,
+        };
+        let decode = |x: u8| srgb_decode(x as f32 / 255.0);
+
+        Some(Self::new(decode(r?), decode(g?), decode(b?)))
+    }
+}
+
+impl Add<Color> for Color {
+    type Output = Self;
+
+    fn add(self, o: Self) -> Self::Output {
+        Self::Output::new(self.r + o.r, self.g + o.g, self.b + o.b)
+    }
+}
+
+impl Mul<Color> for Color {
+    type Output = Self;
+
+    fn mul(self, o: Self) -> Self::Output {
+        Self::Output::new(self.r * o.r, self.g * o.g, self.b * o.b)
+    }
 }
 
 impl Mul<f32> for Color {
@@ -63,3 +120,42 @@ impl Mul<f32> for Color {
         Self::Output::new(self.r * o, self.g * o, self.b * o)
     }
 }
+
+impl Sum for Color {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::BLACK, |acc, color| acc + color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(color: Color) -> [f32; 3] {
+        [color.r, color.g, color.b]
+    }
+
+    #[test]
+    fn from_hex_decodes_the_srgb_channels() {
+        let color = Color::from_hex("#ff8000").unwrap();
+        assert_eq!(color.r, 1.0);
+        assert!((color.g - srgb_decode(128.0 / 255.0)).abs() < 1e-6);
+        assert_eq!(color.b, 0.0);
+    }
+
+    #[test]
+    fn from_hex_expands_the_short_form() {
+        let short = Color::from_hex("#f80").unwrap();
+        let long = Color::from_hex("#FF8800").unwrap();
+        assert_eq!(channels(short), channels(long));
+    }
+
+    #[test]
+    fn from_hex_rejects_the_invalid_colors() {
+        for text in [
+            "ff8800", "#ff880", "#ff88000", "#gg8800", "#", "#ffé00", "#+f+f+f",
+        ] {
+            assert!(Color::from_hex(text).is_none(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::any::Any;
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    math::Vector3,
+    ray::{Hit, Interval, Ray},
+    sdf::{Node, SdfNode},
+};
+
+/**
+ * @brief Get the first surface in front of the ray
+ *
+ * @param intervals sorted and disjoint intervals
+ */
+pub fn first_hit(intervals: &[Interval]) -> Option<Hit> {
+    intervals
+        .iter()
+        .flat_map(|interval| [interval.enter, interval.exit])
+        .find(|hit| hit.distance >= 0.0)
+        .filter(|hit| hit.distance.is_finite())
+}
+
+/**
+ * @brief Pair the sorted intersections of a ray with a closed surface
+ *
+ * @param hits the intersections, sorted by distance
+ */
+pub fn pair_intervals(hits: &[Hit]) -> Vec<Interval> {
+    hits.chunks_exact(2)
+        .map(|pair| Interval {
+            enter: pair[0],
+            exit: pair[1],
+        })
+        .collect()
+}
+
+/**
+ * @brief Flip the normal of a hit, for the surfaces carved by a difference
+ */
+fn flip(hit: Hit) -> Hit {
+    Hit {
+        normal: -hit.normal,
+        ..hit
+    }
+}
+
+/**
+ * @brief Merge intervals into sorted and disjoint intervals
+ *
+ * @param intervals the intervals, in any order
+ */
+pub fn union_intervals(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort_by(|a, b| a.enter.distance.total_cmp(&b.enter.distance));
+
+    let mut result: Vec<Interval> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        match result.last_mut() {
+            Some(last) if interval.enter.distance <= last.exit.distance => {
+                if interval.exit.distance > last.exit.distance {
+                    last.exit = interval.exit;
+                }
+            }
+            _ => result.push(interval),
+        }
+    }
+
+    result
+}
+
+/**
+ * @brief Intersect two lists of sorted and disjoint intervals
+ */
+pub fn intersect_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let enter = if a[i].enter.distance > b[j].enter.distance {
+            a[i].enter
+        } else {
+            b[j].enter
+        };
+        let exit = if a[i].exit.distance < b[j].exit.distance {
+            a[i].exit
+        } else {
+            b[j].exit
+        };
+        if enter.distance < exit.distance {
+            result.push(Interval { enter, exit });
+        }
+
+        // Move forward the interval ending first
+        if a[i].exit.distance < b[j].exit.distance {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    result
+}
+
+/**
+ * @brief Subtract a list of sorted and disjoint intervals from an another
+ */
+pub fn subtract_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    let mut result = Vec::new();
+    for interval in a {
+        // The part of the interval not yet covered by b
+        let mut start = Some(interval.enter);
+        for removed in b {
+            let current = match start {
+                Some(current) => current,
+                None => break,
+            };
+            if removed.exit.distance <= current.distance {
+                continue;
+            }
+            if removed.enter.distance >= interval.exit.distance {
+                break;
+            }
+
+            if removed.enter.distance > current.distance {
+                result.push(Interval {
+                    enter: current,
+                    exit: flip(removed.enter),
+                });
+            }
+            start = if removed.exit.distance < interval.exit.distance {
+                Some(flip(removed.exit))
+            } else {
+                None
+            };
+        }
+
+        if let Some(start) = start {
+            result.push(Interval {
+                enter: start,
+                exit: interval.exit,
+            });
+        }
+    }
+
+    result
+}
+
+/**
+ * @brief The volume common to all the nodes
+ */
+pub struct IntersectionNode {
+    pub nodes: Vec<Box<dyn Node>>,
+}
+
+impl Node for IntersectionNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        first_hit(&self.intervals(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut nodes = self.nodes.iter();
+        let first = match nodes.next() {
+            Some(node) => node.intervals(ray),
+            None => return Vec::new(),
+        };
+
+        nodes.fold(first, |acc, node| {
+            if acc.is_empty() {
+                acc
+            } else {
+                intersect_intervals(&acc, &node.intervals(ray))
+            }
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .iter()
+            .map(|node| node.bounding_box())
+            .reduce(Aabb::intersection)
+            .unwrap_or(Aabb::EMPTY)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl IntersectionNode {
+    /**
+     * @brief Find the field of the nodes farthest from a point
+     */
+    fn farthest_field(&self, point: Vector3) -> Option<(&dyn SdfNode, f32)> {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.as_sdf())
+            .map(|field| (field, field.distance(point)))
+            .reduce(|acc, e| if acc.1 >= e.1 { acc } else { e })
+    }
+}
+
+impl SdfNode for IntersectionNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        self.farthest_field(point)
+            .map_or(f32::INFINITY, |(_, distance)| distance)
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        self.farthest_field(point)
+            .map_or(Material::DEBUG_MATERIAL, |(field, _)| field.material(point))
+    }
+}
+
+/**
+ * @brief The volume of the first node, minus the volume of the other nodes
+ */
+pub struct DifferenceNode {
+    pub nodes: Vec<Box<dyn Node>>,
+}
+
+impl Node for DifferenceNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        first_hit(&self.intervals(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut nodes = self.nodes.iter();
+        let first = match nodes.next() {
+            Some(node) => node.intervals(ray),
+            None => return Vec::new(),
+        };
+
+        nodes.fold(first, |acc, node| {
+            if acc.is_empty() {
+                acc
+            } else {
+                subtract_intervals(&acc, &node.intervals(ray))
+            }
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or(Aabb::EMPTY, |node| node.bounding_box())
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl DifferenceNode {
+    /**
+     * @brief Find the field defining the surface near a point
+     *
+     * @return the field and the distance of the difference
+     */
+    fn active_field(&self, point: Vector3) -> Option<(&dyn SdfNode, f32)> {
+        let mut fields = self.nodes.iter().filter_map(|node| node.as_sdf());
+        let first = fields.next()?;
+
+        // The removed volumes are the outside of their fields
+        Some(fields.fold((first, first.distance(point)), |acc, field| {
+            let distance = -field.distance(point);
+            if distance > acc.1 {
+                (field, distance)
+            } else {
+                acc
+            }
+        }))
+    }
+}
+
+impl SdfNode for DifferenceNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        self.active_field(point)
+            .map_or(f32::INFINITY, |(_, distance)| distance)
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        self.active_field(point)
+            .map_or(Material::DEBUG_MATERIAL, |(field, _)| field.material(point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::math::Vector3;
+
+    fn hit(distance: f32) -> Hit {
+        Hit {
+            position: Vector3::new(0.0, 0.0, distance),
+            normal: Vector3::new(0.0, 0.0, 1.0),
+            distance,
+            material: Material::DEBUG_MATERIAL,
+            uv: [0.0, 0.0],
+            uv_density: 0.0,
+        }
+    }
+
+    fn intervals(spans: &[(f32, f32)]) -> Vec<Interval> {
+        spans
+            .iter()
+            .map(|&(enter, exit)| Interval {
+                enter: hit(enter),
+                exit: hit(exit),
+            })
+            .collect()
+    }
+
+    fn spans(intervals: &[Interval]) -> Vec<(f32, f32)> {
+        intervals
+            .iter()
+            .map(|interval| (interval.enter.distance, interval.exit.distance))
+            .collect()
+    }
+
+    #[test]
+    fn union_merges_the_overlapping_and_nested_spans() {
+        let union = union_intervals(intervals(&[(5.0, 6.0), (0.0, 2.0), (1.0, 3.0), (1.5, 1.8)]));
+        assert_eq!(spans(&union), [(0.0, 3.0), (5.0, 6.0)]);
+    }
+
+    #[test]
+    fn intersection_keeps_the_common_spans() {
+        // An overlapping span, a nested span and a disjoint span
+        let a = intervals(&[(0.0, 2.0), (4.0, 8.0)]);
+        let b = intervals(&[(1.0, 3.0), (5.0, 6.0), (9.0, 10.0)]);
+        assert_eq!(
+            spans(&intersect_intervals(&a, &b)),
+            [(1.0, 2.0), (5.0, 6.0)]
+        );
+
+        assert!(intersect_intervals(&a, &intervals(&[(2.5, 3.5)])).is_empty());
+    }
+
+    #[test]
+    fn subtraction_carves_the_spans_and_flips_their_normals() {
+        let a = intervals(&[(0.0, 2.0), (4.0, 8.0)]);
+        let b = intervals(&[(1.0, 3.0), (5.0, 6.0), (9.0, 10.0)]);
+        let difference = subtract_intervals(&a, &b);
+        assert_eq!(spans(&difference), [(0.0, 1.0), (4.0, 5.0), (6.0, 8.0)]);
+
+        // The carved surfaces face the removed volume
+        assert_eq!(difference[0].enter.normal.z, 1.0);
+        assert_eq!(difference[0].exit.normal.z, -1.0);
+        assert_eq!(difference[2].enter.normal.z, -1.0);
+
+        let span = intervals(&[(1.0, 2.0)]);
+        assert!(subtract_intervals(&span, &intervals(&[(0.0, 3.0)])).is_empty());
+        assert_eq!(
+            spans(&subtract_intervals(&span, &intervals(&[(3.0, 4.0)]))),
+            [(1.0, 2.0)]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::color::Color;
+
+/**
+ * @brief How the colors above 1 are brought back to the displayable range
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    /**
+     * @brief Clip each channel, keeping the colors below 1 untouched
+     */
+    Clamp,
+
+    /**
+     * @brief Compress the channels with x / (1 + x)
+     */
+    Reinhard,
+
+    /**
+     * @brief The Narkowicz fit of the ACES filmic curve
+     */
+    Aces,
+}
+
+impl ToneMapping {
+    /**
+     * @brief Get the operator from its name on the command line or in a scene
+     */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clamp" => Some(Self::Clamp),
+            "reinhard" => Some(Self::Reinhard),
+            "aces" => Some(Self::Aces),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Clamp => "clamp",
+            Self::Reinhard => "reinhard",
+            Self::Aces => "aces",
+        }
+    }
+
+    /**
+     * @brief Map a linear channel to [0, 1]
+     */
+    pub fn apply(&self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        let y = match self {
+            Self::Clamp => x,
+            Self::Reinhard => x / (1.0 + x),
+            Self::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        };
+
+        y.clamp(0.0, 1.0)
+    }
+}
+
+/**
+ * @brief Encode a linear channel in [0, 1] with the sRGB transfer function
+ */
+pub fn srgb_encode(x: f32) -> f32 {
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/**
+ * @brief Decode an sRGB channel in [0, 1] to a linear channel
+ */
+pub fn srgb_decode(x: f32) -> f32 {
+    if x <= 0.040_45 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/**
+ * @brief Turn the linear colors of a render into displayable colors
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayTransform {
+    /**
+     * @brief Exposure adjustment in stops, each stop doubles the light
+     */
+    pub exposure: f32,
+
+    pub tone_mapping: ToneMapping,
+
+    /**
+     * @brief Encode the colors in sRGB, or keep them linear
+     */
+    pub srgb: bool,
+}
+
+impl DisplayTransform {
+    /**
+     * @brief Transform a linear color to a displayable color in [0, 1]
+     */
+    pub fn apply(&self, color: Color) -> Color {
+        let scale = self.exposure.exp2();
+        let transform = |x: f32| {
+            let y = self.tone_mapping.apply(x * scale);
+            if self.srgb {
+                srgb_encode(y)
+            } else {
+                y
+            }
+        };
+
+        Color::new(transform(color.r), transform(color.g), transform(color.b))
+    }
+
+    /**
+     * @brief Transform a linear color to 8-bit channels
+     */
+    pub fn to_rgb8(&self, color: Color) -> [u8; 3] {
+        let color = self.apply(color);
+        let quantize = |x: f32| (x * 255.0 + 0.5) as u8;
+
+        [quantize(color.r), quantize(color.g), quantize(color.b)]
+    }
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_mapping: ToneMapping::Clamp,
+            srgb: true,
+        }
+    }
+}
@@ -0,0 +1,547 @@
+use std::any::Any;
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    math::Vector3,
+    ray::{Hit, Interval, Ray},
+    sdf::{nearest_distance, nearest_material, Node, SdfNode},
+    stats,
+};
+
+/**
+ * @brief Maximal number of steps of the sphere tracing
+ */
+pub const MAX_STEPS: u32 = 256;
+
+/**
+ * @brief Distance to the field under which a point is on the surface
+ */
+pub const HIT_EPSILON: f32 = 1e-4;
+
+/**
+ * @brief Distance after which the unbounded fields are not marched anymore
+ */
+pub const MAX_DISTANCE: f32 = 1000.0;
+
+/**
+ * @brief Offset of the samples used to estimate the gradient of a field
+ */
+const NORMAL_EPSILON: f32 = 1e-3;
+
+/**
+ * @brief Estimate the normal of a field from its gradient
+ *
+ * Uses the tetrahedron technique, with four evaluations of the field
+ * instead of six for the central differences.
+ */
+pub fn estimate_normal(field: &dyn SdfNode, point: Vector3) -> Vector3 {
+    [
+        Vector3::new(1.0, -1.0, -1.0),
+        Vector3::new(-1.0, -1.0, 1.0),
+        Vector3::new(-1.0, 1.0, -1.0),
+        Vector3::new(1.0, 1.0, 1.0),
+    ]
+    .iter()
+    .fold(Vector3::default(), |acc, k| {
+        acc + *k * field.distance(point + *k * NORMAL_EPSILON)
+    })
+    .normalize()
+}
+
+/**
+ * @brief Find the first surface of a field along a segment of a ray with sphere tracing
+ *
+ * The ray may start inside the volume, the surface is then its exit.
+ *
+ * @param field the field
+ * @param ray the ray
+ * @param min_distance the start of the segment
+ * @param max_distance the end of the segment
+ *
+ * @return the distance of the surface
+ */
+pub fn march(field: &dyn SdfNode, ray: &Ray, min_distance: f32, max_distance: f32) -> Option<f32> {
+    let length = ray.direction.length();
+    let mut t = min_distance;
+    for _ in 0..MAX_STEPS {
+        let d = field.distance(ray.origin + ray.direction * t).abs();
+        if d < HIT_EPSILON {
+            return Some(t);
+        }
+
+        t += d / length;
+        if t > max_distance {
+            return None;
+        }
+    }
+
+    None
+}
+
+/**
+ * @brief Build the hit of a ray with the surface of a field
+ */
+pub fn field_hit(field: &dyn SdfNode, ray: &Ray, distance: f32) -> Hit {
+    let position = ray.origin + ray.direction * distance;
+
+    Hit {
+        position,
+        normal: estimate_normal(field, position),
+        distance,
+        material: field.material(position),
+        uv: [0.0, 0.0],
+        uv_density: 0.0,
+    }
+}
+
+/**
+ * @brief Find the first surface of a field in front of a ray
+ *
+ * @param field the field
+ * @param ray the ray
+ * @param bounds the bounding box of the field, to skip the empty space
+ */
+pub fn march_hit(field: &dyn SdfNode, ray: &Ray, bounds: Aabb) -> Option<Hit> {
+    stats::count_ray_primitive_test();
+
+    let limit = MAX_DISTANCE / ray.direction.length();
+    let (start, end) = bounds.clip(ray, 0.0, limit)?;
+    let distance = march(field, ray, start, end)?;
+
+    Some(field_hit(field, ray, distance))
+}
+
+/**
+ * @brief Find the segments of the line of a ray inside a field
+ *
+ * March the whole line through the bounding box, and record each change
+ * of sign of the field.
+ *
+ * @param field the field
+ * @param ray the ray
+ * @param bounds the bounding box of the field
+ */
+pub fn march_intervals(field: &dyn SdfNode, ray: &Ray, bounds: Aabb) -> Vec<Interval> {
+    stats::count_ray_primitive_test();
+
+    let length = ray.direction.length();
+    let limit = MAX_DISTANCE / length;
+    let (start, end) = match bounds.clip(ray, -limit, limit) {
+        Some(range) => range,
+        None => return Vec::new(),
+    };
+
+    let starts_inside = field.distance(ray.origin + ray.direction * start) < 0.0;
+    let mut inside = starts_inside;
+    let mut crossings = Vec::new();
+    let mut t = start;
+    for _ in 0..MAX_STEPS * 4 {
+        let d = field.distance(ray.origin + ray.direction * t);
+        if (d < 0.0) != inside {
+            crossings.push(t);
+            inside = !inside;
+        }
+
+        // Step over the surfaces once close enough
+        t += d.abs().max(HIT_EPSILON) / length;
+        if t > end {
+            break;
+        }
+    }
+
+    // The volumes still open at the ends of the segment extend to infinity
+    let line_end = |distance: f32, direction: f32| Hit {
+        position: ray.origin + ray.direction * distance,
+        normal: ray.direction.normalize() * direction,
+        distance,
+        material: field.material(ray.origin),
+        uv: [0.0, 0.0],
+        uv_density: 0.0,
+    };
+    let mut enter = if starts_inside {
+        Some(line_end(f32::NEG_INFINITY, -1.0))
+    } else {
+        None
+    };
+    let mut intervals = Vec::new();
+    for distance in crossings {
+        let hit = field_hit(field, ray, distance);
+        match enter.take() {
+            Some(enter) => intervals.push(Interval { enter, exit: hit }),
+            None => enter = Some(hit),
+        }
+    }
+    if let Some(enter) = enter {
+        intervals.push(Interval {
+            enter,
+            exit: line_end(f32::INFINITY, 1.0),
+        });
+    }
+
+    intervals
+}
+
+/**
+ * @brief Grow a bounding box by a margin on all sides
+ */
+fn grow_bounds(bounds: Aabb, margin: f32) -> Aabb {
+    let margin = Vector3::new(margin, margin, margin);
+
+    Aabb::new(bounds.min - margin, bounds.max + margin)
+}
+
+/**
+ * @brief Polynomial smooth minimum
+ *
+ * @param a the first distance
+ * @param b the second distance
+ * @param smoothness the size of the blend, 0 for a sharp minimum
+ */
+pub fn smooth_min(a: f32, b: f32, smoothness: f32) -> f32 {
+    if smoothness <= 0.0 {
+        return a.min(b);
+    }
+
+    let h = (smoothness - (a - b).abs()).max(0.0) / smoothness;
+    a.min(b) - h * h * smoothness * 0.25
+}
+
+/**
+ * @brief The union of the nodes, blended where they are close to each other
+ */
+pub struct SmoothUnionNode {
+    pub nodes: Vec<Box<dyn Node>>,
+
+    /**
+     * @brief The size of the blend, 0 for a sharp union
+     */
+    pub smoothness: f32,
+}
+
+impl Node for SmoothUnionNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        march_hit(self, ray, self.bounding_box())
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        march_intervals(self, ray, self.bounding_box())
+    }
+
+    /**
+     * @brief The blend moves the surface by a quarter of the smoothness at most
+     */
+    fn bounding_box(&self) -> Aabb {
+        let bounds = self
+            .nodes
+            .iter()
+            .map(|node| node.bounding_box())
+            .fold(Aabb::EMPTY, Aabb::union);
+
+        grow_bounds(bounds, self.smoothness * 0.25)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for SmoothUnionNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        self.nodes
+            .iter()
+            .map(|node| nearest_distance(std::iter::once(node), point))
+            .reduce(|acc, distance| smooth_min(acc, distance, self.smoothness))
+            .unwrap_or(f32::INFINITY)
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        nearest_material(self.nodes.iter(), point)
+    }
+}
+
+/**
+ * @brief The volume of the first node minus the volume of the other nodes,
+ * with rounded edges
+ */
+pub struct SmoothSubtractionNode {
+    pub nodes: Vec<Box<dyn Node>>,
+
+    /**
+     * @brief The size of the blend, 0 for a sharp subtraction
+     */
+    pub smoothness: f32,
+}
+
+impl Node for SmoothSubtractionNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        march_hit(self, ray, self.bounding_box())
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        march_intervals(self, ray, self.bounding_box())
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or(Aabb::EMPTY, |node| node.bounding_box())
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for SmoothSubtractionNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        let mut distances = self
+            .nodes
+            .iter()
+            .map(|node| nearest_distance(std::iter::once(node), point));
+        let first = distances.next().unwrap_or(f32::INFINITY);
+
+        // Smooth maximum with the outside of the removed volumes
+        distances.fold(first, |acc, distance| {
+            -smooth_min(-acc, distance, self.smoothness)
+        })
+    }
+
+    /**
+     * @brief The carved surfaces take the material of the removed nodes
+     */
+    fn material(&self, point: Vector3) -> Material {
+        let first = match self.nodes.first() {
+            Some(node) => node,
+            None => return Material::DEBUG_MATERIAL,
+        };
+        let first_distance = nearest_distance(std::iter::once(first), point);
+        let removed = self.nodes[1..]
+            .iter()
+            .map(|node| (node, -nearest_distance(std::iter::once(node), point)))
+            .reduce(|acc, e| if acc.1 >= e.1 { acc } else { e });
+
+        match removed {
+            Some((node, distance)) if distance > first_distance => {
+                nearest_material(std::iter::once(node), point)
+            }
+            _ => nearest_material(std::iter::once(first), point),
+        }
+    }
+}
+
+/**
+ * @brief An infinite repetition of a node along the axes
+ *
+ * The node must fit in a cell of the repetition for the field to be exact.
+ */
+pub struct RepetitionNode {
+    pub node: Box<dyn Node>,
+
+    /**
+     * @brief The size of a cell along each axis, 0 to not repeat along an axis
+     */
+    pub period: Vector3,
+}
+
+impl RepetitionNode {
+    /**
+     * @brief Move a point to the cell around the origin
+     */
+    fn fold(&self, point: Vector3) -> Vector3 {
+        let fold_axis = |p: f32, period: f32| {
+            if period > 0.0 {
+                p - period * (p / period).round()
+            } else {
+                p
+            }
+        };
+
+        Vector3::new(
+            fold_axis(point.x, self.period.x),
+            fold_axis(point.y, self.period.y),
+            fold_axis(point.z, self.period.z),
+        )
+    }
+}
+
+impl Node for RepetitionNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        march_hit(self, ray, self.bounding_box())
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        march_intervals(self, ray, self.bounding_box())
+    }
+
+    /**
+     * @brief The bounding box is infinite along the repeated axes
+     */
+    fn bounding_box(&self) -> Aabb {
+        let mut bounds = self.node.bounding_box();
+        for (period, min, max) in [
+            (self.period.x, &mut bounds.min.x, &mut bounds.max.x),
+            (self.period.y, &mut bounds.min.y, &mut bounds.max.y),
+            (self.period.z, &mut bounds.min.z, &mut bounds.max.z),
+        ] {
+            if period > 0.0 {
+                *min = f32::NEG_INFINITY;
+                *max = f32::INFINITY;
+            }
+        }
+
+        bounds
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for RepetitionNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        nearest_distance(std::iter::once(&self.node), self.fold(point))
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        nearest_material(std::iter::once(&self.node), self.fold(point))
+    }
+}
+
+/**
+ * @brief A node twisted around the Y axis
+ */
+pub struct TwistNode {
+    pub node: Box<dyn Node>,
+
+    /**
+     * @brief The rotation around the Y axis per unit of height, in radians
+     */
+    pub amount: f32,
+}
+
+impl TwistNode {
+    /**
+     * @brief Untwist a point to the space of the node
+     */
+    fn untwist(&self, point: Vector3) -> Vector3 {
+        let (sin, cos) = (self.amount * point.y).sin_cos();
+
+        Vector3::new(
+            cos * point.x - sin * point.z,
+            point.y,
+            sin * point.x + cos * point.z,
+        )
+    }
+}
+
+impl Node for TwistNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        march_hit(self, ray, self.bounding_box())
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        march_intervals(self, ray, self.bounding_box())
+    }
+
+    /**
+     * @brief The bounding cylinder of the node around the Y axis
+     */
+    fn bounding_box(&self) -> Aabb {
+        let bounds = self.node.bounding_box();
+        let x = bounds.min.x.abs().max(bounds.max.x.abs());
+        let z = bounds.min.z.abs().max(bounds.max.z.abs());
+        let radius = (x * x + z * z).sqrt();
+
+        Aabb::new(
+            Vector3::new(-radius, bounds.min.y, -radius),
+            Vector3::new(radius, bounds.max.y, radius),
+        )
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for TwistNode {
+    /**
+     * @brief The twist stretches the field, so its distance is divided by
+     * the local Lipschitz bound of the deformation
+     */
+    fn distance(&self, point: Vector3) -> f32 {
+        let radius = (point.x * point.x + point.z * point.z).sqrt();
+        let lipschitz = (1.0 + (self.amount * radius).powi(2)).sqrt();
+
+        nearest_distance(std::iter::once(&self.node), self.untwist(point)) / lipschitz
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        nearest_material(std::iter::once(&self.node), self.untwist(point))
+    }
+}
+
+/**
+ * @brief A node whose surface is displaced by a sinusoidal pattern
+ */
+pub struct DisplacementNode {
+    pub node: Box<dyn Node>,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl Node for DisplacementNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        march_hit(self, ray, self.bounding_box())
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        march_intervals(self, ray, self.bounding_box())
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        grow_bounds(self.node.bounding_box(), self.amplitude.abs())
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for DisplacementNode {
+    /**
+     * @brief The distance is divided by the bound of the gradient of the
+     * displaced field, so that the sphere tracing stays conservative
+     */
+    fn distance(&self, point: Vector3) -> f32 {
+        let p = point * self.frequency;
+        let displacement = self.amplitude * p.x.sin() * p.y.sin() * p.z.sin();
+        let lipschitz = 1.0 + (self.amplitude * self.frequency).abs() * 3f32.sqrt();
+
+        (nearest_distance(std::iter::once(&self.node), point) + displacement) / lipschitz
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        nearest_material(std::iter::once(&self.node), point)
+    }
+}
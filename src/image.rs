@@ -1,20 +1,189 @@
-use crate::canvas::Canvas;
-use crate::math::lerp_color8;
+use crate::canvas::{Canvas, LayeredCanvas};
+use crate::color::Color;
+use crate::display::DisplayTransform;
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, Vec2,
+    WritableImage,
+};
+use image::codecs::hdr::HdrEncoder;
 use image::Rgb;
-use image::RgbImage;
+use image::{Rgb32FImage, RgbImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 
-pub fn save_canvas_to_file(canvas: &dyn Canvas, path: &Path) {
+/**
+ * @brief Save a canvas, in the format given by the extension of the file
+ *
+ * The .exr, .hdr and .pfm files keep the linear and unclamped colors as
+ * 32-bit floats, the other formats go through the display transform and are
+ * quantized to 8 bits.
+ */
+pub fn save_canvas_to_file(canvas: &dyn Canvas, path: &Path, display: &DisplayTransform) {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("exr") => save_canvas_to_exr(canvas, path),
+        Some("hdr") => save_canvas_to_hdr(canvas, path),
+        Some("pfm") => save_canvas_to_pfm(canvas, path),
+        _ => save_canvas_to_ldr(canvas, path, display),
+    }
+}
+
+/**
+ * @brief Save a canvas as an 8-bit image
+ */
+pub fn save_canvas_to_ldr(canvas: &dyn Canvas, path: &Path, display: &DisplayTransform) {
     let (width, height) = (canvas.width(), canvas.height());
     let mut image = RgbImage::new(width, height);
     for (x, y, p) in image.enumerate_pixels_mut() {
         let in_color = canvas.get_pixel(x, height - y - 1);
-        *p = Rgb([
-            lerp_color8(in_color.r),
-            lerp_color8(in_color.g),
-            lerp_color8(in_color.b),
-        ]);
+        *p = Rgb(display.to_rgb8(in_color));
     }
 
     image.save(path).unwrap();
 }
+
+/**
+ * @brief Copy a canvas to a floating point image, with the first row at the top
+ */
+fn canvas_to_float_image(canvas: &dyn Canvas) -> Rgb32FImage {
+    let (width, height) = (canvas.width(), canvas.height());
+    let mut image = Rgb32FImage::new(width, height);
+    for (x, y, p) in image.enumerate_pixels_mut() {
+        let in_color = canvas.get_pixel(x, height - y - 1);
+        *p = Rgb([in_color.r, in_color.g, in_color.b]);
+    }
+
+    image
+}
+
+/**
+ * @brief Save a canvas as an OpenEXR image with 32-bit float channels
+ */
+pub fn save_canvas_to_exr(canvas: &dyn Canvas, path: &Path) {
+    canvas_to_float_image(canvas).save(path).unwrap();
+}
+
+/**
+ * @brief Save a canvas as a Radiance HDR image
+ *
+ * The format shares an 8-bit exponent between the channels, and can't store
+ * negative values.
+ */
+pub fn save_canvas_to_hdr(canvas: &dyn Canvas, path: &Path) {
+    let image = canvas_to_float_image(canvas);
+    let pixels: Vec<Rgb<f32>> = image.pixels().copied().collect();
+    let out = BufWriter::new(File::create(path).unwrap());
+
+    HdrEncoder::new(out)
+        .encode(&pixels, image.width() as usize, image.height() as usize)
+        .unwrap();
+}
+
+/**
+ * @brief Save a canvas as a portable float map
+ *
+ * A raw dump of the little-endian floats after a short text header, readable
+ * without any library. The rows are stored from the bottom, like in the canvas.
+ */
+pub fn save_canvas_to_pfm(canvas: &dyn Canvas, path: &Path) {
+    let (width, height) = (canvas.width(), canvas.height());
+    let mut out = BufWriter::new(File::create(path).unwrap());
+
+    // The negative scale means little-endian
+    write!(out, "PF\n{} {}\n-1.0\n", width, height).unwrap();
+    for y in 0..height {
+        for x in 0..width {
+            let color = canvas.get_pixel(x, y);
+            for channel in [color.r, color.g, color.b] {
+                out.write_all(&channel.to_le_bytes()).unwrap();
+            }
+        }
+    }
+
+    out.flush().unwrap();
+}
+
+/**
+ * @brief Copy a channel of a canvas, with the first row at the top
+ */
+fn canvas_channel(canvas: &dyn Canvas, channel: impl Fn(Color) -> f32) -> Vec<f32> {
+    let (width, height) = (canvas.width(), canvas.height());
+    let mut samples = Vec::with_capacity((width * height) as usize);
+    for y in (0..height).rev() {
+        for x in 0..width {
+            samples.push(channel(canvas.get_pixel(x, y)));
+        }
+    }
+
+    samples
+}
+
+/**
+ * @brief Save a canvas and its passes
+ *
+ * An .exr file gets all the passes as channel groups, like depth.R, next to
+ * the R, G and B channels of the image. The other formats get a portable
+ * float map per pass, like image.depth.pfm, as the depths, the negative
+ * normals and the identifiers don't fit in 8 bits.
+ */
+pub fn save_layers_to_file(
+    canvas: &dyn Canvas,
+    layers: &LayeredCanvas,
+    path: &Path,
+    display: &DisplayTransform,
+) {
+    let is_exr = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"));
+    if is_exr {
+        return save_layers_to_exr(canvas, layers, path);
+    }
+
+    save_canvas_to_file(canvas, path, display);
+
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    for (name, layer) in &layers.layers {
+        let file_name = format!("{}.{}.pfm", stem, name);
+        save_canvas_to_pfm(layer, &path.with_file_name(file_name));
+    }
+}
+
+/**
+ * @brief Save a canvas and its passes as a single OpenEXR image
+ */
+pub fn save_layers_to_exr(canvas: &dyn Canvas, layers: &LayeredCanvas, path: &Path) {
+    let mut channels = Vec::new();
+    let mut add_channels = |prefix: &str, canvas: &dyn Canvas| {
+        for (index, component) in ["R", "G", "B"].into_iter().enumerate() {
+            let samples = canvas_channel(canvas, |color| [color.r, color.g, color.b][index]);
+            channels.push(AnyChannel::new(
+                format!("{}{}", prefix, component).as_str(),
+                FlatSamples::F32(samples),
+            ));
+        }
+    };
+
+    add_channels("", canvas);
+    for (name, layer) in &layers.layers {
+        add_channels(&format!("{}.", name), layer);
+    }
+
+    let size = Vec2(canvas.width() as usize, canvas.height() as usize);
+    let layer = Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels.into()),
+    );
+
+    Image::from_layer(layer).write().to_file(path).unwrap();
+}
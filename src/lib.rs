@@ -1,14 +1,26 @@
+pub mod aabb;
+pub mod aov;
+pub mod bsdf;
+pub mod bvh;
 pub mod camera;
 pub mod canvas;
 pub mod color;
+pub mod csg;
+pub mod display;
+pub mod field;
 pub mod image;
 pub mod light;
 pub mod loader;
 pub mod material;
 pub mod math;
+pub mod mesh;
 pub mod ray;
 pub mod render;
+pub mod sampling;
 pub mod saver;
 pub mod scene;
 pub mod sdf;
 pub mod sdl;
+pub mod stats;
+pub mod texture;
+pub mod transform;
@@ -1,14 +1,72 @@
 use std::any::Any;
 
-use crate::{math::Vector3, ray::Hit};
+use crate::{
+    color::Color,
+    math::Vector3,
+    ray::{Hit, Ray},
+};
 
-pub trait Light {
+/**
+ * @brief Offset applied along the normal to the origin of the shadow rays
+ *
+ * Avoid the surface to shadow itself because of floating point errors
+ */
+pub const SHADOW_EPSILON: f32 = 0.001;
+
+pub trait Light: Send + Sync {
     fn as_any(&self) -> &dyn Any;
 
-    fn compute_intensity(&self, hit: &Hit, inverse_direction: Vector3) -> f32;
+    /**
+     * @brief Compute the light received from the light at a hit
+     *
+     * @param hit the lighted point
+     * @param inverse_direction the direction toward the viewer
+     */
+    fn compute_intensity(&self, hit: &Hit, inverse_direction: Vector3) -> Color;
+
+    /**
+     * @brief Build the ray going from the hit toward the light
+     *
+     * @param hit the point to test
+     *
+     * @return the shadow ray and the maximal distance of an occluder along it,
+     * or None if the light doesn't cast shadows
+     */
+    fn shadow_ray(&self, _hit: &Hit) -> Option<(Ray, f32)> {
+        None
+    }
+
+    /**
+     * @brief Get the light arriving at a point from a single direction
+     *
+     * Used by the renderers sampling the lights explicitly.
+     *
+     * @param hit the lighted point
+     *
+     * @return the direction toward the light and the light received when
+     * facing it, or None if the light doesn't come from a single direction
+     */
+    fn illumination(&self, _hit: &Hit) -> Option<(Vector3, Color)> {
+        None
+    }
+}
+
+/**
+ * @brief Build a shadow ray starting slightly above the surface of the hit
+ *
+ * @param hit the point to test
+ * @param direction the direction toward the light
+ */
+pub fn build_shadow_ray(hit: &Hit, direction: Vector3) -> Ray {
+    Ray {
+        origin: hit.position + hit.normal * SHADOW_EPSILON,
+        direction,
+        spread: 0.0,
+    }
 }
 
 pub struct AmbiantLight {
+    pub color: Color,
     pub intensity: f32,
 }
 
@@ -17,22 +75,22 @@ impl Light for AmbiantLight {
         self
     }
 
-    fn compute_intensity(&self, _hit: &Hit, _inverse_direction: Vector3) -> f32 {
-        self.intensity
+    fn compute_intensity(&self, _hit: &Hit, _inverse_direction: Vector3) -> Color {
+        self.color * self.intensity
     }
 }
 
 pub fn compute_directional_light_intensity(
-    intensity: f32,
+    intensity: Color,
     direction: Vector3,
     hit: &Hit,
     inverse_direction: Vector3,
-) -> f32 {
+) -> Color {
     // Diffuse
     let n_dot_l = hit.normal.dot(direction);
     let mut i = 0.0;
     if n_dot_l > 0.0 {
-        i += intensity * n_dot_l / (hit.normal.length() * direction.length());
+        i += n_dot_l / (hit.normal.length() * direction.length());
     }
 
     // Specular
@@ -40,17 +98,18 @@ pub fn compute_directional_light_intensity(
         let r = hit.normal * hit.normal.dot(direction) * 2.0 - direction;
         let r_dot_v = r.dot(inverse_direction);
         if r_dot_v > 0.0 {
-            i += intensity
-                * (r_dot_v / (r.length() * inverse_direction.length())).powf(hit.material.specular)
+            i += (r_dot_v / (r.length() * inverse_direction.length())).powf(hit.material.specular)
         }
     }
 
-    i
+    intensity * i
 }
 
 pub struct OmniDirectionalLight {
     pub position: Vector3,
+    pub color: Color,
     pub intensity: f32,
+    pub cast_shadows: bool,
 }
 
 impl Light for OmniDirectionalLight {
@@ -58,19 +117,35 @@ impl Light for OmniDirectionalLight {
         self
     }
 
-    fn compute_intensity(&self, hit: &Hit, inverse_direction: Vector3) -> f32 {
+    fn compute_intensity(&self, hit: &Hit, inverse_direction: Vector3) -> Color {
         compute_directional_light_intensity(
-            self.intensity,
+            self.color * self.intensity,
             self.position - hit.position,
             hit,
             inverse_direction,
         )
     }
+
+    fn shadow_ray(&self, hit: &Hit) -> Option<(Ray, f32)> {
+        if !self.cast_shadows {
+            return None;
+        }
+
+        // The direction isn't normalized, so the light is at the distance 1
+        let ray = build_shadow_ray(hit, self.position - hit.position);
+        Some((ray, 1.0))
+    }
+
+    fn illumination(&self, hit: &Hit) -> Option<(Vector3, Color)> {
+        Some((self.position - hit.position, self.color * self.intensity))
+    }
 }
 
 pub struct DirectionalLight {
     pub direction: Vector3,
+    pub color: Color,
     pub intensity: f32,
+    pub cast_shadows: bool,
 }
 
 impl Light for DirectionalLight {
@@ -78,7 +153,24 @@ impl Light for DirectionalLight {
         self
     }
 
-    fn compute_intensity(&self, hit: &Hit, inverse_direction: Vector3) -> f32 {
-        compute_directional_light_intensity(self.intensity, self.direction, hit, inverse_direction)
+    fn compute_intensity(&self, hit: &Hit, inverse_direction: Vector3) -> Color {
+        compute_directional_light_intensity(
+            self.color * self.intensity,
+            self.direction,
+            hit,
+            inverse_direction,
+        )
+    }
+
+    fn shadow_ray(&self, hit: &Hit) -> Option<(Ray, f32)> {
+        if !self.cast_shadows {
+            return None;
+        }
+
+        Some((build_shadow_ray(hit, self.direction), f32::INFINITY))
+    }
+
+    fn illumination(&self, _hit: &Hit) -> Option<(Vector3, Color)> {
+        Some((self.direction, self.color * self.intensity))
     }
 }
@@ -0,0 +1,1265 @@
+use crate::bsdf::{BsdfModel, Conductor, Dielectric, Lambertian, Principled};
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::csg::{DifferenceNode, IntersectionNode};
+use crate::display::{DisplayTransform, ToneMapping};
+use crate::field::{
+    DisplacementNode, RepetitionNode, SmoothSubtractionNode, SmoothUnionNode, TwistNode,
+};
+use crate::light::{AmbiantLight, DirectionalLight, Light, OmniDirectionalLight};
+use crate::material::{Material, MaterialTextures};
+use crate::math::{Matrix4, Vector3};
+use crate::mesh::MeshNode;
+use crate::scene::Scene;
+use crate::sdf::{
+    BoxNode, ConeNode, CylinderNode, DiskNode, Node, PlaneNode, SphereNode, TorusNode, UnionNode,
+};
+use crate::texture::{Texture, TextureFilter, Wrap};
+use crate::transform::{InstanceNode, TransformNode};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::yaml::BVH_THRESHOLD;
+use super::{FieldError, IdAllocator, LoadError, Loader};
+
+/**
+ * @brief An s-expression
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Expression {
+    /**
+     * @brief A symbol, a number or a boolean
+     */
+    Atom(String),
+
+    String(String),
+    List(Vec<Expression>),
+}
+
+impl Expression {
+    /**
+     * @brief Get the symbol at the start of a list
+     */
+    pub fn head(&self) -> Option<&str> {
+        match self {
+            Self::List(items) => match items.first() {
+                Some(Self::Atom(head)) => Some(head),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /**
+     * @brief Get the items of a list after its head
+     */
+    pub fn arguments(&self) -> &[Expression] {
+        match self {
+            Self::List(items) if !items.is_empty() => &items[1..],
+            _ => &[],
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Self::Atom(atom) => atom.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Atom(atom) => atom.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Atom(atom) | Self::String(atom) => Some(atom),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * @brief Read the s-expressions of a text
+ *
+ * The comments start with a semicolon and end with the line.
+ *
+ * @return the expressions, or an error with its line and column
+ */
+pub fn parse_expressions(text: &str) -> Result<Vec<Expression>, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        index: 0,
+    };
+
+    let mut expressions = Vec::new();
+    loop {
+        parser.skip_blanks();
+        if parser.peek().is_none() {
+            return Ok(expressions);
+        }
+        expressions.push(parser.parse_expression()?);
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    /**
+     * @brief Build an error at the current position
+     */
+    fn error(&self, message: &str) -> String {
+        let before = &self.chars[..self.index.min(self.chars.len())];
+        let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+
+        format!("{}:{}: {}", line, column, message)
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == ';' {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.index += 1;
+                }
+            } else if c.is_whitespace() {
+                self.index += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, String> {
+        match self.peek() {
+            Some('(') => {
+                self.index += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blanks();
+                    match self.peek() {
+                        Some(')') => {
+                            self.index += 1;
+                            return Ok(Expression::List(items));
+                        }
+                        Some(_) => items.push(self.parse_expression()?),
+                        None => return Err(self.error("unclosed list")),
+                    }
+                }
+            }
+            Some(')') => Err(self.error("unexpected ')'")),
+            Some('"') => self.parse_string(),
+            Some(_) => {
+                let start = self.index;
+                while self
+                    .peek()
+                    .is_some_and(|c| !c.is_whitespace() && !"()\";".contains(c))
+                {
+                    self.index += 1;
+                }
+                Ok(Expression::Atom(
+                    self.chars[start..self.index].iter().collect(),
+                ))
+            }
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<Expression, String> {
+        self.index += 1;
+        let mut string = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unclosed string"))?;
+            self.index += 1;
+            match c {
+                '"' => return Ok(Expression::String(string)),
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| self.error("unclosed string"))?;
+                    self.index += 1;
+                    string.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        '0' => '\0',
+                        c => c,
+                    });
+                }
+                c => string.push(c),
+            }
+        }
+    }
+}
+
+/**
+ * @brief An expression of a scene file, with its path for the error messages
+ */
+#[derive(Clone)]
+pub struct Field<'a> {
+    /**
+     * @brief The expression, None when the argument is missing
+     */
+    pub data: Option<&'a Expression>,
+
+    /**
+     * @brief The arguments and the items leading to the expression, like scene.root.list[3]
+     */
+    pub path: String,
+}
+
+impl<'a> Field<'a> {
+    pub fn root(data: &'a Expression) -> Self {
+        Field {
+            data: Some(data),
+            path: String::from("scene"),
+        }
+    }
+
+    pub fn head(&self) -> Option<&'a str> {
+        self.data.and_then(Expression::head)
+    }
+
+    /**
+     * @brief Get the items of a list after its head, none if it isn't a list
+     */
+    pub fn items(&self) -> Vec<Field<'a>> {
+        self.data
+            .map_or(&[][..], Expression::arguments)
+            .iter()
+            .enumerate()
+            .map(|(index, data)| Field {
+                data: Some(data),
+                path: format!("{}[{}]", self.path, index),
+            })
+            .collect()
+    }
+
+    pub fn is_missing(&self) -> bool {
+        self.data.is_none()
+    }
+}
+
+/**
+ * @brief Describe an expression for the error messages
+ */
+fn describe(data: Option<&Expression>) -> String {
+    match data {
+        Some(Expression::Atom(atom)) => atom.clone(),
+        Some(Expression::String(string)) => format!("{:?}", string),
+        Some(expression) => match expression.head() {
+            Some(head) => format!("({} ...)", head),
+            None => String::from("a list"),
+        },
+        None => String::from("nothing"),
+    }
+}
+
+/**
+ * @brief The arguments of a form, read in order and named after what they are
+ */
+struct Arguments<'a> {
+    path: String,
+    items: &'a [Expression],
+    index: usize,
+}
+
+impl<'a> Arguments<'a> {
+    fn new(form: &Field<'a>) -> Self {
+        Self {
+            path: form.path.clone(),
+            items: form.data.map_or(&[][..], Expression::arguments),
+            index: 0,
+        }
+    }
+
+    fn field(&self, name: &str, data: Option<&'a Expression>) -> Field<'a> {
+        Field {
+            data,
+            path: format!("{}.{}", self.path, name),
+        }
+    }
+
+    /**
+     * @brief Read an argument, missing after the last one
+     */
+    fn next(&mut self, name: &str) -> Field<'a> {
+        let data = self.items.get(self.index);
+        self.index += 1;
+        self.field(name, data)
+    }
+
+    /**
+     * @brief Read an optional number or symbol, missing at the end or before a form
+     */
+    fn next_atom(&mut self, name: &str) -> Field<'a> {
+        match self.items.get(self.index) {
+            Some(Expression::List(_)) | None => self.field(name, None),
+            Some(_) => self.next(name),
+        }
+    }
+
+    /**
+     * @brief Read an optional argument, present if it is a form with the given head
+     */
+    fn next_form(&mut self, head: &str) -> Field<'a> {
+        match self.items.get(self.index) {
+            Some(item) if item.head() == Some(head) => self.next(head),
+            _ => self.field(head, None),
+        }
+    }
+}
+
+/**
+ * @brief Load the scenes written by the LispSaver
+ *
+ * Also reads the legacy files, with a view port camera and without the
+ * trailing arguments added since then. Like the YAML scenes, the parsing
+ * goes on after an invalid expression, so that all the errors of a file are
+ * reported at once.
+ */
+#[derive(Default)]
+pub struct LispLoader {
+    /**
+     * @brief The directory against which the relative paths are resolved
+     */
+    pub base_directory: PathBuf,
+
+    ids: IdAllocator<Expression>,
+    errors: RefCell<Vec<FieldError>>,
+
+    /**
+     * @brief The prototypes of the (prototypes ...) form, by name
+     */
+    prototypes: RefCell<HashMap<String, Arc<dyn Node>>>,
+
+    /**
+     * @brief The prototypes written inside the legacy instances, shared by the identical definitions
+     */
+    inline_prototypes: RefCell<HashMap<Expression, Arc<dyn Node>>>,
+
+    /**
+     * @brief The texture indices of the materials, checked once the textures are known
+     */
+    texture_indices: RefCell<Vec<(usize, String)>>,
+}
+
+impl LispLoader {
+    /**
+     * @brief Record an invalid expression
+     *
+     * @param data the expression
+     * @param expected what the expression should be
+     */
+    fn error(&self, data: &Field, expected: &str) {
+        self.errors.borrow_mut().push(FieldError {
+            file: None,
+            path: data.path.clone(),
+            expected: expected.to_string(),
+            found: describe(data.data),
+        });
+    }
+
+    /**
+     * @brief Take the errors recorded so far
+     */
+    pub fn take_errors(&self) -> Vec<FieldError> {
+        self.errors.take()
+    }
+
+    /**
+     * @brief Read the arguments of a form, None if the expression is another form
+     */
+    fn expect_form<'a>(&self, data: &Field<'a>, head: &str) -> Option<Arguments<'a>> {
+        if data.head() != Some(head) {
+            self.error(data, &format!("({} ...)", head));
+            return None;
+        }
+        Some(Arguments::new(data))
+    }
+
+    fn parse_number(&self, data: &Field) -> f32 {
+        data.data.and_then(Expression::as_f32).unwrap_or_else(|| {
+            self.error(data, "a number");
+            0.0
+        })
+    }
+
+    fn parse_number_or(&self, data: &Field, default: f32) -> f32 {
+        if data.is_missing() {
+            default
+        } else {
+            self.parse_number(data)
+        }
+    }
+
+    fn parse_bool_or(&self, data: &Field, default: bool) -> bool {
+        if data.is_missing() {
+            return default;
+        }
+
+        data.data.and_then(Expression::as_bool).unwrap_or_else(|| {
+            self.error(data, "a boolean");
+            default
+        })
+    }
+
+    /**
+     * @brief Parse a path relative to the scene file
+     */
+    fn parse_path(&self, data: &Field) -> Option<PathBuf> {
+        match data.data.and_then(Expression::as_str) {
+            Some(path) => Some(self.base_directory.join(path)),
+            None => {
+                self.error(data, "a path");
+                None
+            }
+        }
+    }
+
+    /**
+     * @brief Get the items of a (list ...) form, none if it isn't one
+     */
+    fn parse_list<'a>(&self, data: &Field<'a>) -> Vec<Field<'a>> {
+        if self.expect_form(data, "list").is_none() {
+            return Vec::new();
+        }
+        data.items()
+    }
+
+    fn parse_triplet(&self, data: &Field, head: &str, names: [&str; 3]) -> (f32, f32, f32) {
+        let Some(mut arguments) = self.expect_form(data, head) else {
+            return (0.0, 0.0, 0.0);
+        };
+        (
+            self.parse_number(&arguments.next(names[0])),
+            self.parse_number(&arguments.next(names[1])),
+            self.parse_number(&arguments.next(names[2])),
+        )
+    }
+
+    pub fn parse_vector3(&self, data: &Field) -> Vector3 {
+        let (x, y, z) = self.parse_triplet(data, "vector3", ["x", "y", "z"]);
+        Vector3::new(x, y, z)
+    }
+
+    pub fn parse_matrix4(&self, data: &Field) -> Matrix4 {
+        let Some(mut arguments) = self.expect_form(data, "matrix4") else {
+            return Matrix4::IDENTITY;
+        };
+        let mut matrix = Matrix4::IDENTITY;
+        for (row, values) in matrix.m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = self.parse_number(&arguments.next(&format!("m{}{}", row + 1, column + 1)));
+            }
+        }
+        matrix
+    }
+
+    pub fn parse_color(&self, data: &Field) -> Color {
+        let (r, g, b) = self.parse_triplet(data, "color", ["r", "g", "b"]);
+        Color::new(r, g, b)
+    }
+
+    pub fn parse_camera(&self, data: &Field) -> Camera {
+        let Some(mut arguments) = self.expect_form(data, "camera") else {
+            return Camera::default();
+        };
+        let position = self.parse_vector3(&arguments.next("position"));
+
+        // Legacy cameras only have a view port
+        let view_port = arguments.next_form("view_port");
+        if !view_port.is_missing() {
+            let (x, y, z) = self.parse_triplet(&view_port, "view_port", ["x", "y", "z"]);
+            return Camera::from_view_port(position, Vector3::new(x, y, z));
+        }
+
+        Camera::new(
+            position,
+            self.parse_vector3(&arguments.next("direction")),
+            self.parse_vector3(&arguments.next("up")),
+            self.parse_number(&arguments.next("fov")),
+            self.parse_number_or(&arguments.next_atom("roll"), 0.0),
+        )
+    }
+
+    pub fn parse_nodes(&self, data: &Field) -> Vec<Box<dyn Node>> {
+        self.parse_list(data)
+            .iter()
+            .map(|node| self.parse_node(node))
+            .collect()
+    }
+
+    pub fn parse_union_node(&self, data: &Field) -> Box<dyn Node> {
+        let nodes = self.parse_nodes(&Arguments::new(data).next("list"));
+
+        // Accelerate the large unions with a bounding volume hierarchy
+        if nodes.len() > BVH_THRESHOLD {
+            Box::new(BvhNode::new(nodes))
+        } else {
+            Box::new(UnionNode { nodes })
+        }
+    }
+
+    pub fn parse_smooth_union_node(&self, data: &Field) -> Box<SmoothUnionNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(SmoothUnionNode {
+            smoothness: self.parse_number(&arguments.next("smoothness")),
+            nodes: self.parse_nodes(&arguments.next("list")),
+        })
+    }
+
+    pub fn parse_smooth_subtraction_node(&self, data: &Field) -> Box<SmoothSubtractionNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(SmoothSubtractionNode {
+            smoothness: self.parse_number(&arguments.next("smoothness")),
+            nodes: self.parse_nodes(&arguments.next("list")),
+        })
+    }
+
+    pub fn parse_transform_node(&self, data: &Field) -> Box<dyn Node> {
+        let mut arguments = Arguments::new(data);
+        let matrix_data = arguments.next("matrix");
+        let transform = self.parse_matrix4(&matrix_data);
+        let node = self.parse_node(&arguments.next("node"));
+
+        match TransformNode::new(node, transform) {
+            Some(node) => Box::new(node),
+            None => {
+                self.error(&matrix_data, "an invertible matrix");
+                Box::new(UnionNode { nodes: Vec::new() })
+            }
+        }
+    }
+
+    /**
+     * @brief Parse the named prototypes, each one after the prototypes it uses
+     */
+    pub fn parse_prototypes(&self, data: &Field) {
+        for prototype_data in data.items() {
+            let Some(mut arguments) = self.expect_form(&prototype_data, "prototype") else {
+                continue;
+            };
+            let name_data = arguments.next_atom("name");
+            let node = self.parse_node(&arguments.next("node"));
+            let Some(name) = name_data.data.and_then(Expression::as_str) else {
+                self.error(&name_data, "the name of the prototype");
+                continue;
+            };
+            if self.prototypes.borrow().contains_key(name) {
+                self.error(&name_data, "a name not used by the previous prototypes");
+                continue;
+            }
+            self.prototypes
+                .borrow_mut()
+                .insert(name.to_string(), Arc::from(node));
+        }
+    }
+
+    /**
+     * @brief Parse the prototype of an instance, a name or a legacy definition
+     *
+     * The legacy definitions are parsed once for all the identical ones.
+     */
+    fn parse_prototype(&self, data: &Field) -> Arc<dyn Node> {
+        if let Some(Expression::Atom(name)) = data.data {
+            if let Some(prototype) = self.prototypes.borrow().get(name) {
+                return prototype.clone();
+            }
+
+            let mut names: Vec<String> = self.prototypes.borrow().keys().cloned().collect();
+            names.sort();
+            let expected = if names.is_empty() {
+                String::from("the name of a prototype")
+            } else {
+                format!("one of the prototypes {}", names.join(", "))
+            };
+            self.error(data, &expected);
+            return Arc::new(UnionNode { nodes: Vec::new() });
+        }
+
+        let cached = data
+            .data
+            .and_then(|definition| self.inline_prototypes.borrow().get(definition).cloned());
+        cached.unwrap_or_else(|| {
+            let prototype: Arc<dyn Node> = Arc::from(self.parse_node(data));
+            if let Some(definition) = data.data {
+                self.inline_prototypes
+                    .borrow_mut()
+                    .insert(definition.clone(), prototype.clone());
+            }
+            prototype
+        })
+    }
+
+    /**
+     * @brief Parse an instance, sharing its prototype with the other instances
+     */
+    pub fn parse_instance_node(&self, data: &Field) -> Box<dyn Node> {
+        let mut arguments = Arguments::new(data);
+        let matrix_data = arguments.next("matrix");
+        let transform = self.parse_matrix4(&matrix_data);
+        let prototype = self.parse_prototype(&arguments.next("prototype"));
+        let material_data = arguments.next("material");
+        let material = (!material_data.is_missing()).then(|| self.parse_material(&material_data));
+        let object_id = material.map_or_else(|| self.ids.next_object_id(), |m| m.object_id);
+
+        match InstanceNode::new(prototype, transform, material, object_id) {
+            Some(node) => Box::new(node),
+            None => {
+                self.error(&matrix_data, "an invertible matrix");
+                Box::new(UnionNode { nodes: Vec::new() })
+            }
+        }
+    }
+
+    pub fn parse_repetition_node(&self, data: &Field) -> Box<RepetitionNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(RepetitionNode {
+            period: self.parse_vector3(&arguments.next("period")),
+            node: self.parse_node(&arguments.next("node")),
+        })
+    }
+
+    pub fn parse_twist_node(&self, data: &Field) -> Box<TwistNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(TwistNode {
+            amount: self.parse_number(&arguments.next("amount")),
+            node: self.parse_node(&arguments.next("node")),
+        })
+    }
+
+    pub fn parse_displacement_node(&self, data: &Field) -> Box<DisplacementNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(DisplacementNode {
+            amplitude: self.parse_number(&arguments.next("amplitude")),
+            frequency: self.parse_number(&arguments.next("frequency")),
+            node: self.parse_node(&arguments.next("node")),
+        })
+    }
+
+    /**
+     * @brief Parse the physically based model of a material, if it is one
+     */
+    fn parse_bsdf(&self, data: &Field, arguments: &mut Arguments) -> Option<BsdfModel> {
+        let bsdf = match data.head()? {
+            "lambertian" => BsdfModel::Lambertian(Lambertian {
+                color: self.parse_color(&arguments.next("color")),
+            }),
+            "conductor" => BsdfModel::Conductor(Conductor {
+                color: self.parse_color(&arguments.next("color")),
+                roughness: self.parse_number(&arguments.next("roughness")),
+            }),
+            "dielectric" => BsdfModel::Dielectric(Dielectric {
+                color: self.parse_color(&arguments.next("color")),
+                roughness: self.parse_number(&arguments.next("roughness")),
+                refractive_index: self.parse_number(&arguments.next("refractive_index")),
+            }),
+            "principled" => BsdfModel::Principled(Principled {
+                color: self.parse_color(&arguments.next("color")),
+                metallic: self.parse_number(&arguments.next("metallic")),
+                roughness: self.parse_number(&arguments.next("roughness")),
+                specular: self.parse_number(&arguments.next("specular")),
+                transmission: self.parse_number(&arguments.next("transmission")),
+                refractive_index: self.parse_number(&arguments.next("refractive_index")),
+            }),
+            _ => return None,
+        };
+
+        Some(bsdf)
+    }
+
+    /**
+     * @brief Parse the index of a texture of a material, checked with the textures of the scene
+     */
+    fn parse_texture_index(&self, data: &Field) -> Option<usize> {
+        let mut arguments = Arguments::new(data);
+        let index_data = arguments.next("index");
+        let index = self.parse_number(&index_data);
+        if index < 0.0 || index.fract() != 0.0 {
+            self.error(&index_data, "the index of a texture");
+            return None;
+        }
+
+        self.texture_indices
+            .borrow_mut()
+            .push((index as usize, index_data.path));
+        Some(index as usize)
+    }
+
+    /**
+     * @brief Parse the optional trailing textures of a material, as indices in the textures of the scene
+     */
+    fn parse_material_textures(&self, arguments: &mut Arguments) -> MaterialTextures {
+        let mut texture = |head: &str| {
+            let data = arguments.next_form(head);
+            if data.is_missing() {
+                None
+            } else {
+                self.parse_texture_index(&data)
+            }
+        };
+
+        MaterialTextures {
+            albedo: texture("albedo_texture"),
+            specular: texture("specular_texture"),
+            roughness: texture("roughness_texture"),
+        }
+    }
+
+    /**
+     * @brief Parse the absorption of a transparent material, none by default
+     */
+    fn parse_absorption(&self, arguments: &mut Arguments) -> Color {
+        let data = arguments.next_form("color");
+        if data.is_missing() {
+            Color::BLACK
+        } else {
+            self.parse_color(&data)
+        }
+    }
+
+    /**
+     * @brief Parse the material of an object, with a new object identifier
+     */
+    pub fn parse_material(&self, data: &Field) -> Material {
+        let Some(definition) = data.data else {
+            self.error(data, "a material");
+            return Material {
+                id: self.ids.next_material_id(),
+                object_id: self.ids.next_object_id(),
+                ..Material::DEFAULT_MATERIAL
+            };
+        };
+        let id = self.ids.material_id(definition);
+        let object_id = self.ids.next_object_id();
+        let mut arguments = Arguments::new(data);
+
+        // The physically based materials don't use the Phong parameters
+        if let Some(model) = self.parse_bsdf(data, &mut arguments) {
+            let bsdf = model.as_bsdf();
+            return Material {
+                color: bsdf.albedo(),
+                specular: -1.0,
+                reflective: 0.0,
+                transparency: 0.0,
+                refractive_index: bsdf.refractive_index(),
+                absorption: self.parse_absorption(&mut arguments),
+                bsdf: Some(model),
+                textures: self.parse_material_textures(&mut arguments),
+                id,
+                object_id,
+            };
+        }
+
+        let Some(mut arguments) = self.expect_form(data, "material") else {
+            return Material {
+                id,
+                object_id,
+                ..Material::DEFAULT_MATERIAL
+            };
+        };
+        Material {
+            color: self.parse_color(&arguments.next("color")),
+            specular: self.parse_number(&arguments.next("specular")),
+            reflective: self.parse_number_or(&arguments.next_atom("reflective"), 0.0),
+            transparency: self.parse_number_or(&arguments.next_atom("transparency"), 0.0),
+            refractive_index: self.parse_number_or(&arguments.next_atom("refractive_index"), 1.0),
+            absorption: self.parse_absorption(&mut arguments),
+            bsdf: None,
+            textures: self.parse_material_textures(&mut arguments),
+            id,
+            object_id,
+        }
+    }
+
+    pub fn parse_sphere_node(&self, data: &Field) -> Box<SphereNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(SphereNode {
+            position: self.parse_vector3(&arguments.next("position")),
+            radius: self.parse_number(&arguments.next("radius")),
+            material: self.parse_material(&arguments.next("material")),
+        })
+    }
+
+    pub fn parse_plane_node(&self, data: &Field) -> Box<PlaneNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(PlaneNode {
+            position: self.parse_vector3(&arguments.next("position")),
+            normal: self.parse_vector3(&arguments.next("normal")),
+            material: self.parse_material(&arguments.next("material")),
+        })
+    }
+
+    pub fn parse_disk_node(&self, data: &Field) -> Box<DiskNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(DiskNode {
+            position: self.parse_vector3(&arguments.next("position")),
+            normal: self.parse_vector3(&arguments.next("normal")),
+            radius: self.parse_number(&arguments.next("radius")),
+            material: self.parse_material(&arguments.next("material")),
+        })
+    }
+
+    pub fn parse_box_node(&self, data: &Field) -> Box<BoxNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(BoxNode::new(
+            self.parse_vector3(&arguments.next("position")),
+            self.parse_vector3(&arguments.next("size")),
+            self.parse_vector3(&arguments.next("rotation")),
+            self.parse_material(&arguments.next("material")),
+        ))
+    }
+
+    pub fn parse_cylinder_node(&self, data: &Field) -> Box<CylinderNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(CylinderNode {
+            start: self.parse_vector3(&arguments.next("start")),
+            end: self.parse_vector3(&arguments.next("end")),
+            radius: self.parse_number(&arguments.next("radius")),
+            material: self.parse_material(&arguments.next("material")),
+        })
+    }
+
+    pub fn parse_cone_node(&self, data: &Field) -> Box<ConeNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(ConeNode {
+            start: self.parse_vector3(&arguments.next("start")),
+            end: self.parse_vector3(&arguments.next("end")),
+            radius: self.parse_number(&arguments.next("radius")),
+            material: self.parse_material(&arguments.next("material")),
+        })
+    }
+
+    pub fn parse_torus_node(&self, data: &Field) -> Box<TorusNode> {
+        let mut arguments = Arguments::new(data);
+
+        Box::new(TorusNode {
+            position: self.parse_vector3(&arguments.next("position")),
+            axis: self.parse_vector3(&arguments.next("axis")),
+            major_radius: self.parse_number(&arguments.next("major_radius")),
+            minor_radius: self.parse_number(&arguments.next("minor_radius")),
+            material: self.parse_material(&arguments.next("material")),
+        })
+    }
+
+    pub fn parse_mesh_node(&self, data: &Field) -> Box<dyn Node> {
+        let mut arguments = Arguments::new(data);
+        let path_data = arguments.next("path");
+        let path = self.parse_path(&path_data);
+        let material = self.parse_material(&arguments.next("material"));
+        let Some(path) = path else {
+            return Box::new(UnionNode { nodes: Vec::new() });
+        };
+
+        // The materials of the mesh belong to the same object
+        let mut mesh = match MeshNode::load_obj(&path, material) {
+            Ok(mesh) => mesh,
+            Err(error) => {
+                self.error(
+                    &path_data,
+                    &format!("a readable OBJ file and MTL file ({})", error),
+                );
+                return Box::new(UnionNode { nodes: Vec::new() });
+            }
+        };
+        for mesh_material in &mut mesh.materials {
+            mesh_material.id = self.ids.next_material_id();
+            mesh_material.object_id = material.object_id;
+        }
+
+        Box::new(mesh)
+    }
+
+    pub fn parse_node(&self, data: &Field) -> Box<dyn Node> {
+        match data.head() {
+            Some("union") => self.parse_union_node(data),
+            Some("intersection") => Box::new(IntersectionNode {
+                nodes: self.parse_nodes(&Arguments::new(data).next("list")),
+            }),
+            Some("difference") => Box::new(DifferenceNode {
+                nodes: self.parse_nodes(&Arguments::new(data).next("list")),
+            }),
+            Some("smooth_union") => self.parse_smooth_union_node(data),
+            Some("smooth_subtraction") => self.parse_smooth_subtraction_node(data),
+            Some("repetition") => self.parse_repetition_node(data),
+            Some("twist") => self.parse_twist_node(data),
+            Some("displacement") => self.parse_displacement_node(data),
+            Some("transform") => self.parse_transform_node(data),
+            Some("instance") => self.parse_instance_node(data),
+            Some("sphere") => self.parse_sphere_node(data),
+            Some("plane") => self.parse_plane_node(data),
+            Some("disk") => self.parse_disk_node(data),
+            Some("box") => self.parse_box_node(data),
+            Some("cylinder") => self.parse_cylinder_node(data),
+            Some("cone") => self.parse_cone_node(data),
+            Some("torus") => self.parse_torus_node(data),
+            Some("mesh") => self.parse_mesh_node(data),
+            _ => {
+                self.error(
+                    data,
+                    "one of (union ...), (intersection ...), (difference ...), \
+                     (smooth_union ...), (smooth_subtraction ...), (repetition ...), \
+                     (twist ...), (displacement ...), (transform ...), (instance ...), \
+                     (sphere ...), (plane ...), (disk ...), (box ...), (cylinder ...), \
+                     (cone ...), (torus ...) or (mesh ...)",
+                );
+                Box::new(UnionNode { nodes: Vec::new() })
+            }
+        }
+    }
+
+    /**
+     * @brief Parse the color and the intensity of a light, white by default
+     */
+    fn parse_light_power(&self, arguments: &mut Arguments) -> (Color, f32) {
+        let intensity = self.parse_number(&arguments.next("intensity"));
+        let color_data = arguments.next_form("color");
+        let color = if color_data.is_missing() {
+            Color::WHITE
+        } else {
+            self.parse_color(&color_data)
+        };
+
+        (color, intensity)
+    }
+
+    pub fn parse_light(&self, data: &Field) -> Option<Box<dyn Light>> {
+        let mut arguments = Arguments::new(data);
+
+        let light: Box<dyn Light> = match data.head() {
+            Some("ambiant") => {
+                let (color, intensity) = self.parse_light_power(&mut arguments);
+                Box::new(AmbiantLight { color, intensity })
+            }
+            Some("omnidirectional") => {
+                let (color, intensity) = self.parse_light_power(&mut arguments);
+                Box::new(OmniDirectionalLight {
+                    color,
+                    intensity,
+                    position: self.parse_vector3(&arguments.next("position")),
+                    cast_shadows: self.parse_bool_or(&arguments.next("cast_shadows"), true),
+                })
+            }
+            Some("directional") => {
+                let (color, intensity) = self.parse_light_power(&mut arguments);
+                Box::new(DirectionalLight {
+                    color,
+                    intensity,
+                    direction: self.parse_vector3(&arguments.next("direction")),
+                    cast_shadows: self.parse_bool_or(&arguments.next("cast_shadows"), true),
+                })
+            }
+            _ => {
+                self.error(
+                    data,
+                    "one of (ambiant ...), (omnidirectional ...) or (directional ...)",
+                );
+                return None;
+            }
+        };
+
+        Some(light)
+    }
+
+    pub fn parse_lights(&self, data: &Field) -> Vec<Box<dyn Light>> {
+        self.parse_list(data)
+            .iter()
+            .filter_map(|light| self.parse_light(light))
+            .collect()
+    }
+
+    pub fn parse_display(&self, data: &Field) -> DisplayTransform {
+        let Some(mut arguments) = self.expect_form(data, "display") else {
+            return DisplayTransform::default();
+        };
+        let exposure = self.parse_number(&arguments.next("exposure"));
+        let tone_mapping_data = arguments.next("tone_mapping");
+        let tone_mapping = tone_mapping_data
+            .data
+            .and_then(Expression::as_str)
+            .and_then(ToneMapping::from_name)
+            .unwrap_or_else(|| {
+                self.error(&tone_mapping_data, "one of clamp, reinhard or aces");
+                ToneMapping::Clamp
+            });
+
+        DisplayTransform {
+            exposure,
+            tone_mapping,
+            srgb: self.parse_bool_or(&arguments.next("srgb"), true),
+        }
+    }
+
+    pub fn parse_texture(&self, data: &Field) -> Option<Texture> {
+        let mut arguments = self.expect_form(data, "texture")?;
+        let path_data = arguments.next("path");
+        let path = self.parse_path(&path_data);
+        let srgb = self.parse_bool_or(&arguments.next("srgb"), true);
+        let filter_data = arguments.next("filter");
+        let filter = filter_data
+            .data
+            .and_then(Expression::as_str)
+            .and_then(TextureFilter::from_name);
+        if filter.is_none() {
+            self.error(&filter_data, "one of bilinear or trilinear");
+        }
+        let wrap_data = arguments.next("wrap");
+        let wrap = wrap_data
+            .data
+            .and_then(Expression::as_str)
+            .and_then(Wrap::from_name);
+        if wrap.is_none() {
+            self.error(&wrap_data, "one of repeat or clamp");
+        }
+
+        match Texture::load(&path?, srgb, filter?, wrap?) {
+            Ok(texture) => Some(texture),
+            Err(error) => {
+                self.error(&path_data, &format!("a PNG or JPEG image ({})", error));
+                None
+            }
+        }
+    }
+
+    /**
+     * @brief Parse the textures of the scene, in the order of their indices
+     *
+     * An invalid texture is replaced by a white texel, so that the next
+     * textures keep their indices.
+     */
+    pub fn parse_textures(&self, data: &Field) -> Vec<Texture> {
+        let Some(_) = self.expect_form(data, "textures") else {
+            return Vec::new();
+        };
+        data.items()
+            .iter()
+            .map(|texture| {
+                self.parse_texture(texture).unwrap_or_else(|| {
+                    Texture::new(
+                        1,
+                        1,
+                        vec![Color::WHITE],
+                        TextureFilter::Bilinear,
+                        Wrap::Repeat,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    pub fn parse_scene(&self, data: &Field) -> Scene {
+        let Some(mut arguments) = self.expect_form(data, "scene") else {
+            return Scene {
+                camera: Camera::default(),
+                root: Box::new(UnionNode { nodes: Vec::new() }),
+                lights: Vec::new(),
+                max_depth: Scene::DEFAULT_MAX_DEPTH,
+                display: DisplayTransform::default(),
+                textures: Vec::new(),
+            };
+        };
+
+        let camera = self.parse_camera(&arguments.next("camera"));
+        let prototypes_data = arguments.next_form("prototypes");
+        if !prototypes_data.is_missing() {
+            self.parse_prototypes(&prototypes_data);
+        }
+        let root = self.parse_node(&arguments.next("root"));
+        let lights = self.parse_lights(&arguments.next("lights"));
+        let max_depth = self.parse_number_or(
+            &arguments.next_atom("max_depth"),
+            Scene::DEFAULT_MAX_DEPTH as f32,
+        ) as u32;
+        let display_data = arguments.next_form("display");
+        let display = if display_data.is_missing() {
+            DisplayTransform::default()
+        } else {
+            self.parse_display(&display_data)
+        };
+        let textures_data = arguments.next_form("textures");
+        let textures = if textures_data.is_missing() {
+            Vec::new()
+        } else {
+            self.parse_textures(&textures_data)
+        };
+
+        // The materials come before the textures they use
+        for (index, path) in self.texture_indices.take() {
+            if index >= textures.len() {
+                self.errors.borrow_mut().push(FieldError {
+                    file: None,
+                    path,
+                    expected: format!("the index of one of the {} textures", textures.len()),
+                    found: index.to_string(),
+                });
+            }
+        }
+
+        Scene {
+            camera,
+            root,
+            lights,
+            max_depth,
+            display,
+            textures,
+        }
+    }
+}
+
+impl Loader for LispLoader {
+    fn load_scene_from_file(&self, path: &Path) -> Result<Scene, LoadError> {
+        let text =
+            fs::read_to_string(path).map_err(|error| LoadError::Io(path.to_path_buf(), error))?;
+        let expressions = parse_expressions(&text)
+            .map_err(|error| LoadError::Syntax(path.to_path_buf(), error))?;
+        let scene_data = expressions.first().ok_or_else(|| {
+            LoadError::Syntax(path.to_path_buf(), String::from("1:1: expected a scene"))
+        })?;
+
+        // Resolve the paths relatively to the scene file
+        let loader = LispLoader {
+            base_directory: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            ..Default::default()
+        };
+        let scene = loader.parse_scene(&Field::root(scene_data));
+
+        let errors = loader.take_errors();
+        if !errors.is_empty() {
+            return Err(LoadError::Fields(path.to_path_buf(), errors));
+        }
+
+        Ok(scene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_expressions_reads_atoms_strings_and_lists() {
+        let expressions = parse_expressions("(a \"b \\\"c\\\"\" ; comment\n (1.5))\nd").unwrap();
+        assert_eq!(
+            expressions,
+            [
+                Expression::List(vec![
+                    Expression::Atom(String::from("a")),
+                    Expression::String(String::from("b \"c\"")),
+                    Expression::List(vec![Expression::Atom(String::from("1.5"))]),
+                ]),
+                Expression::Atom(String::from("d")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_expressions_reports_the_position_of_the_errors() {
+        assert_eq!(
+            parse_expressions("(scene\n  (list)").unwrap_err(),
+            "2:9: unclosed list"
+        );
+        assert_eq!(
+            parse_expressions("(a))").unwrap_err(),
+            "1:4: unexpected ')'"
+        );
+        assert_eq!(
+            parse_expressions("(path \"a.obj)").unwrap_err(),
+            "1:14: unclosed string"
+        );
+    }
+
+    #[test]
+    fn parse_scene_reports_every_error_with_its_path() {
+        let text = "(scene
+            (camera (vector3 0 0 0) (vector3 0 0 1) (vector3 0 1 0) 60)
+            (union (list
+                (sphere (vector3 0 x 0) 1 (material (color 1 0 0) 10))
+                (teapot)
+                (sphere (vector3 0 0 0) 1)))
+            (list (spot 1)))";
+        let expressions = parse_expressions(text).unwrap();
+        let loader = LispLoader::default();
+        loader.parse_scene(&Field::root(&expressions[0]));
+
+        let errors: Vec<(String, String)> = loader
+            .take_errors()
+            .into_iter()
+            .map(|error| (error.path, error.found))
+            .collect();
+        let expected = [
+            ("scene.root.list[0].position.y", "x"),
+            ("scene.root.list[1]", "(teapot ...)"),
+            ("scene.root.list[2].material", "nothing"),
+            ("scene.lights[0]", "(spot ...)"),
+        ];
+        assert_eq!(
+            errors,
+            expected.map(|(path, found)| (path.to_string(), found.to_string()))
+        );
+    }
+
+    #[test]
+    fn instances_share_the_named_prototypes() {
+        let text = "(scene
+            (camera (vector3 0 0 0) (view_port 1 1 1))
+            (prototypes
+                (prototype ball (sphere (vector3 0 0 0) 1 (material (color 1 0 0) 10)))
+                (prototype ball (sphere (vector3 0 0 0) 2 (material (color 1 0 0) 10))))
+            (union (list
+                (instance (matrix4 1 0 0 0 0 1 0 0 0 0 1 3 0 0 0 1) ball)
+                (instance (matrix4 1 0 0 2 0 1 0 0 0 0 1 3 0 0 0 1) ball)
+                (instance (matrix4 1 0 0 0 0 1 0 0 0 0 1 3 0 0 0 1) cube)))
+            (list))";
+        let expressions = parse_expressions(text).unwrap();
+        let loader = LispLoader::default();
+        let scene = loader.parse_scene(&Field::root(&expressions[0]));
+
+        let errors: Vec<(String, String)> = loader
+            .take_errors()
+            .into_iter()
+            .map(|error| (error.path, error.expected))
+            .collect();
+        let expected = [
+            (
+                "scene.prototypes[1].name",
+                "a name not used by the previous prototypes",
+            ),
+            ("scene.root.list[2].prototype", "one of the prototypes ball"),
+        ];
+        assert_eq!(
+            errors,
+            expected.map(|(path, expected)| (path.to_string(), expected.to_string()))
+        );
+
+        let root = scene.root.as_any().downcast_ref::<UnionNode>().unwrap();
+        let prototype = |index: usize| {
+            let instance = root.nodes[index].as_any().downcast_ref::<InstanceNode>();
+            instance.unwrap().prototype.clone()
+        };
+        assert!(Arc::ptr_eq(&prototype(0), &prototype(1)));
+    }
+}
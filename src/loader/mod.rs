@@ -1,8 +1,149 @@
 use crate::scene::Scene;
-use std::path::Path;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::io;
+use std::path::{Path, PathBuf};
 
+pub mod lisp;
+pub mod script;
 pub mod yaml;
 
 pub trait Loader {
-    fn load_scene_from_file(&self, path: &Path) -> Scene;
+    fn load_scene_from_file(&self, path: &Path) -> Result<Scene, LoadError>;
+}
+
+/**
+ * @brief An invalid value in a scene file
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldError {
+    /**
+     * @brief The file of the value, None for the loaded file
+     */
+    pub file: Option<PathBuf>,
+
+    /**
+     * @brief Where the value is, like root.nodes[3].material.specular
+     */
+    pub path: String,
+
+    /**
+     * @brief What the value should be, like a number
+     */
+    pub expected: String,
+
+    /**
+     * @brief The offending value, nothing when it is missing
+     */
+    pub found: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.path, self.expected, self.found
+        )
+    }
+}
+
+/**
+ * @brief The reason why a scene can't be loaded
+ */
+#[derive(Debug)]
+pub enum LoadError {
+    /**
+     * @brief The file can't be read
+     */
+    Io(PathBuf, io::Error),
+
+    /**
+     * @brief An error at a position of the file, the message starts with the line and the column
+     */
+    Syntax(PathBuf, String),
+
+    /**
+     * @brief The file is well formed, but some of its values are invalid
+     */
+    Fields(PathBuf, Vec<FieldError>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Syntax(path, message) => write!(f, "{}:{}", path.display(), message),
+            Self::Fields(path, errors) => {
+                for (index, error) in errors.iter().enumerate() {
+                    if index > 0 {
+                        writeln!(f)?;
+                    }
+                    let file = error.file.as_deref().unwrap_or(path);
+                    write!(f, "{}: {}", file.display(), error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * @brief Give identifiers to the materials and the objects of a scene, starting from 1
+ *
+ * The same material definition gets the same identifier.
+ */
+pub struct IdAllocator<K> {
+    material_ids: RefCell<HashMap<K, u32>>,
+    material_count: Cell<u32>,
+    object_count: Cell<u32>,
+}
+
+impl<K: Eq + Hash + Clone> IdAllocator<K> {
+    pub fn next_material_id(&self) -> u32 {
+        self.material_count.set(self.material_count.get() + 1);
+        self.material_count.get()
+    }
+
+    pub fn next_object_id(&self) -> u32 {
+        self.object_count.set(self.object_count.get() + 1);
+        self.object_count.get()
+    }
+
+    /**
+     * @brief Get the identifier of a material definition
+     */
+    pub fn material_id(&self, definition: &K) -> u32 {
+        if let Some(&id) = self.material_ids.borrow().get(definition) {
+            return id;
+        }
+
+        let id = self.next_material_id();
+        self.material_ids
+            .borrow_mut()
+            .insert(definition.clone(), id);
+        id
+    }
+}
+
+impl<K> Default for IdAllocator<K> {
+    fn default() -> Self {
+        Self {
+            material_ids: RefCell::new(HashMap::new()),
+            material_count: Cell::new(0),
+            object_count: Cell::new(0),
+        }
+    }
 }
@@ -0,0 +1,590 @@
+use crate::bsdf::{BsdfModel, Conductor, Dielectric, Lambertian, Principled};
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::csg::{DifferenceNode, IntersectionNode};
+use crate::display::DisplayTransform;
+use crate::light::{AmbiantLight, DirectionalLight, Light, OmniDirectionalLight};
+use crate::material::{Material, MaterialTextures};
+use crate::math::{Matrix4, Quaternion, Vector3};
+use crate::sampling::Random;
+use crate::scene::Scene;
+use crate::sdf::{BoxNode, Node, PlaneNode, SphereNode, UnionNode};
+use crate::transform::{InstanceNode, TransformNode};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Position, INT};
+use std::cell::OnceCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::yaml::BVH_THRESHOLD;
+use super::{IdAllocator, LoadError, Loader};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/**
+ * @brief A node created by a script
+ *
+ * The scripts copy their values freely, so the nodes are kept as factories
+ * and built once the script is done. A node used twice is built twice,
+ * unless it is the prototype of instances.
+ */
+#[derive(Clone)]
+pub struct ScriptNode(Rc<dyn Fn() -> Box<dyn Node>>);
+
+/**
+ * @brief A prototype created by a script, built once for all its instances
+ */
+#[derive(Clone)]
+pub struct ScriptPrototype {
+    node: ScriptNode,
+    built: Rc<OnceCell<Arc<dyn Node>>>,
+}
+
+impl ScriptPrototype {
+    fn build(&self) -> Arc<dyn Node> {
+        self.built
+            .get_or_init(|| Arc::from((self.node.0)()))
+            .clone()
+    }
+}
+
+#[derive(Clone)]
+pub struct ScriptLight(Rc<dyn Fn() -> Box<dyn Light>>);
+
+#[derive(Clone)]
+pub struct ScriptCamera(Rc<Camera>);
+
+/**
+ * @brief The value returned by a scene script
+ */
+#[derive(Clone)]
+pub struct ScriptScene {
+    pub camera: ScriptCamera,
+    pub root: ScriptNode,
+    pub lights: Vec<ScriptLight>,
+    pub max_depth: INT,
+}
+
+/**
+ * @brief Convert a number of a script, integer or not
+ */
+fn to_f32(value: &Dynamic) -> ScriptResult<f32> {
+    if let Ok(value) = value.as_float() {
+        return Ok(value as f32);
+    }
+    if let Ok(value) = value.as_int() {
+        return Ok(value as f32);
+    }
+
+    Err(format!("expected a number, got {}", value.type_name()).into())
+}
+
+/**
+ * @brief Convert an array of a script to the nodes it holds
+ */
+fn to_nodes(nodes: Array) -> ScriptResult<Vec<ScriptNode>> {
+    nodes
+        .into_iter()
+        .map(|node| {
+            let type_name = node.type_name();
+            node.try_cast::<ScriptNode>()
+                .ok_or_else(|| format!("expected a node, got {}", type_name).into())
+        })
+        .collect()
+}
+
+fn to_lights(lights: Array) -> ScriptResult<Vec<ScriptLight>> {
+    lights
+        .into_iter()
+        .map(|light| {
+            let type_name = light.type_name();
+            light
+                .try_cast::<ScriptLight>()
+                .ok_or_else(|| format!("expected a light, got {}", type_name).into())
+        })
+        .collect()
+}
+
+/**
+ * @brief Create a transform, scaled, then rotated around X, Y and Z in degrees, then moved
+ */
+fn to_transform(translation: Vector3, rotation: Vector3, scale: Vector3) -> ScriptResult<Matrix4> {
+    let transform = Matrix4::translation(translation)
+        * Matrix4::rotation(Quaternion::from_euler(rotation))
+        * Matrix4::scaling(scale);
+    if transform.inverse().is_none() {
+        return Err("expected a scale without any zero".into());
+    }
+
+    Ok(transform)
+}
+
+fn build_nodes(nodes: &[ScriptNode]) -> Vec<Box<dyn Node>> {
+    nodes.iter().map(|node| (node.0)()).collect()
+}
+
+/**
+ * @brief Load the scenes written as Rhai scripts
+ *
+ * The script evaluates to a scene, built with functions like vec3, color,
+ * material, sphere, union, look_at, omnidirectional and scene. The random
+ * generators are seeded, so that a script always builds the same scene.
+ */
+#[derive(Default)]
+pub struct ScriptLoader {}
+
+impl ScriptLoader {
+    /**
+     * @brief Create an engine with the bindings of the scene types
+     *
+     * @param ids the identifiers of the materials and the objects of the scene
+     */
+    pub fn create_engine(&self, ids: Rc<IdAllocator<()>>) -> Engine {
+        let mut engine = Engine::new();
+
+        // Math
+        engine
+            .register_type_with_name::<Vector3>("Vector3")
+            .register_fn("vec3", |x: Dynamic, y: Dynamic, z: Dynamic| {
+                Ok::<_, Box<EvalAltResult>>(Vector3::new(to_f32(&x)?, to_f32(&y)?, to_f32(&z)?))
+            })
+            .register_get("x", |v: &mut Vector3| v.x as f64)
+            .register_get("y", |v: &mut Vector3| v.y as f64)
+            .register_get("z", |v: &mut Vector3| v.z as f64)
+            .register_fn("+", |a: Vector3, b: Vector3| a + b)
+            .register_fn("-", |a: Vector3, b: Vector3| a - b)
+            .register_fn("*", |a: Vector3, k: f64| a * k as f32)
+            .register_fn("*", |k: f64, a: Vector3| a * k as f32)
+            .register_fn("/", |a: Vector3, k: f64| a / k as f32)
+            .register_fn("dot", |a: Vector3, b: Vector3| a.dot(b) as f64)
+            .register_fn("cross", |a: Vector3, b: Vector3| a.cross(b))
+            .register_fn("length", |a: Vector3| a.length() as f64)
+            .register_fn("normalize", |a: Vector3| a.normalize())
+            .register_fn("to_string", |v: &mut Vector3| {
+                format!("vec3({}, {}, {})", v.x, v.y, v.z)
+            });
+
+        // Deterministic randomness
+        engine
+            .register_type_with_name::<Random>("Random")
+            .register_fn("random", |seed: INT| Random::new(seed as u64))
+            .register_fn("next", |random: &mut Random| random.next_f32() as f64)
+            .register_fn(
+                "range",
+                |random: &mut Random, min: Dynamic, max: Dynamic| {
+                    let (min, max) = (to_f32(&min)?, to_f32(&max)?);
+                    Ok::<_, Box<EvalAltResult>>((min + (max - min) * random.next_f32()) as f64)
+                },
+            );
+
+        // Materials
+        let material_ids = ids.clone();
+        let new_material = move |color: Color, specular: f32, bsdf: Option<BsdfModel>| {
+            let bsdf_ = bsdf.as_ref().map(|model| model.as_bsdf());
+            Material {
+                color: bsdf_.map_or(color, |bsdf| bsdf.albedo()),
+                specular,
+                reflective: 0.0,
+                transparency: 0.0,
+                refractive_index: bsdf_.map_or(1.0, |bsdf| bsdf.refractive_index()),
+                absorption: Color::BLACK,
+                bsdf,
+                textures: MaterialTextures::NONE,
+                id: material_ids.next_material_id(),
+                object_id: 0,
+            }
+        };
+        let new_material = Rc::new(new_material);
+        engine
+            .register_type_with_name::<Color>("Color")
+            .register_fn("color", |r: Dynamic, g: Dynamic, b: Dynamic| {
+                Ok::<_, Box<EvalAltResult>>(Color::new(to_f32(&r)?, to_f32(&g)?, to_f32(&b)?))
+            })
+            .register_fn("*", |a: Color, k: f64| a * k as f32)
+            .register_fn("*", |a: Color, b: Color| a * b)
+            .register_fn("+", |a: Color, b: Color| a + b)
+            .register_type_with_name::<Material>("Material");
+
+        let material = new_material.clone();
+        engine.register_fn("material", move |color: Color, specular: Dynamic| {
+            Ok::<_, Box<EvalAltResult>>(material(color, to_f32(&specular)?, None))
+        });
+        let material = new_material.clone();
+        engine.register_fn("lambertian", move |color: Color| {
+            material(
+                color,
+                -1.0,
+                Some(BsdfModel::Lambertian(Lambertian { color })),
+            )
+        });
+        let material = new_material.clone();
+        engine.register_fn("conductor", move |color: Color, roughness: Dynamic| {
+            let roughness = to_f32(&roughness)?;
+            let bsdf = BsdfModel::Conductor(Conductor { color, roughness });
+            Ok::<_, Box<EvalAltResult>>(material(color, -1.0, Some(bsdf)))
+        });
+        let material = new_material.clone();
+        engine.register_fn(
+            "dielectric",
+            move |color: Color, roughness: Dynamic, refractive_index: Dynamic| {
+                let bsdf = BsdfModel::Dielectric(Dielectric {
+                    color,
+                    roughness: to_f32(&roughness)?,
+                    refractive_index: to_f32(&refractive_index)?,
+                });
+                Ok::<_, Box<EvalAltResult>>(material(color, -1.0, Some(bsdf)))
+            },
+        );
+        let material = new_material;
+        engine.register_fn(
+            "principled",
+            move |color: Color, metallic: Dynamic, roughness: Dynamic| {
+                let bsdf = BsdfModel::Principled(Principled {
+                    color,
+                    metallic: to_f32(&metallic)?,
+                    roughness: to_f32(&roughness)?,
+                    specular: 0.5,
+                    transmission: 0.0,
+                    refractive_index: 1.5,
+                });
+                Ok::<_, Box<EvalAltResult>>(material(color, -1.0, Some(bsdf)))
+            },
+        );
+        engine
+            .register_set("reflective", |m: &mut Material, value: Dynamic| {
+                m.reflective = to_f32(&value)?;
+                Ok::<_, Box<EvalAltResult>>(())
+            })
+            .register_set("transparency", |m: &mut Material, value: Dynamic| {
+                m.transparency = to_f32(&value)?;
+                Ok::<_, Box<EvalAltResult>>(())
+            })
+            .register_set("refractive_index", |m: &mut Material, value: Dynamic| {
+                m.refractive_index = to_f32(&value)?;
+                Ok::<_, Box<EvalAltResult>>(())
+            })
+            .register_set("absorption", |m: &mut Material, value: Color| {
+                m.absorption = value;
+            });
+
+        // Nodes, each building gets a new object identifier
+        engine.register_type_with_name::<ScriptNode>("Node");
+        let object_ids = ids.clone();
+        engine.register_fn(
+            "sphere",
+            move |position: Vector3, radius: Dynamic, material: Material| {
+                let (ids, radius) = (object_ids.clone(), to_f32(&radius)?);
+                Ok::<_, Box<EvalAltResult>>(ScriptNode(Rc::new(move || {
+                    let object_id = ids.next_object_id();
+                    Box::new(SphereNode {
+                        position,
+                        radius,
+                        material: Material {
+                            object_id,
+                            ..material
+                        },
+                    })
+                })))
+            },
+        );
+        let object_ids = ids.clone();
+        engine.register_fn(
+            "plane",
+            move |position: Vector3, normal: Vector3, material: Material| {
+                let ids = object_ids.clone();
+                ScriptNode(Rc::new(move || {
+                    let object_id = ids.next_object_id();
+                    Box::new(PlaneNode {
+                        position,
+                        normal,
+                        material: Material {
+                            object_id,
+                            ..material
+                        },
+                    })
+                }))
+            },
+        );
+        let object_ids = ids.clone();
+        engine.register_fn(
+            "box",
+            move |position: Vector3, size: Vector3, rotation: Vector3, material: Material| {
+                let ids = object_ids.clone();
+                ScriptNode(Rc::new(move || {
+                    let object_id = ids.next_object_id();
+                    Box::new(BoxNode::new(
+                        position,
+                        size,
+                        rotation,
+                        Material {
+                            object_id,
+                            ..material
+                        },
+                    ))
+                }))
+            },
+        );
+        engine
+            .register_fn("union", |nodes: Array| {
+                let nodes = to_nodes(nodes)?;
+                Ok::<_, Box<EvalAltResult>>(ScriptNode(Rc::new(move || {
+                    // Accelerate the large unions with a bounding volume hierarchy
+                    let nodes = build_nodes(&nodes);
+                    if nodes.len() > BVH_THRESHOLD {
+                        Box::new(BvhNode::new(nodes))
+                    } else {
+                        Box::new(UnionNode { nodes })
+                    }
+                })))
+            })
+            .register_fn("intersection", |nodes: Array| {
+                let nodes = to_nodes(nodes)?;
+                Ok::<_, Box<EvalAltResult>>(ScriptNode(Rc::new(move || {
+                    Box::new(IntersectionNode {
+                        nodes: build_nodes(&nodes),
+                    })
+                })))
+            })
+            .register_fn("difference", |nodes: Array| {
+                let nodes = to_nodes(nodes)?;
+                Ok::<_, Box<EvalAltResult>>(ScriptNode(Rc::new(move || {
+                    Box::new(DifferenceNode {
+                        nodes: build_nodes(&nodes),
+                    })
+                })))
+            })
+            .register_fn(
+                "transform",
+                |node: ScriptNode, translation: Vector3, rotation: Vector3, scale: Vector3| {
+                    let transform = to_transform(translation, rotation, scale)?;
+                    Ok::<_, Box<EvalAltResult>>(ScriptNode(Rc::new(move || {
+                        Box::new(TransformNode::new((node.0)(), transform).unwrap())
+                    })))
+                },
+            );
+
+        // Instances sharing the geometry of a prototype, each with its own object identifier
+        engine
+            .register_type_with_name::<ScriptPrototype>("Prototype")
+            .register_fn("prototype", |node: ScriptNode| ScriptPrototype {
+                node,
+                built: Rc::new(OnceCell::new()),
+            });
+        let object_ids = ids.clone();
+        engine.register_fn(
+            "instance",
+            move |prototype: ScriptPrototype,
+                  translation: Vector3,
+                  rotation: Vector3,
+                  scale: Vector3| {
+                let (ids, transform) = (
+                    object_ids.clone(),
+                    to_transform(translation, rotation, scale)?,
+                );
+                Ok::<_, Box<EvalAltResult>>(ScriptNode(Rc::new(move || {
+                    let object_id = ids.next_object_id();
+                    Box::new(
+                        InstanceNode::new(prototype.build(), transform, None, object_id).unwrap(),
+                    )
+                })))
+            },
+        );
+        let object_ids = ids;
+        engine.register_fn(
+            "instance",
+            move |prototype: ScriptPrototype,
+                  translation: Vector3,
+                  rotation: Vector3,
+                  scale: Vector3,
+                  material: Material| {
+                let (ids, transform) = (
+                    object_ids.clone(),
+                    to_transform(translation, rotation, scale)?,
+                );
+                Ok::<_, Box<EvalAltResult>>(ScriptNode(Rc::new(move || {
+                    let object_id = ids.next_object_id();
+                    Box::new(
+                        InstanceNode::new(prototype.build(), transform, Some(material), object_id)
+                            .unwrap(),
+                    )
+                })))
+            },
+        );
+
+        // Lights, white unless a color is given
+        engine
+            .register_type_with_name::<ScriptLight>("Light")
+            .register_fn("ambiant", |intensity: Dynamic, color: Color| {
+                let intensity = to_f32(&intensity)?;
+                Ok::<_, Box<EvalAltResult>>(ScriptLight(Rc::new(move || {
+                    Box::new(AmbiantLight { color, intensity })
+                })))
+            })
+            .register_fn(
+                "omnidirectional",
+                |intensity: Dynamic, color: Color, position: Vector3| {
+                    let intensity = to_f32(&intensity)?;
+                    Ok::<_, Box<EvalAltResult>>(ScriptLight(Rc::new(move || {
+                        Box::new(OmniDirectionalLight {
+                            position,
+                            color,
+                            intensity,
+                            cast_shadows: true,
+                        })
+                    })))
+                },
+            )
+            .register_fn(
+                "directional",
+                |intensity: Dynamic, color: Color, direction: Vector3| {
+                    let intensity = to_f32(&intensity)?;
+                    Ok::<_, Box<EvalAltResult>>(ScriptLight(Rc::new(move || {
+                        Box::new(DirectionalLight {
+                            direction,
+                            color,
+                            intensity,
+                            cast_shadows: true,
+                        })
+                    })))
+                },
+            );
+        engine.register_fn("ambiant", |intensity: Dynamic| {
+            let intensity = to_f32(&intensity)?;
+            Ok::<_, Box<EvalAltResult>>(ScriptLight(Rc::new(move || {
+                Box::new(AmbiantLight {
+                    color: Color::WHITE,
+                    intensity,
+                })
+            })))
+        });
+        engine.register_fn(
+            "omnidirectional",
+            |intensity: Dynamic, position: Vector3| {
+                let intensity = to_f32(&intensity)?;
+                Ok::<_, Box<EvalAltResult>>(ScriptLight(Rc::new(move || {
+                    Box::new(OmniDirectionalLight {
+                        position,
+                        color: Color::WHITE,
+                        intensity,
+                        cast_shadows: true,
+                    })
+                })))
+            },
+        );
+        engine.register_fn("directional", |intensity: Dynamic, direction: Vector3| {
+            let intensity = to_f32(&intensity)?;
+            Ok::<_, Box<EvalAltResult>>(ScriptLight(Rc::new(move || {
+                Box::new(DirectionalLight {
+                    direction,
+                    color: Color::WHITE,
+                    intensity,
+                    cast_shadows: true,
+                })
+            })))
+        });
+
+        // Camera and scene
+        engine
+            .register_type_with_name::<ScriptCamera>("Camera")
+            .register_fn(
+                "look_at",
+                |position: Vector3, target: Vector3, fov: Dynamic| {
+                    let camera = Camera::look_at(
+                        position,
+                        target,
+                        Vector3::new(0.0, 1.0, 0.0),
+                        to_f32(&fov)?,
+                        0.0,
+                    );
+                    Ok::<_, Box<EvalAltResult>>(ScriptCamera(Rc::new(camera)))
+                },
+            )
+            .register_fn(
+                "camera",
+                |position: Vector3, direction: Vector3, fov: Dynamic| {
+                    let camera = Camera::new(
+                        position,
+                        direction,
+                        Vector3::new(0.0, 1.0, 0.0),
+                        to_f32(&fov)?,
+                        0.0,
+                    );
+                    Ok::<_, Box<EvalAltResult>>(ScriptCamera(Rc::new(camera)))
+                },
+            )
+            .register_type_with_name::<ScriptScene>("Scene")
+            .register_fn(
+                "scene",
+                |camera: ScriptCamera, root: ScriptNode, lights: Array| {
+                    Ok::<_, Box<EvalAltResult>>(ScriptScene {
+                        camera,
+                        root,
+                        lights: to_lights(lights)?,
+                        max_depth: Scene::DEFAULT_MAX_DEPTH as INT,
+                    })
+                },
+            )
+            .register_get_set(
+                "max_depth",
+                |scene: &mut ScriptScene| scene.max_depth,
+                |scene: &mut ScriptScene, max_depth: INT| scene.max_depth = max_depth,
+            );
+
+        engine
+    }
+
+    /**
+     * @brief Run a scene script
+     *
+     * @param script the source of the script
+     *
+     * @return the scene, or the error and its position
+     */
+    pub fn eval_scene(&self, script: &str) -> Result<Scene, (String, Position)> {
+        let ids = Rc::new(IdAllocator::default());
+        let engine = self.create_engine(ids);
+
+        let ast = engine
+            .compile(script)
+            .map_err(|error| (error.0.to_string(), error.1))?;
+        let scene = engine.eval_ast::<ScriptScene>(&ast).map_err(|mut error| {
+            let position = error.take_position();
+            (error.to_string(), position)
+        })?;
+
+        let camera = &scene.camera.0;
+        Ok(Scene {
+            camera: Camera::new(
+                camera.position,
+                camera.direction,
+                camera.up,
+                camera.fov,
+                camera.roll,
+            ),
+            root: (scene.root.0)(),
+            lights: scene.lights.iter().map(|light| (light.0)()).collect(),
+            max_depth: scene.max_depth.max(0) as u32,
+            display: DisplayTransform::default(),
+            textures: Vec::new(),
+        })
+    }
+}
+
+impl Loader for ScriptLoader {
+    fn load_scene_from_file(&self, path: &Path) -> Result<Scene, LoadError> {
+        let script =
+            fs::read_to_string(path).map_err(|error| LoadError::Io(path.to_path_buf(), error))?;
+
+        self.eval_scene(&script).map_err(|(message, position)| {
+            let message = format!(
+                "{}:{}: {}",
+                position.line().unwrap_or(0),
+                position.position().unwrap_or(0),
+                message
+            );
+            LoadError::Syntax(path.to_path_buf(), message)
+        })
+    }
+}
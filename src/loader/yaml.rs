@@ -1,132 +1,1724 @@
+use crate::bsdf::{BsdfModel, Conductor, Dielectric, Lambertian, Principled};
+use crate::bvh::BvhNode;
 use crate::camera::Camera;
 use crate::color::Color;
+use crate::csg::{DifferenceNode, IntersectionNode};
+use crate::display::{DisplayTransform, ToneMapping};
+use crate::field::{
+    DisplacementNode, RepetitionNode, SmoothSubtractionNode, SmoothUnionNode, TwistNode,
+};
 use crate::light::{AmbiantLight, DirectionalLight, Light, OmniDirectionalLight};
-use crate::material::Material;
-use crate::math::Vector3;
+use crate::material::{Material, MaterialTextures};
+use crate::math::{Matrix4, Quaternion, Vector3};
+use crate::mesh::MeshNode;
 use crate::scene::Scene;
-use crate::sdf::{Node, SphereNode, UnionNode};
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
+use crate::sdf::{
+    BoxNode, ConeNode, CylinderNode, DiskNode, Node, PlaneNode, SphereNode, TorusNode, UnionNode,
+};
+use crate::texture::{Texture, TextureFilter, Wrap};
+use crate::transform::{InstanceNode, TransformNode};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use yaml_rust::yaml::Hash;
 use yaml_rust::Yaml;
 use yaml_rust::YamlLoader as YamlLoader_;
 
-use super::Loader;
+use super::{FieldError, IdAllocator, LoadError, Loader};
 
-pub struct YamlLoader {}
+/**
+ * @brief Number of nodes from which an union is stored in a BVH
+ */
+pub const BVH_THRESHOLD: usize = 8;
+
+/**
+ * @brief The keys of an instance node which keep its prototype shared
+ */
+const INSTANCE_KEYS: [&str; 6] = [
+    "type",
+    "prototype",
+    "translation",
+    "rotation",
+    "scale",
+    "material",
+];
+
+/**
+ * @brief A value of a scene file, with its path for the error messages
+ */
+#[derive(Clone)]
+pub struct Field<'a> {
+    pub data: &'a Yaml,
+
+    /**
+     * @brief The keys and the indices leading to the value, like root.nodes[3]
+     */
+    pub path: String,
+}
+
+impl<'a> Field<'a> {
+    pub fn root(data: &'a Yaml) -> Self {
+        Field {
+            data,
+            path: String::new(),
+        }
+    }
+
+    /**
+     * @brief Get a value of a mapping, missing if there is no such key
+     */
+    pub fn get(&self, key: &str) -> Field<'a> {
+        let data: &'a Yaml = self.data;
+        let path = if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        };
+
+        Field {
+            data: &data[key],
+            path,
+        }
+    }
+
+    /**
+     * @brief Get the values of a list, none if it isn't a list
+     */
+    pub fn items(&self) -> Vec<Field<'a>> {
+        let items = match self.data {
+            Yaml::Array(items) => items.as_slice(),
+            _ => &[],
+        };
+
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, data)| Field {
+                data,
+                path: format!("{}[{}]", self.path, index),
+            })
+            .collect()
+    }
+
+    pub fn is_missing(&self) -> bool {
+        matches!(self.data, Yaml::BadValue | Yaml::Null)
+    }
+}
+
+/**
+ * @brief Describe a value for the error messages
+ */
+fn describe(data: &Yaml) -> String {
+    match data {
+        Yaml::Real(value) => value.clone(),
+        Yaml::Integer(value) => value.to_string(),
+        Yaml::String(value) => format!("{:?}", value),
+        Yaml::Boolean(value) => value.to_string(),
+        Yaml::Array(_) => String::from("a list"),
+        Yaml::Hash(_) => String::from("a mapping"),
+        Yaml::Alias(_) => String::from("an alias"),
+        Yaml::Null | Yaml::BadValue => String::from("nothing"),
+    }
+}
+
+/**
+ * @brief Merge a mapping into another one, recursively
+ *
+ * The values of the second mapping replace the ones of the first, except the
+ * mappings which are merged.
+ */
+fn merge_yaml(base: &Yaml, over: &Yaml) -> Yaml {
+    let (Yaml::Hash(base_hash), Yaml::Hash(over_hash)) = (base, over) else {
+        return over.clone();
+    };
+
+    let mut merged = base_hash.clone();
+    for (key, value) in over_hash {
+        let value = match base_hash.get(key) {
+            Some(base_value) => merge_yaml(base_value, value),
+            None => value.clone(),
+        };
+        merged.insert(key.clone(), value);
+    }
+
+    Yaml::Hash(merged)
+}
+
+/**
+ * @brief Where a value of a merged scene comes from
+ */
+#[derive(Clone, Debug)]
+struct Origin {
+    /**
+     * @brief The path of the value in the merged scene
+     */
+    merged: String,
+
+    file: PathBuf,
+
+    /**
+     * @brief The path of the value in its file
+     */
+    original: String,
+}
+
+/**
+ * @brief Get the rest of a path after a prefix, like .radius or [3], None if it isn't in the prefix
+ */
+fn path_suffix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    if prefix.is_empty() {
+        return Some(path);
+    }
+
+    let rest = path.strip_prefix(prefix)?;
+    (rest.is_empty() || rest.starts_with(['.', '['])).then_some(rest)
+}
+
+/**
+ * @brief Append the rest of a path to a prefix
+ */
+fn join_path(prefix: &str, rest: &str) -> String {
+    match (prefix.is_empty(), rest.strip_prefix('.')) {
+        (true, Some(rest)) => rest.to_string(),
+        (false, None) if !rest.is_empty() && !rest.starts_with('[') => {
+            format!("{}.{}", prefix, rest)
+        }
+        _ => format!("{}{}", prefix, rest),
+    }
+}
+
+/**
+ * @brief Move the origins of the values at a path to another path
+ */
+fn relocate(origins: &[Origin], from: &str, to: &str) -> Vec<Origin> {
+    origins
+        .iter()
+        .filter_map(|origin| {
+            if let Some(rest) = path_suffix(&origin.merged, from) {
+                // The origin is inside the moved value
+                Some(Origin {
+                    merged: join_path(to, rest),
+                    ..origin.clone()
+                })
+            } else {
+                // The moved value is inside the origin
+                path_suffix(from, &origin.merged).map(|rest| Origin {
+                    merged: to.to_string(),
+                    file: origin.file.clone(),
+                    original: join_path(&origin.original, rest),
+                })
+            }
+        })
+        .collect()
+}
+
+/**
+ * @brief Find the file and the path of an error in a merged scene
+ *
+ * The errors already having a file, like the ones of the includes, are kept.
+ */
+fn locate(error: FieldError, origins: &[Origin]) -> FieldError {
+    if error.file.is_some() {
+        return error;
+    }
+
+    let origin = origins
+        .iter()
+        .filter(|origin| path_suffix(&error.path, &origin.merged).is_some())
+        .max_by_key(|origin| origin.merged.len());
+    match origin {
+        Some(origin) => FieldError {
+            file: Some(origin.file.clone()),
+            path: join_path(
+                &origin.original,
+                path_suffix(&error.path, &origin.merged).unwrap_or(""),
+            ),
+            ..error
+        },
+        None => error,
+    }
+}
+
+/**
+ * @brief Merge an included scene into a scene
+ *
+ * The materials and the prototypes of both scenes are available, the lights
+ * are added and the roots are put in an union. The other values of the scene
+ * replace the ones of the included scene.
+ *
+ * @return the merged scene, and where its values come from
+ */
+fn merge_scenes(
+    (included, included_origins): (&Yaml, &[Origin]),
+    (scene, scene_origins): (&Yaml, &[Origin]),
+) -> (Yaml, Vec<Origin>) {
+    let Some(included_hash) = included.as_hash() else {
+        return (scene.clone(), scene_origins.to_vec());
+    };
+    let Some(scene_hash) = scene.as_hash() else {
+        return (included.clone(), included_origins.to_vec());
+    };
+
+    // The values only in the included scene keep their origins
+    let mut origins: Vec<Origin> = included_hash
+        .keys()
+        .filter(|key| !scene_hash.contains_key(key))
+        .filter_map(Yaml::as_str)
+        .flat_map(|key| relocate(included_origins, key, key))
+        .collect();
+
+    let mut merged = included_hash.clone();
+    for (key, value) in scene_hash {
+        let name = key.as_str().unwrap_or("");
+        let value = match (key.as_str(), included_hash.get(key), value) {
+            (Some("materials" | "prototypes"), Some(Yaml::Hash(library)), Yaml::Hash(entries)) => {
+                for entry in library.keys().filter(|entry| !entries.contains_key(entry)) {
+                    let entry_path = format!("{}.{}", name, entry.as_str().unwrap_or(""));
+                    origins.extend(relocate(included_origins, &entry_path, &entry_path));
+                }
+                origins.extend(relocate(scene_origins, name, name));
+
+                let mut library = library.clone();
+                library.extend(entries.clone());
+                Yaml::Hash(library)
+            }
+            (Some("lights"), Some(Yaml::Array(lights)), Yaml::Array(scene_lights)) => {
+                origins.extend(relocate(included_origins, name, name));
+                for index in 0..scene_lights.len() {
+                    origins.extend(relocate(
+                        scene_origins,
+                        &format!("lights[{}]", index),
+                        &format!("lights[{}]", lights.len() + index),
+                    ));
+                }
+
+                Yaml::Array(lights.iter().chain(scene_lights).cloned().collect())
+            }
+            (Some("root"), Some(root), scene_root) => {
+                origins.extend(relocate(included_origins, "root", "root.nodes[0]"));
+                origins.extend(relocate(scene_origins, "root", "root.nodes[1]"));
+
+                let mut union = Hash::new();
+                union.insert(
+                    Yaml::String(String::from("type")),
+                    Yaml::String(String::from("union")),
+                );
+                union.insert(
+                    Yaml::String(String::from("nodes")),
+                    Yaml::Array(vec![root.clone(), scene_root.clone()]),
+                );
+                Yaml::Hash(union)
+            }
+            _ => {
+                origins.extend(relocate(scene_origins, name, name));
+                value.clone()
+            }
+        };
+        merged.insert(key.clone(), value);
+    }
+
+    (Yaml::Hash(merged), origins)
+}
+
+/**
+ * @brief Make the relative file paths of an included scene relative to the including scene
+ *
+ * @param data the included scene
+ * @param directory the directory of the included scene, relatively to the including scene
+ */
+fn rebase_paths(data: &mut Yaml, directory: &Path) {
+    match data {
+        Yaml::Hash(hash) => {
+            for (key, value) in hash.iter_mut() {
+                match (key.as_str(), value) {
+                    (Some(key), Yaml::String(path))
+                        if key == "path" || key.ends_with("_texture") =>
+                    {
+                        *path = directory.join(&*path).to_string_lossy().into_owned();
+                    }
+                    (_, value) => rebase_paths(value, directory),
+                }
+            }
+        }
+        Yaml::Array(items) => {
+            for item in items {
+                rebase_paths(item, directory);
+            }
+        }
+        _ => {}
+    }
+}
+
+/**
+ * @brief Load the scenes written in YAML
+ *
+ * The parsing goes on after an invalid value, with a default value in its
+ * place, so that all the errors of a file are reported at once.
+ *
+ * Most fields are optional:
+ * - the camera is at the origin and looks toward +Z
+ * - the objects are at the origin, with a radius or a size of 1, facing up
+ * - the missing components of a vector or a color are 0
+ * - an object without a material is white and diffuse
+ * - a scene without lights has a headlight, a light at the camera
+ *
+ * The vectors can be written as [x, y, z] and the colors as [r, g, b] or as
+ * hexadecimal sRGB colors, like "#ff8800".
+ */
+#[derive(Default)]
+pub struct YamlLoader {
+    /**
+     * @brief The directory against which the relative paths are resolved
+     */
+    pub base_directory: PathBuf,
+
+    /**
+     * @brief Warn about the unknown keys, which are ignored otherwise
+     */
+    pub strict: bool,
+
+    ids: IdAllocator<Yaml>,
+    errors: RefCell<Vec<FieldError>>,
+    warnings: RefCell<Vec<FieldError>>,
+
+    /**
+     * @brief The materials of the scene, by name
+     */
+    materials: RefCell<HashMap<String, Yaml>>,
+
+    /**
+     * @brief The reusable subtrees of the scene, by name
+     */
+    prototypes: RefCell<HashMap<String, Yaml>>,
+
+    /**
+     * @brief The prototypes being parsed, to detect the cycles
+     */
+    expanding: RefCell<Vec<String>>,
+
+    /**
+     * @brief The prototypes already parsed, shared by their instances
+     */
+    shared: RefCell<HashMap<String, Arc<dyn Node>>>,
+
+    /**
+     * @brief The textures of the scene, and their indices by image and settings
+     */
+    textures: RefCell<Vec<Texture>>,
+    texture_ids: RefCell<HashMap<(PathBuf, bool, TextureFilter, Wrap), usize>>,
+}
 
 impl YamlLoader {
-    pub fn parse_vector3(&self, data: &Yaml) -> Vector3 {
-        Vector3::new(
-            data["x"].as_f64().unwrap() as f32,
-            data["y"].as_f64().unwrap() as f32,
-            data["z"].as_f64().unwrap() as f32,
-        )
+    /**
+     * @brief Record an invalid value
+     *
+     * @param data the value
+     * @param expected what the value should be
+     */
+    fn error(&self, data: &Field, expected: &str) {
+        self.push_error(FieldError {
+            file: None,
+            path: data.path.clone(),
+            expected: expected.to_string(),
+            found: describe(data.data),
+        });
     }
 
-    pub fn parse_camera(&self, data: &Yaml) -> Camera {
-        Camera {
-            position: self.parse_vector3(&data["position"]),
-            view_port: self.parse_vector3(&data["view_port"]),
+    /**
+     * @brief Record an error once, the materials of the library being parsed
+     * for each of their uses
+     */
+    fn push_error(&self, error: FieldError) {
+        let mut errors = self.errors.borrow_mut();
+        if !errors.contains(&error) {
+            errors.push(error);
         }
     }
 
-    pub fn parse_union_node(&self, data: &Yaml) -> Box<UnionNode> {
-        Box::new(UnionNode {
-            nodes: data["nodes"]
-                .to_owned()
-                .into_iter()
-                .map(|node| self.parse_node(&node))
-                .collect(),
+    /**
+     * @brief Take the errors recorded so far
+     */
+    pub fn take_errors(&self) -> Vec<FieldError> {
+        self.errors.take()
+    }
+
+    /**
+     * @brief Take the warnings recorded so far, about the unknown keys
+     */
+    pub fn take_warnings(&self) -> Vec<FieldError> {
+        self.warnings.take()
+    }
+
+    /**
+     * @brief Warn about the keys of a mapping which aren't known, in strict mode
+     *
+     * @param data the mapping
+     * @param keys the known keys
+     */
+    fn check_keys(&self, data: &Field, keys: &[&str]) {
+        let Some(hash) = data.data.as_hash().filter(|_| self.strict) else {
+            return;
+        };
+
+        for key in hash.keys() {
+            if key.as_str().is_some_and(|key| keys.contains(&key)) {
+                continue;
+            }
+            self.warnings.borrow_mut().push(FieldError {
+                file: None,
+                path: data.path.clone(),
+                expected: format!("one of the keys {}", keys.join(", ")),
+                found: describe(key),
+            });
+        }
+    }
+
+    fn parse_number(&self, data: &Field) -> f32 {
+        match data.data {
+            Yaml::Real(_) | Yaml::Integer(_) => data
+                .data
+                .as_f64()
+                .or_else(|| data.data.as_i64().map(|value| value as f64))
+                .unwrap() as f32,
+            _ => {
+                self.error(data, "a number");
+                0.0
+            }
+        }
+    }
+
+    fn parse_number_or(&self, data: &Field, default: f32) -> f32 {
+        if data.is_missing() {
+            default
+        } else {
+            self.parse_number(data)
+        }
+    }
+
+    fn parse_bool_or(&self, data: &Field, default: bool) -> bool {
+        if data.is_missing() {
+            return default;
+        }
+
+        data.data.as_bool().unwrap_or_else(|| {
+            self.error(data, "a boolean");
+            default
+        })
+    }
+
+    /**
+     * @brief Get the values of a list, a missing list being empty
+     */
+    fn parse_list<'a>(&self, data: &Field<'a>, expected: &str) -> Vec<Field<'a>> {
+        if !data.is_missing() && data.data.as_vec().is_none() {
+            self.error(data, expected);
+        }
+
+        data.items()
+    }
+
+    /**
+     * @brief Parse a vector, either {x, y, z} or [x, y, z]
+     */
+    pub fn parse_vector3(&self, data: &Field) -> Vector3 {
+        match data.data {
+            Yaml::Hash(_) => {
+                self.check_keys(data, &["x", "y", "z"]);
+                Vector3::new(
+                    self.parse_number_or(&data.get("x"), 0.0),
+                    self.parse_number_or(&data.get("y"), 0.0),
+                    self.parse_number_or(&data.get("z"), 0.0),
+                )
+            }
+            Yaml::Array(items) if items.len() == 3 => {
+                let items = data.items();
+                Vector3::new(
+                    self.parse_number(&items[0]),
+                    self.parse_number(&items[1]),
+                    self.parse_number(&items[2]),
+                )
+            }
+            _ => {
+                self.error(data, "a vector, like [x, y, z] or {x, y, z}");
+                Vector3::default()
+            }
+        }
+    }
+
+    fn parse_vector3_or(&self, data: &Field, default: Vector3) -> Vector3 {
+        if data.is_missing() {
+            default
+        } else {
+            self.parse_vector3(data)
+        }
+    }
+
+    pub fn parse_camera(&self, data: &Field) -> Camera {
+        self.check_keys(
+            data,
+            &[
+                "position",
+                "view_port",
+                "look_at",
+                "direction",
+                "up",
+                "fov",
+                "roll",
+                "yaw",
+                "pitch",
+            ],
+        );
+        let position = self.parse_vector3_or(&data.get("position"), Vector3::default());
+
+        // Legacy cameras only have a view port
+        let has_orientation = ["look_at", "direction", "yaw", "pitch", "fov"]
+            .iter()
+            .any(|key| !data.get(key).is_missing());
+        if !has_orientation && !data.get("view_port").is_missing() {
+            return Camera::from_view_port(position, self.parse_vector3(&data.get("view_port")));
+        }
+
+        let fov = self.parse_number_or(&data.get("fov"), Camera::DEFAULT_FOV);
+        let roll = self.parse_number_or(&data.get("roll"), 0.0);
+        let up = self.parse_vector3_or(&data.get("up"), Vector3::new(0.0, 1.0, 0.0));
+
+        if !data.get("look_at").is_missing() {
+            Camera::look_at(
+                position,
+                self.parse_vector3(&data.get("look_at")),
+                up,
+                fov,
+                roll,
+            )
+        } else if !data.get("direction").is_missing() {
+            Camera::new(
+                position,
+                self.parse_vector3(&data.get("direction")),
+                up,
+                fov,
+                roll,
+            )
+        } else {
+            let yaw = self.parse_number_or(&data.get("yaw"), 0.0);
+            let pitch = self.parse_number_or(&data.get("pitch"), 0.0);
+            Camera::from_angles(position, yaw, pitch, roll, fov)
+        }
+    }
+
+    pub fn parse_nodes(&self, data: &Field) -> Vec<Box<dyn Node>> {
+        self.parse_list(data, "a list of nodes")
+            .iter()
+            .map(|node| self.parse_node(node))
+            .collect()
+    }
+
+    pub fn parse_union_node(&self, data: &Field) -> Box<dyn Node> {
+        self.check_keys(data, &["type", "nodes"]);
+        let nodes = self.parse_nodes(&data.get("nodes"));
+
+        // Accelerate the large unions with a bounding volume hierarchy
+        if nodes.len() > BVH_THRESHOLD {
+            Box::new(BvhNode::new(nodes))
+        } else {
+            Box::new(UnionNode { nodes })
+        }
+    }
+
+    pub fn parse_intersection_node(&self, data: &Field) -> Box<IntersectionNode> {
+        self.check_keys(data, &["type", "nodes"]);
+        Box::new(IntersectionNode {
+            nodes: self.parse_nodes(&data.get("nodes")),
+        })
+    }
+
+    pub fn parse_difference_node(&self, data: &Field) -> Box<DifferenceNode> {
+        self.check_keys(data, &["type", "nodes"]);
+        Box::new(DifferenceNode {
+            nodes: self.parse_nodes(&data.get("nodes")),
+        })
+    }
+
+    pub fn parse_smooth_union_node(&self, data: &Field) -> Box<SmoothUnionNode> {
+        self.check_keys(data, &["type", "nodes", "smoothness"]);
+        Box::new(SmoothUnionNode {
+            nodes: self.parse_nodes(&data.get("nodes")),
+            smoothness: self.parse_number(&data.get("smoothness")),
+        })
+    }
+
+    pub fn parse_smooth_subtraction_node(&self, data: &Field) -> Box<SmoothSubtractionNode> {
+        self.check_keys(data, &["type", "nodes", "smoothness"]);
+        Box::new(SmoothSubtractionNode {
+            nodes: self.parse_nodes(&data.get("nodes")),
+            smoothness: self.parse_number(&data.get("smoothness")),
+        })
+    }
+
+    pub fn parse_repetition_node(&self, data: &Field) -> Box<RepetitionNode> {
+        self.check_keys(data, &["type", "node", "period"]);
+        Box::new(RepetitionNode {
+            node: self.parse_node(&data.get("node")),
+            period: self.parse_vector3(&data.get("period")),
+        })
+    }
+
+    pub fn parse_twist_node(&self, data: &Field) -> Box<TwistNode> {
+        self.check_keys(data, &["type", "node", "amount"]);
+        Box::new(TwistNode {
+            node: self.parse_node(&data.get("node")),
+            amount: self.parse_number(&data.get("amount")),
+        })
+    }
+
+    pub fn parse_displacement_node(&self, data: &Field) -> Box<DisplacementNode> {
+        self.check_keys(data, &["type", "node", "amplitude", "frequency"]);
+        Box::new(DisplacementNode {
+            node: self.parse_node(&data.get("node")),
+            amplitude: self.parse_number(&data.get("amplitude")),
+            frequency: self.parse_number(&data.get("frequency")),
         })
     }
 
-    fn parse_color(&self, data: &Yaml) -> Color {
-        Color {
-            r: data["r"].as_f64().unwrap() as f32,
-            g: data["g"].as_f64().unwrap() as f32,
-            b: data["b"].as_f64().unwrap() as f32,
+    /**
+     * @brief Parse a rotation, either Euler angles or {axis, angle}, in degrees
+     */
+    fn parse_rotation(&self, data: &Field) -> Quaternion {
+        if data.is_missing() {
+            return Quaternion::IDENTITY;
+        }
+        if data.get("axis").is_missing() {
+            return Quaternion::from_euler(self.parse_vector3(data));
+        }
+
+        self.check_keys(data, &["axis", "angle"]);
+        let axis_data = data.get("axis");
+        let axis = self.parse_vector3(&axis_data);
+        if axis.length() == 0.0 {
+            self.error(&axis_data, "a vector which isn't null");
+            return Quaternion::IDENTITY;
+        }
+
+        Quaternion::from_axis_angle(axis, self.parse_number(&data.get("angle")))
+    }
+
+    /**
+     * @brief Parse a scale, either the same for all the axes or a vector
+     */
+    fn parse_scale(&self, data: &Field) -> Vector3 {
+        match data.data {
+            Yaml::Real(_) | Yaml::Integer(_) => {
+                let scale = self.parse_number(data);
+                Vector3::new(scale, scale, scale)
+            }
+            _ => self.parse_vector3_or(data, Vector3::new(1.0, 1.0, 1.0)),
+        }
+    }
+
+    /**
+     * @brief Parse a transform, scaled, then rotated, then moved
+     */
+    fn parse_transform(&self, data: &Field) -> Matrix4 {
+        Matrix4::translation(self.parse_vector3_or(&data.get("translation"), Vector3::default()))
+            * Matrix4::rotation(self.parse_rotation(&data.get("rotation")))
+            * Matrix4::scaling(self.parse_scale(&data.get("scale")))
+    }
+
+    /**
+     * @brief Record a transform which can't be inverted, and replace its node by an empty union
+     */
+    fn singular_transform(&self, data: &Field) -> Box<dyn Node> {
+        self.error(&data.get("scale"), "a scale without any zero");
+        Box::new(UnionNode { nodes: Vec::new() })
+    }
+
+    pub fn parse_transform_node(&self, data: &Field) -> Box<dyn Node> {
+        self.check_keys(data, &["type", "node", "translation", "rotation", "scale"]);
+        let transform = self.parse_transform(data);
+
+        match TransformNode::new(self.parse_node(&data.get("node")), transform) {
+            Some(node) => Box::new(node),
+            None => self.singular_transform(data),
+        }
+    }
+
+    fn parse_color(&self, data: &Field) -> Color {
+        let expected = "a color, like [r, g, b], {r, g, b} or \"#rrggbb\"";
+
+        match data.data {
+            Yaml::Hash(_) => {
+                self.check_keys(data, &["r", "g", "b"]);
+                Color {
+                    r: self.parse_number_or(&data.get("r"), 0.0),
+                    g: self.parse_number_or(&data.get("g"), 0.0),
+                    b: self.parse_number_or(&data.get("b"), 0.0),
+                }
+            }
+            Yaml::Array(items) if items.len() == 3 => {
+                let items = data.items();
+                Color {
+                    r: self.parse_number(&items[0]),
+                    g: self.parse_number(&items[1]),
+                    b: self.parse_number(&items[2]),
+                }
+            }
+            Yaml::String(text) => Color::from_hex(text).unwrap_or_else(|| {
+                self.error(data, expected);
+                Color::BLACK
+            }),
+            _ => {
+                self.error(data, expected);
+                Color::BLACK
+            }
         }
     }
 
-    fn parse_material(&self, data: &Yaml) -> Material {
+    fn parse_color_or(&self, data: &Field, default: Color) -> Color {
+        if data.is_missing() {
+            default
+        } else {
+            self.parse_color(data)
+        }
+    }
+
+    /**
+     * @brief Parse the physically based model of a material, if it has a type
+     */
+    fn parse_bsdf(&self, data: &Field) -> Option<BsdfModel> {
+        let number = |key: &str, default: f32| self.parse_number_or(&data.get(key), default);
+
+        let model_type = data.get("type");
+        if model_type.is_missing() {
+            return None;
+        }
+        let bsdf = match model_type.data.as_str() {
+            Some("lambertian") => BsdfModel::Lambertian(Lambertian {
+                color: self.parse_color_or(&data.get("color"), Color::WHITE),
+            }),
+            Some("conductor") => BsdfModel::Conductor(Conductor {
+                color: self.parse_color_or(&data.get("color"), Color::WHITE),
+                roughness: number("roughness", 0.0),
+            }),
+            Some("dielectric") => BsdfModel::Dielectric(Dielectric {
+                color: self.parse_color_or(&data.get("color"), Color::WHITE),
+                roughness: number("roughness", 0.0),
+                refractive_index: number("refractive_index", 1.5),
+            }),
+            Some("principled") => BsdfModel::Principled(Principled {
+                color: self.parse_color_or(&data.get("color"), Color::WHITE),
+                metallic: number("metallic", 0.0),
+                roughness: number("roughness", 0.5),
+                specular: number("specular", 0.5),
+                transmission: number("transmission", 0.0),
+                refractive_index: number("refractive_index", 1.5),
+            }),
+            _ => {
+                self.error(
+                    &model_type,
+                    "one of lambertian, conductor, dielectric or principled",
+                );
+                BsdfModel::Lambertian(Lambertian {
+                    color: Color::WHITE,
+                })
+            }
+        };
+
+        Some(bsdf)
+    }
+
+    /**
+     * @brief Parse a texture, either the path of an image or {path, filter, wrap}
+     *
+     * The images used by several materials are loaded once.
+     *
+     * @param srgb whether the image holds sRGB colors rather than linear values
+     *
+     * @return the index of the texture in the scene, None if there is none
+     */
+    fn parse_texture(&self, data: &Field, srgb: bool) -> Option<usize> {
+        if data.is_missing() {
+            return None;
+        }
+
+        let (path_data, filter, wrap) = if data.data.as_hash().is_some() {
+            self.check_keys(data, &["path", "filter", "wrap"]);
+            let filter_data = data.get("filter");
+            let filter = if filter_data.is_missing() {
+                TextureFilter::Trilinear
+            } else {
+                filter_data
+                    .data
+                    .as_str()
+                    .and_then(TextureFilter::from_name)
+                    .unwrap_or_else(|| {
+                        self.error(&filter_data, "one of bilinear or trilinear");
+                        TextureFilter::Trilinear
+                    })
+            };
+            let wrap_data = data.get("wrap");
+            let wrap = if wrap_data.is_missing() {
+                Wrap::Repeat
+            } else {
+                wrap_data
+                    .data
+                    .as_str()
+                    .and_then(Wrap::from_name)
+                    .unwrap_or_else(|| {
+                        self.error(&wrap_data, "one of repeat or clamp");
+                        Wrap::Repeat
+                    })
+            };
+            (data.get("path"), filter, wrap)
+        } else {
+            (data.clone(), TextureFilter::Trilinear, Wrap::Repeat)
+        };
+        let Some(path) = path_data.data.as_str() else {
+            self.error(&path_data, "the path of an image");
+            return None;
+        };
+
+        let path = self.base_directory.join(path);
+        let key = (path.clone(), srgb, filter, wrap);
+        if let Some(&index) = self.texture_ids.borrow().get(&key) {
+            return Some(index);
+        }
+        match Texture::load(&path, srgb, filter, wrap) {
+            Ok(texture) => {
+                let mut textures = self.textures.borrow_mut();
+                textures.push(texture);
+                self.texture_ids
+                    .borrow_mut()
+                    .insert(key, textures.len() - 1);
+                Some(textures.len() - 1)
+            }
+            Err(error) => {
+                self.push_error(FieldError {
+                    file: None,
+                    path: path_data.path.clone(),
+                    expected: String::from("a PNG or JPEG image"),
+                    found: format!("{} ({})", describe(path_data.data), error),
+                });
+                None
+            }
+        }
+    }
+
+    /**
+     * @brief Parse the material of an object, with a new object identifier
+     */
+    fn parse_material(&self, data: &Field) -> Material {
+        if let Some(name) = data.data.as_str() {
+            return self.parse_named_material(data, name);
+        }
+
+        let id = self.ids.material_id(data.data);
+        let object_id = self.ids.next_object_id();
+        if data.data.as_hash().is_none() {
+            if !data.is_missing() {
+                self.error(data, "a material");
+            }
+            return Material {
+                id,
+                object_id,
+                ..Material::DEFAULT_MATERIAL
+            };
+        }
+        self.check_keys(
+            data,
+            &[
+                "type",
+                "color",
+                "specular",
+                "reflective",
+                "transparency",
+                "refractive_index",
+                "absorption",
+                "roughness",
+                "metallic",
+                "transmission",
+                "albedo_texture",
+                "specular_texture",
+                "roughness_texture",
+            ],
+        );
+        let absorption = self.parse_color_or(&data.get("absorption"), Color::BLACK);
+        let textures = MaterialTextures {
+            albedo: self.parse_texture(&data.get("albedo_texture"), true),
+            specular: self.parse_texture(&data.get("specular_texture"), false),
+            roughness: self.parse_texture(&data.get("roughness_texture"), false),
+        };
+
+        // The physically based materials don't use the Phong parameters
+        if let Some(model) = self.parse_bsdf(data) {
+            let bsdf = model.as_bsdf();
+            return Material {
+                color: bsdf.albedo(),
+                specular: -1.0,
+                reflective: 0.0,
+                transparency: 0.0,
+                refractive_index: bsdf.refractive_index(),
+                absorption,
+                bsdf: Some(model),
+                textures,
+                id,
+                object_id,
+            };
+        }
+
         Material {
-            color: self.parse_color(&data["color"]),
-            specular: data["specular"].as_f64().unwrap() as f32,
+            color: self.parse_color_or(&data.get("color"), Color::WHITE),
+            specular: self.parse_number_or(&data.get("specular"), -1.0),
+            reflective: self.parse_number_or(&data.get("reflective"), 0.0),
+            transparency: self.parse_number_or(&data.get("transparency"), 0.0),
+            refractive_index: self.parse_number_or(&data.get("refractive_index"), 1.0),
+            absorption,
+            bsdf: None,
+            textures,
+            id,
+            object_id,
+        }
+    }
+
+    /**
+     * @brief Parse a material of the library, from its name
+     */
+    fn parse_named_material(&self, data: &Field, name: &str) -> Material {
+        // The library holds definitions, not names which could loop
+        let definition = self.materials.borrow().get(name).cloned();
+        match definition {
+            Some(definition) if definition.as_str().is_none() => self.parse_material(&Field {
+                data: &definition,
+                path: format!("materials.{}", name),
+            }),
+            _ => {
+                let expected = match self.library_names(&self.materials) {
+                    names if names.is_empty() => String::from("a material"),
+                    names => format!("a material, or one of the materials {}", names),
+                };
+                self.error(data, &expected);
+                Material {
+                    id: self.ids.next_material_id(),
+                    object_id: self.ids.next_object_id(),
+                    ..Material::DEFAULT_MATERIAL
+                }
+            }
         }
     }
 
-    pub fn parse_sphere_node(&self, data: &Yaml) -> Box<SphereNode> {
+    pub fn parse_sphere_node(&self, data: &Field) -> Box<SphereNode> {
+        self.check_keys(data, &["type", "position", "radius", "material"]);
         Box::new(SphereNode {
-            position: self.parse_vector3(&data["position"]),
-            radius: data["radius"].as_f64().unwrap() as f32,
-            material: self.parse_material(&data["material"]),
+            position: self.parse_vector3_or(&data.get("position"), Vector3::default()),
+            radius: self.parse_number_or(&data.get("radius"), 1.0),
+            material: self.parse_material(&data.get("material")),
         })
     }
-    pub fn parse_node(&self, data: &Yaml) -> Box<dyn Node> {
-        match data["type"].as_str().unwrap() {
-            "union" => self.parse_union_node(data),
-            "sphere" => self.parse_sphere_node(data),
-            _ => panic!("unexpected node type"),
-        }
+
+    pub fn parse_plane_node(&self, data: &Field) -> Box<PlaneNode> {
+        self.check_keys(data, &["type", "position", "normal", "material"]);
+        Box::new(PlaneNode {
+            position: self.parse_vector3_or(&data.get("position"), Vector3::default()),
+            normal: self.parse_vector3_or(&data.get("normal"), Vector3::new(0.0, 1.0, 0.0)),
+            material: self.parse_material(&data.get("material")),
+        })
     }
 
-    pub fn parse_ambiant_light(&self, data: &Yaml) -> Box<AmbiantLight> {
-        Box::new(AmbiantLight {
-            intensity: data["intensity"].as_f64().unwrap() as f32,
+    pub fn parse_disk_node(&self, data: &Field) -> Box<DiskNode> {
+        self.check_keys(data, &["type", "position", "normal", "radius", "material"]);
+        Box::new(DiskNode {
+            position: self.parse_vector3_or(&data.get("position"), Vector3::default()),
+            normal: self.parse_vector3_or(&data.get("normal"), Vector3::new(0.0, 1.0, 0.0)),
+            radius: self.parse_number_or(&data.get("radius"), 1.0),
+            material: self.parse_material(&data.get("material")),
         })
     }
 
-    pub fn parse_omnidirectional_light(&self, data: &Yaml) -> Box<OmniDirectionalLight> {
+    pub fn parse_box_node(&self, data: &Field) -> Box<BoxNode> {
+        self.check_keys(data, &["type", "position", "size", "rotation", "material"]);
+        Box::new(BoxNode::new(
+            self.parse_vector3_or(&data.get("position"), Vector3::default()),
+            self.parse_vector3_or(&data.get("size"), Vector3::new(1.0, 1.0, 1.0)),
+            self.parse_vector3_or(&data.get("rotation"), Vector3::default()),
+            self.parse_material(&data.get("material")),
+        ))
+    }
+
+    pub fn parse_cylinder_node(&self, data: &Field) -> Box<CylinderNode> {
+        self.check_keys(data, &["type", "start", "end", "radius", "material"]);
+        Box::new(CylinderNode {
+            start: self.parse_vector3_or(&data.get("start"), Vector3::default()),
+            end: self.parse_vector3_or(&data.get("end"), Vector3::new(0.0, 1.0, 0.0)),
+            radius: self.parse_number_or(&data.get("radius"), 1.0),
+            material: self.parse_material(&data.get("material")),
+        })
+    }
+
+    pub fn parse_cone_node(&self, data: &Field) -> Box<ConeNode> {
+        self.check_keys(data, &["type", "start", "end", "radius", "material"]);
+        Box::new(ConeNode {
+            start: self.parse_vector3_or(&data.get("start"), Vector3::default()),
+            end: self.parse_vector3_or(&data.get("end"), Vector3::new(0.0, 1.0, 0.0)),
+            radius: self.parse_number_or(&data.get("radius"), 1.0),
+            material: self.parse_material(&data.get("material")),
+        })
+    }
+
+    pub fn parse_torus_node(&self, data: &Field) -> Box<TorusNode> {
+        self.check_keys(
+            data,
+            &[
+                "type",
+                "position",
+                "axis",
+                "major_radius",
+                "minor_radius",
+                "material",
+            ],
+        );
+        Box::new(TorusNode {
+            position: self.parse_vector3_or(&data.get("position"), Vector3::default()),
+            axis: self.parse_vector3_or(&data.get("axis"), Vector3::new(0.0, 1.0, 0.0)),
+            major_radius: self.parse_number_or(&data.get("major_radius"), 1.0),
+            minor_radius: self.parse_number_or(&data.get("minor_radius"), 0.25),
+            material: self.parse_material(&data.get("material")),
+        })
+    }
+
+    /**
+     * @brief Parse a mesh, an empty union if its file can't be loaded
+     */
+    pub fn parse_mesh_node(&self, data: &Field) -> Box<dyn Node> {
+        self.check_keys(data, &["type", "path", "material"]);
+        let material = self.parse_material(&data.get("material"));
+        let path_data = data.get("path");
+        let Some(path) = path_data.data.as_str() else {
+            self.error(&path_data, "the path of an OBJ file");
+            return Box::new(UnionNode { nodes: Vec::new() });
+        };
+
+        // The materials of the mesh belong to the same object
+        let mut mesh = match MeshNode::load_obj(&self.base_directory.join(path), material) {
+            Ok(mesh) => mesh,
+            Err(error) => {
+                self.error(
+                    &path_data,
+                    &format!("a readable OBJ file and MTL file ({})", error),
+                );
+                return Box::new(UnionNode { nodes: Vec::new() });
+            }
+        };
+        for mesh_material in &mut mesh.materials {
+            mesh_material.id = self.ids.next_material_id();
+            mesh_material.object_id = material.object_id;
+        }
+
+        Box::new(mesh)
+    }
+
+    /**
+     * @brief Parse an instance of a prototype, an empty union if there is none
+     *
+     * An instance with only a transform and a material shares the prototype
+     * with the other instances. Any key of the prototype given by the
+     * instance, like its position, replaces it in a transformed copy of the
+     * prototype. The other keys are unknown.
+     */
+    pub fn parse_instance_node(&self, data: &Field) -> Box<dyn Node> {
+        let name_data = data.get("prototype");
+        let name = name_data.data.as_str().unwrap_or("");
+        let Some(prototype) = self.prototypes.borrow().get(name).cloned() else {
+            let expected = match self.library_names(&self.prototypes) {
+                names if names.is_empty() => String::from("the name of a prototype"),
+                names => format!("one of the prototypes {}", names),
+            };
+            self.error(&name_data, &expected);
+            return Box::new(UnionNode { nodes: Vec::new() });
+        };
+
+        // A prototype can't contain itself
+        let expanding = self.expanding.borrow().clone();
+        if let Some(start) = expanding.iter().position(|expanded| expanded == name) {
+            let cycle = expanding[start..].join(" -> ");
+            self.error(
+                &name_data,
+                &format!("a prototype outside of the cycle {} -> {}", cycle, name),
+            );
+            return Box::new(UnionNode { nodes: Vec::new() });
+        }
+
+        let prototype_keys = prototype.as_hash().cloned().unwrap_or_default();
+        let mut keys = INSTANCE_KEYS.to_vec();
+        keys.extend(
+            prototype_keys
+                .keys()
+                .filter_map(|key| key.as_str())
+                .filter(|key| !INSTANCE_KEYS.contains(key)),
+        );
+        self.check_keys(data, &keys);
+
+        let overrides: Hash = data
+            .data
+            .as_hash()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(key, _)| match key.as_str() {
+                Some("material") => true,
+                Some(name) => !INSTANCE_KEYS.contains(&name) && prototype_keys.contains_key(key),
+                None => false,
+            })
+            .collect();
+        let material_data = data.get("material");
+        let shared = overrides.keys().all(|key| key.as_str() == Some("material"));
+        if shared {
+            let prototype = self.parse_shared_prototype(name, &prototype);
+            let material = if material_data.is_missing() {
+                None
+            } else {
+                Some(self.parse_material(&material_data))
+            };
+            let object_id = material.map_or_else(|| self.ids.next_object_id(), |m| m.object_id);
+
+            return match InstanceNode::new(
+                prototype,
+                self.parse_transform(data),
+                material,
+                object_id,
+            ) {
+                Some(node) => Box::new(node),
+                None => self.singular_transform(data),
+            };
+        }
+
+        let definition = merge_yaml(&prototype, &Yaml::Hash(overrides));
+
+        self.expanding.borrow_mut().push(name.to_string());
+        let node = self.parse_node(&Field {
+            data: &definition,
+            path: data.path.clone(),
+        });
+        self.expanding.borrow_mut().pop();
+
+        let has_transform = ["translation", "rotation", "scale"]
+            .iter()
+            .any(|key| !data.get(key).is_missing());
+        if !has_transform {
+            return node;
+        }
+        match TransformNode::new(node, self.parse_transform(data)) {
+            Some(node) => Box::new(node),
+            None => self.singular_transform(data),
+        }
+    }
+
+    /**
+     * @brief Parse a prototype once, for all its instances
+     */
+    fn parse_shared_prototype(&self, name: &str, definition: &Yaml) -> Arc<dyn Node> {
+        if let Some(node) = self.shared.borrow().get(name) {
+            return node.clone();
+        }
+
+        self.expanding.borrow_mut().push(name.to_string());
+        let node: Arc<dyn Node> = Arc::from(self.parse_node(&Field {
+            data: definition,
+            path: format!("prototypes.{}", name),
+        }));
+        self.expanding.borrow_mut().pop();
+
+        self.shared
+            .borrow_mut()
+            .insert(name.to_string(), node.clone());
+        node
+    }
+
+    /**
+     * @brief Parse a node, an empty union if its type is unknown
+     */
+    pub fn parse_node(&self, data: &Field) -> Box<dyn Node> {
+        let node_type = data.get("type");
+        match node_type.data.as_str() {
+            Some("union") => self.parse_union_node(data),
+            Some("intersection") => self.parse_intersection_node(data),
+            Some("difference") => self.parse_difference_node(data),
+            Some("smooth_union") => self.parse_smooth_union_node(data),
+            Some("smooth_subtraction") => self.parse_smooth_subtraction_node(data),
+            Some("repetition") => self.parse_repetition_node(data),
+            Some("twist") => self.parse_twist_node(data),
+            Some("displacement") => self.parse_displacement_node(data),
+            Some("transform") => self.parse_transform_node(data),
+            Some("sphere") => self.parse_sphere_node(data),
+            Some("plane") => self.parse_plane_node(data),
+            Some("disk") => self.parse_disk_node(data),
+            Some("box") => self.parse_box_node(data),
+            Some("cylinder") => self.parse_cylinder_node(data),
+            Some("cone") => self.parse_cone_node(data),
+            Some("torus") => self.parse_torus_node(data),
+            Some("mesh") => self.parse_mesh_node(data),
+            Some("instance") => self.parse_instance_node(data),
+            _ => {
+                self.error(
+                    &node_type,
+                    "one of union, intersection, difference, smooth_union, \
+                     smooth_subtraction, repetition, twist, displacement, transform, \
+                     sphere, plane, disk, box, cylinder, cone, torus, mesh or instance",
+                );
+                Box::new(UnionNode { nodes: Vec::new() })
+            }
+        }
+    }
+
+    /**
+     * @brief Parse the color and the intensity of a light
+     *
+     * A light without a color is white, a light without an intensity has
+     * the power of its color.
+     */
+    fn parse_light_power(&self, data: &Field) -> (Color, f32) {
+        (
+            self.parse_color_or(&data.get("color"), Color::WHITE),
+            self.parse_number_or(&data.get("intensity"), 1.0),
+        )
+    }
+
+    pub fn parse_ambiant_light(&self, data: &Field) -> Box<AmbiantLight> {
+        self.check_keys(data, &["type", "color", "intensity"]);
+        let (color, intensity) = self.parse_light_power(data);
+
+        Box::new(AmbiantLight { color, intensity })
+    }
+
+    /**
+     * @brief Parse the shadow flag of a light, lights cast shadows by default
+     */
+    fn parse_cast_shadows(&self, data: &Field) -> bool {
+        self.parse_bool_or(data, true)
+    }
+
+    pub fn parse_omnidirectional_light(&self, data: &Field) -> Box<OmniDirectionalLight> {
+        self.check_keys(
+            data,
+            &["type", "color", "intensity", "position", "cast_shadows"],
+        );
+        let (color, intensity) = self.parse_light_power(data);
+
         Box::new(OmniDirectionalLight {
-            intensity: data["intensity"].as_f64().unwrap() as f32,
-            position: self.parse_vector3(&data["position"]),
+            color,
+            intensity,
+            position: self.parse_vector3(&data.get("position")),
+            cast_shadows: self.parse_cast_shadows(&data.get("cast_shadows")),
         })
     }
 
-    pub fn parse_directional_light(&self, data: &Yaml) -> Box<DirectionalLight> {
+    pub fn parse_directional_light(&self, data: &Field) -> Box<DirectionalLight> {
+        self.check_keys(
+            data,
+            &["type", "color", "intensity", "direction", "cast_shadows"],
+        );
+        let (color, intensity) = self.parse_light_power(data);
+
         Box::new(DirectionalLight {
-            intensity: data["intensity"].as_f64().unwrap() as f32,
-            direction: self.parse_vector3(&data["direction"]),
+            color,
+            intensity,
+            direction: self.parse_vector3(&data.get("direction")),
+            cast_shadows: self.parse_cast_shadows(&data.get("cast_shadows")),
         })
     }
 
-    pub fn parse_ligth(&self, data: &Yaml) -> Box<dyn Light> {
-        match data["type"].as_str().unwrap() {
-            "ambiant" => self.parse_ambiant_light(data),
-            "omnidirectional" => self.parse_omnidirectional_light(data),
-            "directional" => self.parse_directional_light(data),
-            _ => panic!("unexpected"),
+    /**
+     * @brief Parse a light, none if its type is unknown
+     */
+    pub fn parse_ligth(&self, data: &Field) -> Option<Box<dyn Light>> {
+        let light_type = data.get("type");
+        match light_type.data.as_str() {
+            Some("ambiant") => Some(self.parse_ambiant_light(data)),
+            Some("omnidirectional") => Some(self.parse_omnidirectional_light(data)),
+            Some("directional") => Some(self.parse_directional_light(data)),
+            _ => {
+                self.error(
+                    &light_type,
+                    "one of ambiant, omnidirectional or directional",
+                );
+                None
+            }
         }
     }
 
-    pub fn parse_ligths(&self, data: &Yaml) -> Vec<Box<dyn Light>> {
-        data.to_owned()
-            .into_iter()
-            .map(|light| self.parse_ligth(&light))
+    pub fn parse_ligths(&self, data: &Field) -> Vec<Box<dyn Light>> {
+        self.parse_list(data, "a list of lights")
+            .iter()
+            .filter_map(|light| self.parse_ligth(light))
             .collect()
     }
 
-    pub fn parse_scene(&self, data: &Yaml) -> Scene {
+    /**
+     * @brief Parse the display transform, every field is optional
+     */
+    pub fn parse_display(&self, data: &Field) -> DisplayTransform {
+        self.check_keys(data, &["exposure", "tone_mapping", "srgb"]);
+        let default = DisplayTransform::default();
+        let tone_mapping = data.get("tone_mapping");
+
+        DisplayTransform {
+            exposure: self.parse_number_or(&data.get("exposure"), default.exposure),
+            tone_mapping: if tone_mapping.is_missing() {
+                default.tone_mapping
+            } else {
+                tone_mapping
+                    .data
+                    .as_str()
+                    .and_then(ToneMapping::from_name)
+                    .unwrap_or_else(|| {
+                        self.error(&tone_mapping, "one of clamp, reinhard or aces");
+                        default.tone_mapping
+                    })
+            },
+            srgb: self.parse_bool_or(&data.get("srgb"), default.srgb),
+        }
+    }
+
+    fn parse_max_depth(&self, data: &Field) -> u32 {
+        if data.is_missing() {
+            return Scene::DEFAULT_MAX_DEPTH;
+        }
+
+        match data.data.as_i64() {
+            Some(max_depth) if max_depth >= 0 => max_depth as u32,
+            _ => {
+                self.error(data, "a non-negative integer");
+                Scene::DEFAULT_MAX_DEPTH
+            }
+        }
+    }
+
+    /**
+     * @brief Create the light of the scenes without lights, at the camera
+     */
+    fn create_headlight(&self, camera: &Camera) -> Box<dyn Light> {
+        Box::new(OmniDirectionalLight {
+            position: camera.position,
+            color: Color::WHITE,
+            intensity: 1.0,
+            cast_shadows: false,
+        })
+    }
+
+    /**
+     * @brief Parse the definitions of a library, a mapping from names
+     */
+    fn parse_library(&self, data: &Field, library: &RefCell<HashMap<String, Yaml>>) {
+        let Some(hash) = data.data.as_hash() else {
+            if !data.is_missing() {
+                self.error(data, "a mapping from names to definitions");
+            }
+            return;
+        };
+
+        for (name, definition) in hash {
+            match name.as_str() {
+                Some(name) => {
+                    library
+                        .borrow_mut()
+                        .insert(name.to_string(), definition.clone());
+                }
+                None => self.error(data, "a mapping from names to definitions"),
+            }
+        }
+    }
+
+    fn library_names(&self, library: &RefCell<HashMap<String, Yaml>>) -> String {
+        let mut names: Vec<String> = library.borrow().keys().cloned().collect();
+        names.sort();
+
+        names.join(", ")
+    }
+
+    pub fn parse_scene(&self, data: &Field) -> Scene {
+        self.check_keys(
+            data,
+            &[
+                "include",
+                "materials",
+                "prototypes",
+                "camera",
+                "root",
+                "lights",
+                "max_depth",
+                "display",
+            ],
+        );
+        self.parse_library(&data.get("materials"), &self.materials);
+        self.parse_library(&data.get("prototypes"), &self.prototypes);
+        let camera = self.parse_camera(&data.get("camera"));
+        let lights = if data.get("lights").is_missing() {
+            vec![self.create_headlight(&camera)]
+        } else {
+            self.parse_ligths(&data.get("lights"))
+        };
+
         Scene {
-            camera: self.parse_camera(&data["camera"]),
-            root: self.parse_node(&data["root"]),
-            lights: self.parse_ligths(&data["lights"]),
+            camera,
+            root: self.parse_node(&data.get("root")),
+            lights,
+            max_depth: self.parse_max_depth(&data.get("max_depth")),
+            display: self.parse_display(&data.get("display")),
+            textures: self.textures.take(),
+        }
+    }
+
+    /**
+     * @brief Read a scene file, with the files it includes merged into it
+     *
+     * @param path the path of the file
+     * @param including the files including this one, to detect the cycles
+     *
+     * @return the merged scene, and where its values come from
+     */
+    fn load_document(
+        &self,
+        path: &Path,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<(Yaml, Vec<Origin>), LoadError> {
+        let buffer =
+            fs::read_to_string(path).map_err(|error| LoadError::Io(path.to_path_buf(), error))?;
+        let docs = YamlLoader_::load_from_str(&buffer).map_err(|error| {
+            // The description is the message without the position
+            let marker = error.marker();
+            #[allow(deprecated)]
+            let info = error.description();
+            let message = format!("{}:{}: {}", marker.line(), marker.col() + 1, info);
+            LoadError::Syntax(path.to_path_buf(), message)
+        })?;
+        let mut data = docs.into_iter().next().unwrap_or(Yaml::BadValue);
+
+        // A single file or a list of files, relative to this one
+        let include = Field::root(&data).get("include");
+        let entries = match include.data {
+            Yaml::String(_) => vec![include.clone()],
+            _ => self.parse_list(&include, "a file or a list of files"),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            match entry.data.as_str() {
+                Some(name) => names.push((entry.path.clone(), name.to_string())),
+                None => self.push_error(FieldError {
+                    file: Some(path.to_path_buf()),
+                    path: entry.path.clone(),
+                    expected: String::from("the path of a scene file"),
+                    found: describe(entry.data),
+                }),
+            }
+        }
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        including.push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        let (mut included, mut included_origins) = (Yaml::BadValue, Vec::new());
+        for (field_path, name) in names {
+            let include_path = directory.join(&name);
+            let canonical_path =
+                fs::canonicalize(&include_path).unwrap_or_else(|_| include_path.clone());
+            if let Some(start) = including.iter().position(|path| *path == canonical_path) {
+                let cycle: Vec<String> = including[start..]
+                    .iter()
+                    .chain([&canonical_path])
+                    .map(|path| path.display().to_string())
+                    .collect();
+                self.push_error(FieldError {
+                    file: Some(path.to_path_buf()),
+                    path: field_path,
+                    expected: format!("a file outside of the cycle {}", cycle.join(" -> ")),
+                    found: format!("{:?}", name),
+                });
+                continue;
+            }
+
+            let (mut document, origins) = self.load_document(&include_path, including)?;
+            rebase_paths(
+                &mut document,
+                Path::new(&name).parent().unwrap_or(Path::new("")),
+            );
+            (included, included_origins) =
+                merge_scenes((&included, &included_origins), (&document, &origins));
+        }
+        including.pop();
+
+        if let Yaml::Hash(hash) = &mut data {
+            hash.remove(&Yaml::String(String::from("include")));
         }
+
+        let origins = [Origin {
+            merged: String::new(),
+            file: path.to_path_buf(),
+            original: String::new(),
+        }];
+        Ok(merge_scenes(
+            (&included, &included_origins),
+            (&data, &origins),
+        ))
     }
 }
 
 impl Loader for YamlLoader {
-    fn load_scene_from_file(&self, path: &Path) -> Scene {
-        // Read the file
-        let mut file = File::open(path).unwrap();
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer).unwrap();
+    fn load_scene_from_file(&self, path: &Path) -> Result<Scene, LoadError> {
+        // Resolve the paths relatively to the scene file
+        let loader = YamlLoader {
+            base_directory: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            strict: self.strict,
+            ..Default::default()
+        };
+
+        // Read the file and the files it includes
+        let (scene_data, origins) = loader.load_document(path, &mut Vec::new())?;
+        let scene = loader.parse_scene(&Field::root(&scene_data));
+
+        // Report the values of the included files in their files
+        self.warnings.borrow_mut().extend(
+            loader
+                .take_warnings()
+                .into_iter()
+                .map(|warning| locate(warning, &origins)),
+        );
+        let errors: Vec<FieldError> = loader
+            .take_errors()
+            .into_iter()
+            .map(|error| locate(error, &origins))
+            .collect();
+        if !errors.is_empty() {
+            return Err(LoadError::Fields(path.to_path_buf(), errors));
+        }
+
+        Ok(scene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn parse_errors(text: &str) -> Vec<(String, String)> {
+        let documents = YamlLoader_::load_from_str(text).unwrap();
+        let loader = YamlLoader::default();
+        loader.parse_scene(&Field::root(&documents[0]));
+
+        loader
+            .take_errors()
+            .into_iter()
+            .map(|error| (error.path, error.found))
+            .collect()
+    }
 
-        // Parse the data
-        let docs = YamlLoader_::load_from_str(&buffer).unwrap();
-        let scene_data = &docs[0];
+    #[test]
+    fn invalid_fields_are_reported_with_their_path() {
+        let errors = parse_errors(
+            "camera:
+  position: {x: 0, y: 0, z: 0}
+  view_port: {x: 1, y: 1, z: 1}
+lights: []
+root:
+  type: union
+  nodes:
+    - type: sphere
+      position: {x: 0, y: 0, z: 3}
+      radius: big
+      material: {color: {r: 1, g: 0, b: 0}, specular: 10}
+    - type: mesh
+      material: {color: {r: 1, g: 1, b: 1}, specular: 10}
+    - type: teapot
+",
+        );
+
+        let expected = [
+            ("root.nodes[0].radius", "\"big\""),
+            ("root.nodes[1].path", "nothing"),
+            ("root.nodes[2].type", "\"teapot\""),
+        ];
+        assert_eq!(
+            errors,
+            expected.map(|(path, found)| (path.to_string(), found.to_string()))
+        );
+    }
+
+    #[test]
+    fn bsdf_without_color_is_white() {
+        for model in ["lambertian", "conductor", "dielectric", "principled"] {
+            let text = format!("{{type: {}}}", model);
+            let documents = YamlLoader_::load_from_str(&text).unwrap();
+            let loader = YamlLoader::default();
+            let material = loader.parse_material(&Field::root(&documents[0]));
+
+            assert!(loader.take_errors().is_empty(), "{}", model);
+            assert!(material.bsdf.is_some(), "{}", model);
+            if model == "lambertian" {
+                let color = material.color;
+                assert_eq!((color.r, color.g, color.b), (1.0, 1.0, 1.0));
+            }
+        }
+    }
+
+    /**
+     * @brief Write the files of a test in a new directory
+     */
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("raytracer-{}-{}", test, process::id()));
+        for (name, text) in files {
+            let path = directory.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        directory
+    }
+
+    fn load_errors(path: &Path) -> Vec<FieldError> {
+        match YamlLoader::default().load_scene_from_file(path) {
+            Err(LoadError::Fields(_, errors)) => errors,
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("the scene loaded without errors"),
+        }
+    }
+
+    #[test]
+    fn include_cycle_is_reported_in_the_including_file() {
+        let directory = write_files(
+            "include-cycle",
+            &[
+                ("a.yml", "include: sub/b.yml\nroot: {type: sphere}\n"),
+                ("sub/b.yml", "include: [../a.yml]\n"),
+            ],
+        );
+
+        let errors = load_errors(&directory.join("a.yml"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file, Some(directory.join("sub/b.yml")));
+        assert_eq!(errors[0].path, "include[0]");
+        assert!(errors[0]
+            .expected
+            .starts_with("a file outside of the cycle"));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn prototype_cycle_is_reported_at_the_instance() {
+        let documents = YamlLoader_::load_from_str(
+            "prototypes:
+  a: {type: union, nodes: [{type: instance, prototype: b}]}
+  b: {type: instance, prototype: a}
+root: {type: instance, prototype: a}
+",
+        )
+        .unwrap();
+        let loader = YamlLoader::default();
+        loader.parse_scene(&Field::root(&documents[0]));
 
-        self.parse_scene(scene_data)
+        let errors = loader.take_errors();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].path, "prototypes.b.prototype");
+        assert_eq!(
+            errors[0].expected,
+            "a prototype outside of the cycle a -> b -> a"
+        );
     }
 }
@@ -1,16 +1,25 @@
 extern crate sdl2;
 
-use raytracer::canvas::FrameBufferCanvas;
-use raytracer::image::save_canvas_to_file;
+use raytracer::aov::{render_aovs, Aov};
+use raytracer::canvas::{FrameBufferCanvas, LayeredCanvas};
+use raytracer::display::ToneMapping;
+use raytracer::image::save_layers_to_file;
+use raytracer::loader::lisp::LispLoader;
+use raytracer::loader::script::ScriptLoader;
 use raytracer::loader::yaml::YamlLoader;
 use raytracer::loader::Loader;
 use raytracer::render::opengl::OpenGLRenderer;
+use raytracer::render::pathtracer::PathTracerRenderer;
+use raytracer::render::raymarching::RaymarchingRenderer;
 use raytracer::render::software::SoftwareRenderer;
-use raytracer::render::{Renderer, RendererType};
+use raytracer::render::{default_thread_count, Renderer, RendererType};
+use raytracer::sampling::{Filter, Sampling};
 use raytracer::sdl::sdl_main;
+use raytracer::stats;
 
 use std::env;
 use std::path::Path;
+use std::process;
 
 pub fn main() -> Result<(), String> {
     // Parse the CLI args
@@ -19,6 +28,15 @@ pub fn main() -> Result<(), String> {
     let mut scene_path = Path::new("");
     let mut output_image_path = None;
     let mut renderer_type = RendererType::Software;
+    let mut max_depth = None;
+    let mut threads = default_thread_count();
+    let mut sampling = Sampling::default();
+    let mut exposure = None;
+    let mut tone_mapping = None;
+    let mut linear = false;
+    let mut strict = false;
+    let mut print_stats = false;
+    let mut aovs = Vec::new();
     let args: Vec<String> = env::args().collect();
     let args_count = args.len();
     let mut i = 1;
@@ -41,11 +59,68 @@ pub fn main() -> Result<(), String> {
                 output_image_path = Some(Path::new(next_arg));
                 i += 1;
             }
+        } else if arg == "-d" || arg == "--max-depth" {
+            if let Some(next_arg) = next_arg {
+                max_depth = Some(next_arg.parse().unwrap());
+                i += 1;
+            }
+        } else if arg == "-t" || arg == "--threads" {
+            if let Some(next_arg) = next_arg {
+                threads = next_arg.parse().unwrap();
+                i += 1;
+            }
+        } else if arg == "-s" || arg == "--samples" {
+            if let Some(next_arg) = next_arg {
+                sampling.samples = next_arg.parse().unwrap();
+                i += 1;
+            }
+        } else if arg == "-f" || arg == "--filter" {
+            if let Some(next_arg) = next_arg {
+                sampling.filter = Filter::from_name(next_arg).expect("unsupported filter");
+                i += 1;
+            }
+        } else if arg == "--seed" {
+            if let Some(next_arg) = next_arg {
+                sampling.seed = next_arg.parse().unwrap();
+                i += 1;
+            }
+        } else if arg == "-e" || arg == "--exposure" {
+            if let Some(next_arg) = next_arg {
+                exposure = Some(next_arg.parse().unwrap());
+                i += 1;
+            }
+        } else if arg == "--tone-mapping" {
+            if let Some(next_arg) = next_arg {
+                tone_mapping =
+                    Some(ToneMapping::from_name(next_arg).expect("unsupported tone mapping"));
+                i += 1;
+            }
+        } else if arg == "--linear" {
+            linear = true;
+        } else if arg == "--strict" {
+            strict = true;
+        } else if arg == "--stats" {
+            print_stats = true;
+        } else if arg == "-a" || arg == "--aov" {
+            if let Some(next_arg) = next_arg {
+                // A comma separated list of passes, or all of them
+                aovs = if next_arg == "all" {
+                    Aov::ALL.to_vec()
+                } else {
+                    next_arg
+                        .split(',')
+                        .map(|name| Aov::from_name(name).expect("unsupported aov"))
+                        .collect()
+                };
+                i += 1;
+            }
         } else if arg == "-r" || arg == "--renderer" {
             if let Some(next_arg) = next_arg {
                 renderer_type = match next_arg.as_str() {
                     "software" => RendererType::Software,
                     "opengl" => RendererType::OpenGL,
+                    "raymarching" => RendererType::Raymarching,
+                    "pathtracer" => RendererType::PathTracer,
                     _ => panic!("unsupported renderer"),
                 };
                 i += 1;
@@ -60,13 +135,48 @@ pub fn main() -> Result<(), String> {
         return Err(String::from("missing scene"));
     }
 
-    // Create the scene
-    let scene = YamlLoader {}.load_scene_from_file(&scene_path);
+    // Create the scene, with the loader matching the extension of the file
+    let mut scene = match scene_path.extension().and_then(|e| e.to_str()) {
+        Some("lisp") => LispLoader::default().load_scene_from_file(scene_path),
+        Some("rhai") => ScriptLoader::default().load_scene_from_file(scene_path),
+        _ => {
+            let mut loader = YamlLoader::default();
+            loader.strict = strict;
+            let scene = loader.load_scene_from_file(scene_path);
+            for warning in loader.take_warnings() {
+                let file = warning.file.as_deref().unwrap_or(scene_path);
+                eprintln!("{}: warning: {}", file.display(), warning);
+            }
+            scene
+        }
+    }
+    .unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    if let Some(max_depth) = max_depth {
+        scene.max_depth = max_depth;
+    }
+    if let Some(exposure) = exposure {
+        scene.display.exposure = exposure;
+    }
+    if let Some(tone_mapping) = tone_mapping {
+        scene.display.tone_mapping = tone_mapping;
+    }
+    if linear {
+        scene.display.srgb = false;
+    }
 
     // Create the render
     let renderer: Box<dyn Renderer> = match renderer_type {
-        RendererType::Software => Box::new(SoftwareRenderer {}),
+        RendererType::Software => Box::new(SoftwareRenderer { threads, sampling }),
         RendererType::OpenGL => Box::new(OpenGLRenderer {}),
+        RendererType::Raymarching => Box::new(RaymarchingRenderer {
+            threads,
+            sampling,
+            ..Default::default()
+        }),
+        RendererType::PathTracer => Box::new(PathTracerRenderer { threads, sampling }),
     };
 
     // Create the canvas
@@ -75,7 +185,19 @@ pub fn main() -> Result<(), String> {
     // Do one render, save it and and return
     if let Some(path) = output_image_path {
         renderer.render(&scene, &mut canvas);
-        save_canvas_to_file(&canvas, &path);
+        // Count the tests of the render only, not the ones of the passes
+        let stats = stats::get();
+
+        let mut layers = LayeredCanvas::new(canvas_width, canvas_height);
+        render_aovs(&scene, &aovs, &mut layers, threads);
+        save_layers_to_file(&canvas, &layers, path, &scene.display);
+
+        if print_stats {
+            eprintln!(
+                "{} ray/box tests, {} ray/primitive tests",
+                stats.ray_box_tests, stats.ray_primitive_tests
+            );
+        }
 
         return Ok(());
     }
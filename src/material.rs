@@ -1,14 +1,141 @@
-use crate::color::Color;
+use crate::{bsdf::BsdfModel, color::Color, texture::Texture};
+
+/**
+ * @brief The textures of a material, as indices in the textures of the scene
+ *
+ * Each texture multiplies a parameter of the material.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MaterialTextures {
+    /**
+     * @brief Multiplies the color of the material or of its BSDF
+     */
+    pub albedo: Option<usize>,
+
+    /**
+     * @brief Multiplies the Phong exponent, or the specular of a principled BSDF
+     */
+    pub specular: Option<usize>,
+
+    /**
+     * @brief Multiplies the roughness of the BSDF
+     */
+    pub roughness: Option<usize>,
+}
+
+impl MaterialTextures {
+    pub const NONE: MaterialTextures = MaterialTextures {
+        albedo: None,
+        specular: None,
+        roughness: None,
+    };
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::NONE
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct Material {
     pub color: Color,
     pub specular: f32,
+    pub reflective: f32,
+    pub transparency: f32,
+    pub refractive_index: f32,
+    pub absorption: Color,
+
+    /**
+     * @brief The physically based model of the surface, replacing the Phong
+     * parameters when set
+     */
+    pub bsdf: Option<BsdfModel>,
+
+    pub textures: MaterialTextures,
+
+    /**
+     * @brief Identifier of the material for the material ID pass, 0 if unknown
+     */
+    pub id: u32,
+
+    /**
+     * @brief Identifier of the object for the object ID pass, 0 if unknown
+     *
+     * Each object holds its own copy of its material, so the hits carry the
+     * identifier of their object along with their material.
+     */
+    pub object_id: u32,
 }
 
 impl Material {
     pub const DEBUG_MATERIAL: Material = Material {
         color: Color::MAGENTA,
         specular: 0.0,
+        reflective: 0.0,
+        transparency: 0.0,
+        refractive_index: 1.0,
+        absorption: Color::BLACK,
+        bsdf: None,
+        textures: MaterialTextures::NONE,
+        id: 0,
+        object_id: 0,
     };
+
+    /**
+     * @brief White diffuse material, for the objects without a material
+     */
+    pub const DEFAULT_MATERIAL: Material = Material {
+        color: Color::WHITE,
+        specular: -1.0,
+        reflective: 0.0,
+        transparency: 0.0,
+        refractive_index: 1.0,
+        absorption: Color::BLACK,
+        bsdf: None,
+        textures: MaterialTextures::NONE,
+        id: 0,
+        object_id: 0,
+    };
+
+    /**
+     * @brief The material at a point of a surface, with its textures applied
+     *
+     * @param textures the textures of the scene
+     * @param uv the texture coordinates of the point
+     * @param footprint the size of the pixel in texture coordinates
+     */
+    pub fn textured(&self, textures: &[Texture], uv: [f32; 2], footprint: f32) -> Material {
+        let mut material = *self;
+        if let Some(index) = self.textures.albedo {
+            let albedo = textures[index].sample(uv, footprint);
+            material.color = material.color * albedo;
+            match &mut material.bsdf {
+                Some(BsdfModel::Lambertian(bsdf)) => bsdf.color = bsdf.color * albedo,
+                Some(BsdfModel::Conductor(bsdf)) => bsdf.color = bsdf.color * albedo,
+                Some(BsdfModel::Dielectric(bsdf)) => bsdf.color = bsdf.color * albedo,
+                Some(BsdfModel::Principled(bsdf)) => bsdf.color = bsdf.color * albedo,
+                None => {}
+            }
+        }
+        if let Some(index) = self.textures.specular {
+            let specular = textures[index].sample_value(uv, footprint);
+            match &mut material.bsdf {
+                Some(BsdfModel::Principled(bsdf)) => bsdf.specular *= specular,
+                Some(_) => {}
+                // A black texel removes the highlight, as an exponent of 0 would fill the surface
+                None if material.specular > 0.0 && specular > 0.0 => material.specular *= specular,
+                None => material.specular = -1.0,
+            }
+        }
+        if let Some(index) = self.textures.roughness {
+            let roughness = textures[index].sample_value(uv, footprint);
+            match &mut material.bsdf {
+                Some(BsdfModel::Conductor(bsdf)) => bsdf.roughness *= roughness,
+                Some(BsdfModel::Dielectric(bsdf)) => bsdf.roughness *= roughness,
+                Some(BsdfModel::Principled(bsdf)) => bsdf.roughness *= roughness,
+                _ => {}
+            }
+        }
+
+        material
+    }
 }
@@ -1,5 +1,6 @@
 use std::ops::Add;
 use std::ops::Div;
+use std::ops::Index;
 use std::ops::Mul;
 use std::ops::Neg;
 use std::ops::Sub;
@@ -45,6 +46,96 @@ impl Vector3 {
     pub fn normalize(self) -> Self {
         self / self.length()
     }
+
+    /**
+     * @brief The component-wise minimum of the vector and an another vector
+     */
+    pub fn min(self, o: Self) -> Self {
+        Self::new(self.x.min(o.x), self.y.min(o.y), self.z.min(o.z))
+    }
+
+    /**
+     * @brief The component-wise maximum of the vector and an another vector
+     */
+    pub fn max(self, o: Self) -> Self {
+        Self::new(self.x.max(o.x), self.y.max(o.y), self.z.max(o.z))
+    }
+
+    /**
+     * @brief The cross product of the vector with an another vector
+     */
+    pub fn cross(&self, o: Self) -> Self {
+        Self::new(
+            self.y * o.z - self.z * o.y,
+            self.z * o.x - self.x * o.z,
+            self.x * o.y - self.y * o.x,
+        )
+    }
+
+    /**
+     * @brief Build two vectors forming an orthonormal basis with the vector
+     *
+     * The vector must be normalized
+     */
+    pub fn orthonormal_basis(self) -> (Self, Self) {
+        let helper = if self.x.abs() > 0.9 {
+            Self::new(0.0, 1.0, 0.0)
+        } else {
+            Self::new(1.0, 0.0, 0.0)
+        };
+        let tangent = helper.cross(self).normalize();
+        let bitangent = self.cross(tangent);
+
+        (tangent, bitangent)
+    }
+
+    /**
+     * @brief Rotate the vector with Euler angles, around X, then Y, then Z
+     *
+     * @param angles the rotation around each axis, in degrees
+     */
+    pub fn rotate_euler(self, angles: Self) -> Self {
+        let (sin_x, cos_x) = angles.x.to_radians().sin_cos();
+        let (sin_y, cos_y) = angles.y.to_radians().sin_cos();
+        let (sin_z, cos_z) = angles.z.to_radians().sin_cos();
+
+        let v = Self::new(
+            self.x,
+            self.y * cos_x - self.z * sin_x,
+            self.y * sin_x + self.z * cos_x,
+        );
+        let v = Self::new(v.x * cos_y + v.z * sin_y, v.y, -v.x * sin_y + v.z * cos_y);
+        Self::new(v.x * cos_z - v.y * sin_z, v.x * sin_z + v.y * cos_z, v.z)
+    }
+
+    /**
+     * @brief Reflect the vector relatively to a normal
+     *
+     * @param normal the normal of the mirror, must be normalized
+     */
+    pub fn reflect(self, normal: Self) -> Self {
+        self - normal * (normal.dot(self) * 2.0)
+    }
+
+    /**
+     * @brief Refract the vector through a surface using the Snell's law
+     *
+     * @param normal the normal of the surface, facing the vector, must be normalized
+     * @param eta the ratio between the incident and the transmitted refractive indices
+     *
+     * @return the refracted vector, or None in case of total internal reflection
+     */
+    pub fn refract(self, normal: Self, eta: f32) -> Option<Self> {
+        let direction = self.normalize();
+        let cos_i = -normal.dot(direction);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(direction * eta + normal * (eta * cos_i - cos_t))
+    }
 }
 
 impl Default for Vector3 {
@@ -53,6 +144,19 @@ impl Default for Vector3 {
     }
 }
 
+impl Index<usize> for Vector3 {
+    type Output = f32;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("invalid axis {}", axis),
+        }
+    }
+}
+
 impl Neg for Vector3 {
     type Output = Self;
 
@@ -125,6 +229,390 @@ impl Div<f32> for Vector3 {
     }
 }
 
+/**
+ * @brief A quaternion, for the rotations
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /**
+     * @brief Create a rotation around an axis
+     *
+     * @param axis the axis of the rotation, doesn't need to be normalized
+     * @param angle the angle of the rotation, in degrees
+     */
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        let (sin, cos) = (angle.to_radians() * 0.5).sin_cos();
+        let axis = axis.normalize() * sin;
+
+        Self::new(cos, axis.x, axis.y, axis.z)
+    }
+
+    /**
+     * @brief Create a rotation from Euler angles, around X, then Y, then Z
+     *
+     * @param angles the rotation around each axis, in degrees
+     */
+    pub fn from_euler(angles: Vector3) -> Self {
+        let x = Self::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), angles.x);
+        let y = Self::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), angles.y);
+        let z = Self::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), angles.z);
+
+        z * y * x
+    }
+
+    pub fn length(self) -> f32 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        Self::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    /**
+     * @brief The inverse rotation, for a normalized quaternion
+     */
+    pub fn conjugate(self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /**
+     * @brief Rotate a vector, the quaternion must be normalized
+     */
+    pub fn rotate(self, v: Vector3) -> Vector3 {
+        let axis = Vector3::new(self.x, self.y, self.z);
+        let t = axis.cross(v) * 2.0;
+
+        v + t * self.w + axis.cross(t)
+    }
+}
+
+impl Mul<Quaternion> for Quaternion {
+    type Output = Self;
+
+    /**
+     * @brief Compose two rotations, the right one being applied first
+     */
+    fn mul(self, o: Self) -> Self::Output {
+        Self::Output::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+/**
+ * @brief A 4x4 matrix, for the affine transforms
+ *
+ * The matrix is stored by rows and multiplies column vectors, so that the
+ * product A * B applies B first.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn translation(offset: Vector3) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        matrix
+    }
+
+    pub fn scaling(scale: Vector3) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix.m[0][0] = scale.x;
+        matrix.m[1][1] = scale.y;
+        matrix.m[2][2] = scale.z;
+        matrix
+    }
+
+    /**
+     * @brief Create a rotation matrix from a normalized quaternion
+     */
+    pub fn rotation(rotation: Quaternion) -> Self {
+        let Quaternion { w, x, y, z } = rotation;
+
+        Self::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /**
+     * @brief Transform a point, with the translation
+     */
+    pub fn transform_point(&self, p: Vector3) -> Vector3 {
+        let m = &self.m;
+        Vector3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    /**
+     * @brief Transform a vector, without the translation
+     */
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let m = &self.m;
+        Vector3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut matrix = *self;
+        for (i, row) in matrix.m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        matrix
+    }
+
+    /**
+     * @brief Invert an affine transform
+     *
+     * @return the inverse, or None if the transform flattens the space
+     */
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.m;
+        let cofactor = |i: usize, j: usize| {
+            let (i0, i1) = ((i + 1) % 3, (i + 2) % 3);
+            let (j0, j1) = ((j + 1) % 3, (j + 2) % 3);
+            m[i0][j0] * m[i1][j1] - m[i0][j1] * m[i1][j0]
+        };
+        let determinant = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum::<f32>();
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        // The inverse of the linear part is its adjugate over its determinant
+        let mut inverse = Self::IDENTITY;
+        for i in 0..3 {
+            for j in 0..3 {
+                inverse.m[i][j] = cofactor(j, i) / determinant;
+            }
+        }
+        let translation = inverse.transform_vector(Vector3::new(m[0][3], m[1][3], m[2][3]));
+        inverse.m[0][3] = -translation.x;
+        inverse.m[1][3] = -translation.y;
+        inverse.m[2][3] = -translation.z;
+
+        Some(inverse)
+    }
+
+    /**
+     * @brief The largest factor by which the transform stretches a vector
+     *
+     * The square root of the largest eigenvalue of the symmetric matrix
+     * A^T A, A being the linear part of the transform.
+     */
+    pub fn max_scale(&self) -> f32 {
+        let mut b = [[0.0f32; 3]; 3];
+        for (i, row) in b.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.m[k][i] * self.m[k][j]).sum();
+            }
+        }
+
+        // Eigenvalues of a symmetric 3x3 matrix with the trigonometric method
+        let off_diagonal = b[0][1] * b[0][1] + b[0][2] * b[0][2] + b[1][2] * b[1][2];
+        if off_diagonal == 0.0 {
+            return b[0][0].max(b[1][1]).max(b[2][2]).sqrt();
+        }
+        let q = (b[0][0] + b[1][1] + b[2][2]) / 3.0;
+        let p = (((b[0][0] - q).powi(2)
+            + (b[1][1] - q).powi(2)
+            + (b[2][2] - q).powi(2)
+            + 2.0 * off_diagonal)
+            / 6.0)
+            .sqrt();
+        let c = |i: usize, j: usize| (b[i][j] - if i == j { q } else { 0.0 }) / p;
+        let determinant = c(0, 0) * (c(1, 1) * c(2, 2) - c(1, 2) * c(2, 1))
+            - c(0, 1) * (c(1, 0) * c(2, 2) - c(1, 2) * c(2, 0))
+            + c(0, 2) * (c(1, 0) * c(2, 1) - c(1, 1) * c(2, 0));
+        let phi = (determinant * 0.5).clamp(-1.0, 1.0).acos() / 3.0;
+
+        (q + 2.0 * p * phi.cos()).max(0.0).sqrt()
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Self;
+
+    fn mul(self, o: Self) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * o.m[k][j]).sum();
+            }
+        }
+
+        Self::Output::new(m)
+    }
+}
+
+/**
+ * @brief Solve a x^2 + b x + c = 0
+ *
+ * @return the real roots, in ascending order
+ */
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return if b == 0.0 { vec![] } else { vec![-c / b] };
+    }
+
+    let delta = b * b - 4.0 * a * c;
+    if delta < 0.0 {
+        return vec![];
+    }
+
+    // Avoid the catastrophic cancellation of the naive formula
+    let q = -0.5 * (b + b.signum() * delta.sqrt());
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+
+    vec![t0.min(t1), t0.max(t1)]
+}
+
+/**
+ * @brief Solve x^3 + a x^2 + b x + c = 0
+ *
+ * @return the real roots
+ */
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // Depress the cubic with x = y - a / 3
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let offset = -a / 3.0;
+
+    let delta = q * q / 4.0 + p * p * p / 27.0;
+    if delta > 0.0 {
+        let sqrt_delta = delta.sqrt();
+        vec![(-q / 2.0 + sqrt_delta).cbrt() + (-q / 2.0 - sqrt_delta).cbrt() + offset]
+    } else if p == 0.0 {
+        vec![offset]
+    } else {
+        // Three real roots, use the trigonometric method
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let theta = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3)
+            .map(|k| m * (theta - 2.0 * std::f64::consts::PI * k as f64 / 3.0).cos() + offset)
+            .collect()
+    }
+}
+
+/**
+ * @brief Solve x^4 + a x^3 + b x^2 + c x + d = 0 with the Ferrari's method
+ *
+ * @return the real roots, refined with a few Newton iterations
+ */
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // Depress the quartic with x = y - a / 4
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = Vec::new();
+    if q.abs() < 1e-12 {
+        // Biquadratic equation
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                roots.push(z.sqrt());
+                roots.push(-z.sqrt());
+            }
+        }
+    } else {
+        // Split in two quadratics with a positive root of the resolvent cubic
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return vec![];
+        }
+
+        let s = (2.0 * m).sqrt();
+        roots.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+        roots.extend(solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s)));
+    }
+
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - a / 4.0;
+            for _ in 0..2 {
+                let f = (((x + a) * x + b) * x + c) * x + d;
+                let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+                if df != 0.0 {
+                    x -= f / df;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
 /**
  * @brief Do the linear interpolation of a value
  *
@@ -152,3 +640,116 @@ pub fn lerp01(x: f32, x0: f32, x1: f32) -> f32 {
 pub fn lerp_color8(x: f32) -> u8 {
     lerp(x, 0.0, 1.0, 0.0, 255.0) as u8
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn sorted(mut roots: Vec<f64>) -> Vec<f64> {
+        roots.sort_by(f64::total_cmp);
+        roots
+    }
+
+    #[test]
+    fn solve_quartic_finds_the_real_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let roots = sorted(solve_quartic(-10.0, 35.0, -50.0, 24.0));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((root - expected).abs() < 1e-6, "{:?}", roots);
+        }
+
+        // (x^2 - 1)(x^2 - 4), a biquadratic equation
+        let roots = sorted(solve_quartic(0.0, -5.0, 0.0, 4.0));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([-2.0, -1.0, 1.0, 2.0]) {
+            assert!((root - expected).abs() < 1e-6, "{:?}", roots);
+        }
+
+        // x^4 + 1 has no real root
+        assert!(solve_quartic(0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Matrix4::translation(Vector3::new(1.0, -2.0, 3.0))
+            * Matrix4::rotation(Quaternion::from_euler(Vector3::new(30.0, 45.0, 60.0)))
+            * Matrix4::scaling(Vector3::new(2.0, 0.5, 3.0));
+        let inverse = transform.inverse().unwrap();
+
+        let p = Vector3::new(0.3, 1.7, -2.5);
+        assert_close(inverse.transform_point(transform.transform_point(p)), p);
+        assert_close(transform.transform_point(inverse.transform_point(p)), p);
+        assert_close(inverse.transform_vector(transform.transform_vector(p)), p);
+    }
+
+    #[test]
+    fn inverse_of_a_flat_transform_is_none() {
+        let flat = Matrix4::scaling(Vector3::new(1.0, 0.0, 1.0));
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn max_scale_is_the_largest_stretch() {
+        let scaling = Matrix4::scaling(Vector3::new(2.0, -5.0, 3.0));
+        assert!((scaling.max_scale() - 5.0).abs() < 1e-5);
+
+        // The rotations and the translations don't stretch
+        let transform = Matrix4::translation(Vector3::new(4.0, 5.0, 6.0))
+            * Matrix4::rotation(Quaternion::from_axis_angle(
+                Vector3::new(1.0, 1.0, 0.0),
+                40.0,
+            ))
+            * scaling;
+        assert!((transform.max_scale() - 5.0).abs() < 1e-4);
+
+        // A shear stretches more than its diagonal
+        let mut shear = Matrix4::IDENTITY;
+        shear.m[0][1] = 1.0;
+        let golden_ratio = (1.0 + 5.0f32.sqrt()) / 2.0;
+        assert!((shear.max_scale() - golden_ratio).abs() < 1e-5);
+    }
+
+    #[test]
+    fn axis_angle_rotates_counterclockwise() {
+        let rotation = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 2.0), 90.0);
+        assert_close(
+            rotation.rotate(Vector3::new(1.0, 0.0, 0.0)),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        assert_close(
+            rotation.conjugate().rotate(Vector3::new(0.0, 1.0, 0.0)),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn euler_angles_rotate_around_x_then_y_then_z() {
+        let angles = Vector3::new(90.0, 90.0, 0.0);
+        let rotation = Quaternion::from_euler(angles);
+
+        // X turns Y to Z, then Y turns Z to X
+        assert_close(
+            rotation.rotate(Vector3::new(0.0, 1.0, 0.0)),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+        assert!((rotation.length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rotation_matrix_matches_the_quaternion() {
+        let rotation = Quaternion::from_euler(Vector3::new(10.0, -70.0, 125.0));
+        let matrix = Matrix4::rotation(rotation);
+        for v in [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.3, -0.4, 2.0),
+        ] {
+            assert_close(matrix.transform_vector(v), rotation.rotate(v));
+        }
+    }
+}
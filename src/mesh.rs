@@ -0,0 +1,315 @@
+use std::any::Any;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    aabb::Aabb,
+    bvh::Bvh,
+    color::Color,
+    csg::pair_intervals,
+    material::{Material, MaterialTextures},
+    math::Vector3,
+    ray::{Hit, Interval, Ray},
+    sdf::Node,
+    stats,
+};
+
+/**
+ * @brief A triangle of a mesh
+ */
+#[derive(Clone, Copy)]
+pub struct Triangle {
+    /**
+     * @brief Indices of the vertices in the arrays of the mesh
+     */
+    pub indices: [u32; 3],
+
+    /**
+     * @brief Index of the material in the mesh, None to use the material of the mesh
+     */
+    pub material: Option<u32>,
+}
+
+/**
+ * @brief A triangle mesh, with its own bounding volume hierarchy
+ */
+pub struct MeshNode {
+    pub positions: Vec<Vector3>,
+
+    /**
+     * @brief Normals of the vertices, empty for a flat shading
+     */
+    pub normals: Vec<Vector3>,
+
+    /**
+     * @brief Texture coordinates of the vertices, may be empty
+     */
+    pub uvs: Vec<[f32; 2]>,
+
+    /**
+     * @brief The triangles, ordered by the hierarchy
+     */
+    pub triangles: Vec<Triangle>,
+
+    pub materials: Vec<Material>,
+
+    /**
+     * @brief The material of the triangles without their own material
+     */
+    pub material: Material,
+
+    /**
+     * @brief The file the mesh was loaded from
+     */
+    pub path: Option<PathBuf>,
+
+    bvh: Bvh,
+}
+
+impl MeshNode {
+    /**
+     * @brief Create a new mesh and build its hierarchy
+     *
+     * @param positions the positions of the vertices
+     * @param normals the normals of the vertices, or empty
+     * @param uvs the texture coordinates of the vertices, or empty
+     * @param triangles the triangles
+     * @param materials the materials referenced by the triangles
+     * @param material the material of the triangles without their own material
+     */
+    pub fn new(
+        positions: Vec<Vector3>,
+        normals: Vec<Vector3>,
+        uvs: Vec<[f32; 2]>,
+        triangles: Vec<Triangle>,
+        materials: Vec<Material>,
+        material: Material,
+    ) -> Self {
+        let bounds: Vec<Aabb> = triangles
+            .iter()
+            .map(|triangle| {
+                triangle
+                    .indices
+                    .iter()
+                    .fold(Aabb::EMPTY, |acc, i| acc.grow(positions[*i as usize]))
+            })
+            .collect();
+        let (bvh, order) = Bvh::build(&bounds);
+        let triangles = order.into_iter().map(|index| triangles[index]).collect();
+
+        Self {
+            positions,
+            normals,
+            uvs,
+            triangles,
+            materials,
+            material,
+            path: None,
+            bvh,
+        }
+    }
+
+    /**
+     * @brief Load a mesh from a Wavefront OBJ file
+     *
+     * The materials of the companion MTL file are converted to our materials.
+     *
+     * @param path the path of the OBJ file
+     * @param material the material of the faces without a material
+     *
+     * @return the mesh, or the error of the OBJ file or of its MTL file
+     */
+    pub fn load_obj(path: &Path, material: Material) -> Result<Self, tobj::LoadError> {
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        };
+        let (models, obj_materials) = tobj::load_obj(path, &options)?;
+        let materials: Vec<Material> = obj_materials?.iter().map(convert_obj_material).collect();
+
+        // Merge all the models in a single mesh
+        let (mut positions, mut normals, mut uvs, mut triangles) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let has_normals = models.iter().all(|model| !model.mesh.normals.is_empty());
+        let has_uvs = models.iter().all(|model| !model.mesh.texcoords.is_empty());
+        for model in models.iter() {
+            let mesh = &model.mesh;
+            let offset = positions.len() as u32;
+            positions.extend(
+                mesh.positions
+                    .chunks_exact(3)
+                    .map(|p| Vector3::new(p[0], p[1], p[2])),
+            );
+            if has_normals {
+                normals.extend(
+                    mesh.normals
+                        .chunks_exact(3)
+                        .map(|n| Vector3::new(n[0], n[1], n[2])),
+                );
+            }
+            if has_uvs {
+                uvs.extend(mesh.texcoords.chunks_exact(2).map(|uv| [uv[0], uv[1]]));
+            }
+            triangles.extend(mesh.indices.chunks_exact(3).map(|indices| Triangle {
+                indices: [
+                    offset + indices[0],
+                    offset + indices[1],
+                    offset + indices[2],
+                ],
+                material: mesh.material_id.map(|id| id as u32),
+            }));
+        }
+
+        let mut mesh = Self::new(positions, normals, uvs, triangles, materials, material);
+        mesh.path = Some(path.to_path_buf());
+
+        Ok(mesh)
+    }
+
+    /**
+     * @brief Intersect a ray with a triangle with the Möller–Trumbore algorithm
+     *
+     * @param ray the ray
+     * @param triangle the triangle
+     */
+    pub fn hit_triangle(&self, ray: &Ray, triangle: &Triangle) -> Option<Hit> {
+        self.intersect_triangle(ray, triangle)
+            .filter(|hit| hit.distance >= 0.0)
+    }
+
+    /**
+     * @brief Intersect the whole line of a ray with a triangle
+     *
+     * @param ray the ray
+     * @param triangle the triangle
+     */
+    pub fn intersect_triangle(&self, ray: &Ray, triangle: &Triangle) -> Option<Hit> {
+        stats::count_ray_primitive_test();
+
+        let [i0, i1, i2] = triangle.indices.map(|i| i as usize);
+        let (v0, v1, v2) = (self.positions[i0], self.positions[i1], self.positions[i2]);
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let p = ray.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let s = ray.origin - v0;
+        let u = s.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = ray.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge2.dot(q) * inverse_determinant;
+
+        // Interpolate the normals of the vertices for a smooth shading
+        let normal = if self.normals.is_empty() {
+            edge1.cross(edge2).normalize()
+        } else {
+            (self.normals[i0] * (1.0 - u - v) + self.normals[i1] * u + self.normals[i2] * v)
+                .normalize()
+        };
+        let material = triangle
+            .material
+            .map_or(self.material, |index| self.materials[index as usize]);
+
+        // Interpolate the texture coordinates, their density being the ratio
+        // of the areas of the triangle in the texture and in the space
+        let (uv, uv_density) = if self.uvs.is_empty() {
+            ([0.0, 0.0], 0.0)
+        } else {
+            let (t0, t1, t2) = (self.uvs[i0], self.uvs[i1], self.uvs[i2]);
+            let uv = [
+                t0[0] * (1.0 - u - v) + t1[0] * u + t2[0] * v,
+                t0[1] * (1.0 - u - v) + t1[1] * u + t2[1] * v,
+            ];
+            let uv_area =
+                ((t1[0] - t0[0]) * (t2[1] - t0[1]) - (t2[0] - t0[0]) * (t1[1] - t0[1])).abs();
+            let area = edge1.cross(edge2).length();
+            (uv, (uv_area / area).sqrt())
+        };
+
+        Some(Hit {
+            position: ray.origin + ray.direction * distance,
+            normal,
+            distance,
+            material,
+            uv,
+            uv_density,
+        })
+    }
+}
+
+/**
+ * @brief Convert a material of a MTL file
+ */
+fn convert_obj_material(material: &tobj::Material) -> Material {
+    let [kd_r, kd_g, kd_b] = material.diffuse;
+    let specular_strength = material.specular.iter().sum::<f32>() / 3.0;
+
+    // The illumination models 3 and above enable the reflections
+    let reflective = match material.illumination_model {
+        Some(model) if model >= 3 => specular_strength,
+        _ => 0.0,
+    };
+
+    Material {
+        color: Color::new(kd_r, kd_g, kd_b),
+        specular: if specular_strength > 0.0 && material.shininess > 0.0 {
+            material.shininess
+        } else {
+            -1.0
+        },
+        reflective,
+        transparency: 1.0 - material.dissolve,
+        refractive_index: if material.optical_density > 0.0 {
+            material.optical_density
+        } else {
+            1.0
+        },
+        absorption: Color::BLACK,
+        bsdf: None,
+        textures: MaterialTextures::NONE,
+        id: 0,
+        object_id: 0,
+    }
+}
+
+impl Node for MeshNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.bvh.hit(ray, None, |index| {
+            self.hit_triangle(ray, &self.triangles[index])
+        })
+    }
+
+    /**
+     * @brief The intervals of a closed mesh, by pairing its sorted hits
+     */
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut hits = Vec::new();
+        self.bvh.visit_line(ray, |index| {
+            hits.extend(self.intersect_triangle(ray, &self.triangles[index]));
+        });
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        // A ray going through an edge hits both triangles
+        hits.dedup_by(|a, b| (a.distance - b.distance).abs() < 1e-6);
+
+        pair_intervals(&hits)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+}
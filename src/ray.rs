@@ -1,13 +1,47 @@
 use crate::{material::Material, math::Vector3};
 
+#[derive(Clone, Copy)]
 pub struct Ray {
     pub origin: Vector3,
     pub direction: Vector3,
+
+    /**
+     * @brief The growth of the width of the ray per unit of distance, to filter the textures
+     *
+     * The secondary rays keep the spread of their parent, but start thin
+     * again from its hit. 0 for the rays which don't look at textures.
+     */
+    pub spread: f32,
 }
 
+#[derive(Clone, Copy)]
 pub struct Hit {
     pub position: Vector3,
     pub normal: Vector3,
     pub distance: f32,
     pub material: Material,
+
+    /**
+     * @brief The texture coordinates of the point, [0, 0] on the surfaces without any
+     */
+    pub uv: [f32; 2],
+
+    /**
+     * @brief The change of the texture coordinates per unit of length on the surface
+     *
+     * Used to filter the textures, 0 on the surfaces without texture coordinates.
+     */
+    pub uv_density: f32,
+}
+
+/**
+ * @brief A segment of a ray inside a solid
+ *
+ * The distances may be negative or infinite, as the intervals cover the
+ * whole line of the ray. The normals point outside of the solid.
+ */
+#[derive(Clone, Copy)]
+pub struct Interval {
+    pub enter: Hit,
+    pub exit: Hit,
 }
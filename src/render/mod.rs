@@ -1,8 +1,23 @@
-use crate::{canvas::Canvas, scene::Scene};
+use crate::{
+    canvas::{Canvas, Tile},
+    color::Color,
+    scene::Scene,
+    stats,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
 pub mod opengl;
+pub mod pathtracer;
+pub mod raymarching;
 pub mod software;
 
+/**
+ * @brief Size of the side of the tiles rendered by the threads
+ */
+pub const TILE_SIZE: u32 = 32;
+
 pub trait Renderer {
     fn render(&self, scene: &Scene, canvas: &mut dyn Canvas);
 }
@@ -10,4 +25,92 @@ pub trait Renderer {
 pub enum RendererType {
     Software,
     OpenGL,
+    Raymarching,
+    PathTracer,
+}
+
+/**
+ * @brief Get the number of threads to use when nothing is specified
+ */
+pub fn default_thread_count() -> usize {
+    thread::available_parallelism().map_or(1, |count| count.get())
+}
+
+/**
+ * @brief Compute the reflectance of a surface with the Schlick's approximation
+ *
+ * @param cos_i the cosine of the incident angle
+ * @param n1 the refractive index of the incident medium
+ * @param n2 the refractive index of the transmitted medium
+ */
+pub fn schlick(cos_i: f32, n1: f32, n2: f32) -> f32 {
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+
+    // When going to a less dense medium, use the angle of the transmitted ray
+    let cos = if n1 > n2 {
+        let sin2_t = (n1 / n2).powi(2) * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+        (1.0 - sin2_t).sqrt()
+    } else {
+        cos_i
+    };
+
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+/**
+ * @brief Split the canvas in tiles and render them with a pool of threads
+ *
+ * Each pixel is computed independently, so the result doesn't depend on
+ * the number of threads.
+ *
+ * @param canvas the canvas to draw on
+ * @param threads the number of threads to use
+ * @param render_pixel the function computing the color of a pixel of the canvas
+ */
+pub fn render_tiles<F>(canvas: &mut dyn Canvas, threads: usize, render_pixel: F)
+where
+    F: Fn(u32, u32) -> Color + Sync,
+{
+    let (width, height) = (canvas.width(), canvas.height());
+
+    // Split the canvas
+    let mut areas = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            areas.push((x, y, TILE_SIZE.min(width - x), TILE_SIZE.min(height - y)));
+        }
+    }
+
+    // Each worker takes the next available tile and sends it back once rendered
+    let next_area = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, areas.len().max(1)) {
+            let sender = sender.clone();
+            let (areas, next_area, render_pixel) = (&areas, &next_area, &render_pixel);
+            scope.spawn(move || {
+                while let Some(&(x, y, width, height)) =
+                    areas.get(next_area.fetch_add(1, Ordering::Relaxed))
+                {
+                    let mut tile = Tile::new(x, y, width, height);
+                    for v in 0..height {
+                        for u in 0..width {
+                            tile.set_pixel(u, v, render_pixel(x + u, y + v));
+                        }
+                    }
+                    stats::flush();
+                    sender.send(tile).unwrap();
+                }
+            });
+        }
+        drop(sender);
+
+        // Copy the tiles to the canvas as soon as they are ready
+        for tile in receiver {
+            canvas.set_tile(&tile);
+        }
+    });
 }
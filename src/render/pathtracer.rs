@@ -0,0 +1,226 @@
+use std::f32::consts::PI;
+
+use crate::{
+    bsdf::{Bsdf, Lambertian},
+    canvas::Canvas,
+    color::Color,
+    light::{AmbiantLight, Light, SHADOW_EPSILON},
+    math::Vector3,
+    ray::{Hit, Ray},
+    render::{default_thread_count, render_tiles, schlick, Renderer},
+    sampling::{Random, Sampling},
+    scene::Scene,
+};
+
+/**
+ * @brief Number of bounces after which the paths may be terminated by the Russian roulette
+ */
+const ROULETTE_DEPTH: u32 = 3;
+
+/**
+ * @brief Compute the global illumination with unidirectional Monte Carlo path tracing
+ *
+ * The diffuse bounces are sampled with a cosine-weighted distribution and the
+ * point and directional lights are sampled explicitly at each diffuse hit.
+ * The ambient lights become a uniform sky, seen by the paths leaving the scene.
+ * The physically based materials are sampled with their BSDF, the other
+ * ones ignore the specular exponent of the Phong model. Like the reflections
+ * of the other renderers, the paths stop after the max_depth bounces of the
+ * scene.
+ */
+pub struct PathTracerRenderer {
+    pub threads: usize,
+    pub sampling: Sampling,
+}
+
+impl PathTracerRenderer {
+    /**
+     * @brief Get the light coming from the sky
+     */
+    pub fn environment(&self, lights: &[Box<dyn Light>]) -> Color {
+        lights
+            .iter()
+            .filter_map(|light| light.as_any().downcast_ref::<AmbiantLight>())
+            .map(|light| light.color * light.intensity)
+            .sum()
+    }
+
+    /**
+     * @brief Sample the point and directional lights from a hit
+     *
+     * The lights are scaled by PI, like in the software renderer.
+     *
+     * @param scene the scene
+     * @param hit the hit, with the outward normal
+     * @param bsdf the BSDF of the surface
+     * @param wo the normalized direction toward the viewer
+     */
+    pub fn sample_lights(&self, scene: &Scene, hit: &Hit, bsdf: &dyn Bsdf, wo: Vector3) -> Color {
+        // Cast the shadow rays from the side of the viewer
+        let shadow_hit = if hit.normal.dot(wo) < 0.0 {
+            Hit {
+                normal: -hit.normal,
+                ..*hit
+            }
+        } else {
+            *hit
+        };
+
+        scene
+            .lights
+            .iter()
+            .filter_map(|light| {
+                let (direction, light_color) = light.illumination(&shadow_hit)?;
+                let direction = direction.normalize();
+                if shadow_hit.normal.dot(direction) <= 0.0 {
+                    return None;
+                }
+
+                let occluded = light
+                    .shadow_ray(&shadow_hit)
+                    .is_some_and(|(ray, max_distance)| {
+                        scene
+                            .root
+                            .hit(&ray)
+                            .is_some_and(|occluder| occluder.distance < max_distance)
+                    });
+                if occluded {
+                    None
+                } else {
+                    Some(bsdf.evaluate(hit.normal, wo, direction) * light_color * PI)
+                }
+            })
+            .sum()
+    }
+
+    /**
+     * @brief Compute the light carried by a path
+     *
+     * @param scene the scene
+     * @param ray the ray starting the path
+     * @param random the random generator of the pixel
+     */
+    pub fn trace_path(&self, scene: &Scene, ray: &Ray, random: &mut Random) -> Color {
+        let environment = self.environment(&scene.lights);
+        let mut radiance = Color::BLACK;
+        let mut throughput = Color::WHITE;
+        let mut ray = *ray;
+
+        // The primary hit, then the bounces
+        for bounce in 0..=scene.max_depth {
+            let hit = match scene.root.hit(&ray) {
+                Some(hit) => scene.texture_hit(&ray, hit),
+                None => return radiance + throughput * environment,
+            };
+            let material = hit.material;
+
+            // Orient the normal against the ray
+            let entering = ray.direction.dot(hit.normal) < 0.0;
+            let normal = if entering { hit.normal } else { -hit.normal };
+
+            // Attenuate the light that traveled inside the object with the Beer's law
+            if !entering {
+                let distance = hit.distance * ray.direction.length();
+                let absorption = material.absorption;
+                throughput = throughput
+                    * Color::new(
+                        (-absorption.r * distance).exp(),
+                        (-absorption.g * distance).exp(),
+                        (-absorption.b * distance).exp(),
+                    );
+            }
+
+            // Pick a lobe with the same weights as the Whitted renderer
+            let transparency = material.transparency;
+            let reflective = material.reflective * (1.0 - transparency);
+            let choice = random.next_f32();
+            let wo = -ray.direction.normalize();
+            let direction = if let Some(model) = &material.bsdf {
+                let bsdf = model.as_bsdf();
+                radiance = radiance + throughput * self.sample_lights(scene, &hit, bsdf, wo);
+                match bsdf.sample(hit.normal, wo, random) {
+                    Some(sample) => {
+                        throughput = throughput * sample.weight;
+                        sample.direction
+                    }
+                    None => break,
+                }
+            } else if choice < transparency {
+                let (n1, n2) = if entering {
+                    (1.0, material.refractive_index)
+                } else {
+                    (material.refractive_index, 1.0)
+                };
+                let cos_i = -normal.dot(ray.direction.normalize());
+                let refracted = ray.direction.refract(normal, n1 / n2);
+                match refracted {
+                    Some(direction) if random.next_f32() >= schlick(cos_i, n1, n2) => direction,
+                    _ => ray.direction.reflect(normal),
+                }
+            } else if choice < transparency + reflective {
+                ray.direction.reflect(normal)
+            } else {
+                // Diffuse, with the direct light sampled explicitly
+                let diffuse = Lambertian {
+                    color: material.color,
+                };
+                radiance = radiance + throughput * self.sample_lights(scene, &hit, &diffuse, wo);
+                throughput = throughput * material.color;
+                random.cosine_hemisphere(normal)
+            };
+
+            // Start the next ray on the side of the surface it goes to
+            let side = if direction.dot(normal) > 0.0 {
+                SHADOW_EPSILON
+            } else {
+                -SHADOW_EPSILON
+            };
+            ray = Ray {
+                origin: hit.position + normal * side,
+                direction,
+                spread: ray.spread,
+            };
+
+            // Terminate the paths carrying little light, without bias
+            if bounce >= ROULETTE_DEPTH {
+                let survival = throughput
+                    .r
+                    .max(throughput.g)
+                    .max(throughput.b)
+                    .clamp(0.05, 0.95);
+                if random.next_f32() >= survival {
+                    break;
+                }
+                throughput = throughput * (1.0 / survival);
+            }
+        }
+
+        radiance
+    }
+
+    pub fn render_pixel(&self, scene: &Scene, u: u32, v: u32, width: u32, height: u32) -> Color {
+        self.sampling.sample_pixel(u, v, |x, y, random| {
+            let ray = scene.camera.ray(x, y, width, height);
+            self.trace_path(scene, &ray, random)
+        })
+    }
+}
+
+impl Default for PathTracerRenderer {
+    fn default() -> Self {
+        Self {
+            threads: default_thread_count(),
+            sampling: Sampling::default(),
+        }
+    }
+}
+
+impl Renderer for PathTracerRenderer {
+    fn render(&self, scene: &Scene, canvas: &mut dyn Canvas) {
+        let (width, height) = (canvas.width(), canvas.height());
+
+        render_tiles(canvas, self.threads, |u, v| {
+            self.render_pixel(scene, u, v, width, height)
+        });
+    }
+}
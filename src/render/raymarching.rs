@@ -0,0 +1,218 @@
+use crate::{
+    canvas::Canvas,
+    color::Color,
+    field::{field_hit, march, HIT_EPSILON, MAX_DISTANCE, MAX_STEPS},
+    light::{AmbiantLight, SHADOW_EPSILON},
+    math::Vector3,
+    ray::{Hit, Ray},
+    render::{default_thread_count, render_tiles, Renderer},
+    sampling::Sampling,
+    scene::Scene,
+    sdf::SdfNode,
+};
+
+/**
+ * @brief Number of samples along the normal for the ambient occlusion
+ */
+const OCCLUSION_SAMPLES: u32 = 5;
+
+/**
+ * @brief Render the signed distance field of the scene with sphere tracing
+ *
+ * Only the nodes with a field are visible. The surfaces are opaque but may
+ * be reflective, the shadows of the lights are soft and the ambient light
+ * is attenuated by the occlusion of the field.
+ */
+pub struct RaymarchingRenderer {
+    pub threads: usize,
+    pub sampling: Sampling,
+
+    /**
+     * @brief Sharpness of the penumbras, higher values give harder shadows
+     */
+    pub shadow_hardness: f32,
+
+    /**
+     * @brief Distance between the samples of the ambient occlusion
+     */
+    pub occlusion_step: f32,
+}
+
+impl RaymarchingRenderer {
+    /**
+     * @brief Compute the fraction of a light reaching a point
+     *
+     * The nearest distance to the field along the shadow ray, relative to
+     * the distance traveled, estimates how much of the light is covered.
+     *
+     * @param field the field of the scene
+     * @param ray the ray toward the light
+     * @param max_distance the distance of the light along the ray
+     */
+    pub fn soft_shadow(&self, field: &dyn SdfNode, ray: &Ray, max_distance: f32) -> f32 {
+        let length = ray.direction.length();
+        let max_distance = (max_distance * length).min(MAX_DISTANCE);
+        let mut light: f32 = 1.0;
+        let mut t = SHADOW_EPSILON;
+        for _ in 0..MAX_STEPS {
+            if t >= max_distance {
+                break;
+            }
+
+            let d = field.distance(ray.origin + ray.direction * (t / length));
+            if d < HIT_EPSILON {
+                return 0.0;
+            }
+            light = light.min(self.shadow_hardness * d / t);
+            t += d;
+        }
+
+        light.clamp(0.0, 1.0)
+    }
+
+    /**
+     * @brief Estimate the ambient occlusion of a point
+     *
+     * Compare the distance to the field with the distance to the surface
+     * at a few points along the normal.
+     *
+     * @return 1 for a fully visible point, 0 for a fully occluded one
+     */
+    pub fn ambient_occlusion(
+        &self,
+        field: &dyn SdfNode,
+        position: Vector3,
+        normal: Vector3,
+    ) -> f32 {
+        let mut occlusion = 0.0;
+        let mut weight = 1.0;
+        for i in 1..=OCCLUSION_SAMPLES {
+            let distance = self.occlusion_step * i as f32;
+            occlusion += weight * (distance - field.distance(position + normal * distance));
+            weight *= 0.5;
+        }
+
+        (1.0 - occlusion / self.occlusion_step).clamp(0.0, 1.0)
+    }
+
+    pub fn compute_light(
+        &self,
+        scene: &Scene,
+        field: &dyn SdfNode,
+        hit: &Hit,
+        inverse_direction: Vector3,
+    ) -> Color {
+        let occlusion = self.ambient_occlusion(field, hit.position, hit.normal);
+
+        scene
+            .lights
+            .iter()
+            .map(|light| {
+                let intensity = light.compute_intensity(hit, inverse_direction);
+                if light.as_any().is::<AmbiantLight>() {
+                    intensity * occlusion
+                } else if let Some((ray, max_distance)) = light.shadow_ray(hit) {
+                    intensity * self.soft_shadow(field, &ray, max_distance)
+                } else {
+                    intensity
+                }
+            })
+            .sum()
+    }
+
+    /**
+     * @brief Compute the color seen by a ray
+     *
+     * @param scene the scene
+     * @param field the field of the root of the scene
+     * @param ray the ray
+     * @param depth the remaining number of reflection bounces
+     */
+    pub fn compute_color(
+        &self,
+        scene: &Scene,
+        field: &dyn SdfNode,
+        ray: &Ray,
+        depth: u32,
+    ) -> Color {
+        let limit = MAX_DISTANCE / ray.direction.length();
+        let hit = match scene
+            .root
+            .bounding_box()
+            .clip(ray, 0.0, limit)
+            .and_then(|(start, end)| march(field, ray, start, end))
+        {
+            Some(distance) => scene.texture_hit(ray, field_hit(field, ray, distance)),
+            None => return Color::WHITE,
+        };
+        let material = hit.material;
+
+        // Orient the normal against the ray
+        let normal = if ray.direction.dot(hit.normal) < 0.0 {
+            hit.normal
+        } else {
+            -hit.normal
+        };
+        let hit = Hit { normal, ..hit };
+
+        let local_color = material.color * self.compute_light(scene, field, &hit, -ray.direction);
+        let reflective = material.reflective;
+        if depth == 0 || reflective <= 0.0 {
+            return local_color;
+        }
+
+        let reflected_ray = Ray {
+            origin: hit.position + normal * SHADOW_EPSILON,
+            direction: ray.direction.reflect(normal),
+            spread: ray.spread,
+        };
+        let reflected_color = self.compute_color(scene, field, &reflected_ray, depth - 1);
+
+        local_color * (1.0 - reflective) + reflected_color * reflective
+    }
+
+    pub fn render_pixel(
+        &self,
+        scene: &Scene,
+        field: &dyn SdfNode,
+        u: u32,
+        v: u32,
+        width: u32,
+        height: u32,
+    ) -> Color {
+        self.sampling.sample_pixel(u, v, |x, y, _| {
+            let ray = scene.camera.ray(x, y, width, height);
+            self.compute_color(scene, field, &ray, scene.max_depth)
+        })
+    }
+}
+
+impl Default for RaymarchingRenderer {
+    fn default() -> Self {
+        Self {
+            threads: default_thread_count(),
+            sampling: Sampling::default(),
+            shadow_hardness: 16.0,
+            occlusion_step: 0.05,
+        }
+    }
+}
+
+impl Renderer for RaymarchingRenderer {
+    fn render(&self, scene: &Scene, canvas: &mut dyn Canvas) {
+        let (width, height) = (canvas.width(), canvas.height());
+
+        // Nothing can be raymarched without a field
+        let field = match scene.root.as_sdf() {
+            Some(field) => field,
+            None => {
+                render_tiles(canvas, self.threads, |_, _| Color::WHITE);
+                return;
+            }
+        };
+
+        render_tiles(canvas, self.threads, |u, v| {
+            self.render_pixel(scene, field, u, v, width, height)
+        });
+    }
+}
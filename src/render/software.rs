@@ -1,66 +1,254 @@
+use std::f32::consts::PI;
+
 use crate::{
+    bsdf::Bsdf,
     canvas::Canvas,
     color::Color,
-    light::Light,
+    light::{Light, SHADOW_EPSILON},
     math::Vector3,
     ray::{Hit, Ray},
-    render::Renderer,
+    render::{default_thread_count, render_tiles, schlick, Renderer},
+    sampling::Sampling,
     scene::Scene,
+    sdf::Node,
 };
 
-pub struct SoftwareRenderer {}
+pub struct SoftwareRenderer {
+    pub threads: usize,
+    pub sampling: Sampling,
+}
 
 impl SoftwareRenderer {
+    /**
+     * @brief Check if something is between the hit and the light
+     *
+     * @param root the root node of the scene
+     * @param light the light to test
+     * @param hit the lighted point
+     */
+    pub fn is_in_shadow(&self, root: &dyn Node, light: &dyn Light, hit: &Hit) -> bool {
+        match light.shadow_ray(hit) {
+            Some((ray, max_distance)) => root
+                .hit(&ray)
+                .is_some_and(|occluder| occluder.distance < max_distance),
+            None => false,
+        }
+    }
+
     pub fn compute_light(
         &self,
-        lights: &Vec<Box<dyn Light>>,
+        root: &dyn Node,
+        lights: &[Box<dyn Light>],
         hit: &Hit,
         inverse_direction: Vector3,
-    ) -> f32 {
+    ) -> Color {
         lights
             .iter()
+            .filter(|light| !self.is_in_shadow(root, light.as_ref(), hit))
             .map(|light| light.compute_intensity(hit, inverse_direction))
             .sum()
     }
 
-    pub fn compute_color(&self, scene: &Scene, ray: &Ray) -> Color {
-        let color = if let Some(hit) = scene.root.hit(ray) {
-            hit.material.color * self.compute_light(&scene.lights, &hit, -ray.direction)
-        } else {
-            Color::WHITE
+    /**
+     * @brief Compute the direct light reflected by a physically based surface
+     *
+     * The point and directional lights are scaled by PI, so that a white
+     * Lambertian surface facing a light reflects its intensity, like with the
+     * Phong model. The ambient lights are reflected with the albedo.
+     *
+     * @param wo the normalized direction toward the viewer
+     */
+    pub fn compute_bsdf_light(
+        &self,
+        root: &dyn Node,
+        lights: &[Box<dyn Light>],
+        hit: &Hit,
+        bsdf: &dyn Bsdf,
+        wo: Vector3,
+    ) -> Color {
+        lights
+            .iter()
+            .filter(|light| !self.is_in_shadow(root, light.as_ref(), hit))
+            .map(|light| match light.illumination(hit) {
+                Some((direction, light_color)) => {
+                    bsdf.evaluate(hit.normal, wo, direction.normalize()) * light_color * PI
+                }
+                None => bsdf.albedo() * light.compute_intensity(hit, wo),
+            })
+            .sum()
+    }
+
+    /**
+     * @brief Compute the color of a physically based surface
+     *
+     * Only the mirror reflection and the refraction are traced, weighted by
+     * the BSDF.
+     */
+    pub fn compute_bsdf_color(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        hit: &Hit,
+        bsdf: &dyn Bsdf,
+        depth: u32,
+    ) -> Color {
+        let wo = -ray.direction.normalize();
+        let mut color = self.compute_bsdf_light(scene.root.as_ref(), &scene.lights, hit, bsdf, wo);
+        if depth == 0 {
+            return color;
+        }
+
+        // Orient the normal against the ray
+        let entering = ray.direction.dot(hit.normal) < 0.0;
+        let normal = if entering { hit.normal } else { -hit.normal };
+        let (reflection, refraction) = bsdf.specular_weights(hit.normal, wo);
+
+        if reflection.luminance() > 0.0 {
+            let reflected_ray = Ray {
+                origin: hit.position + normal * SHADOW_EPSILON,
+                direction: ray.direction.reflect(normal),
+                spread: ray.spread,
+            };
+            color = color + reflection * self.compute_color(scene, &reflected_ray, depth - 1);
+        }
+
+        if refraction.luminance() > 0.0 {
+            let (n1, n2) = if entering {
+                (1.0, bsdf.refractive_index())
+            } else {
+                (bsdf.refractive_index(), 1.0)
+            };
+            if let Some(direction) = ray.direction.refract(normal, n1 / n2) {
+                let refracted_ray = Ray {
+                    origin: hit.position - normal * SHADOW_EPSILON,
+                    direction,
+                    spread: ray.spread,
+                };
+                color = color + refraction * self.compute_color(scene, &refracted_ray, depth - 1);
+            }
+        }
+
+        color
+    }
+
+    /**
+     * @brief Compute the color of a surface with the Phong parameters of its material
+     */
+    pub fn compute_phong_color(&self, scene: &Scene, ray: &Ray, hit: &Hit, depth: u32) -> Color {
+        let material = hit.material;
+
+        // Orient the normal against the ray
+        let entering = ray.direction.dot(hit.normal) < 0.0;
+        let normal = if entering { hit.normal } else { -hit.normal };
+
+        let local_color = material.color
+            * self.compute_light(scene.root.as_ref(), &scene.lights, hit, -ray.direction);
+
+        // Stop here if we hit the recursion limit or if the object doesn't spawn rays
+        let reflective = material.reflective;
+        let transparency = material.transparency;
+        if depth == 0 || (reflective <= 0.0 && transparency <= 0.0) {
+            return local_color;
+        }
+
+        // Compute the reflected color
+        let reflected_ray = Ray {
+            origin: hit.position + normal * SHADOW_EPSILON,
+            direction: ray.direction.reflect(normal),
+            spread: ray.spread,
         };
+        let reflected_color = self.compute_color(scene, &reflected_ray, depth - 1);
+        let mut color = local_color * (1.0 - reflective) + reflected_color * reflective;
+
+        // Compute the transmitted color
+        if transparency > 0.0 {
+            let (n1, n2) = if entering {
+                (1.0, material.refractive_index)
+            } else {
+                (material.refractive_index, 1.0)
+            };
+            let cos_i = -normal.dot(ray.direction.normalize());
+            let reflectance = schlick(cos_i, n1, n2);
+
+            // The refracted ray doesn't exist in case of total internal reflection
+            let refracted_color = match ray.direction.refract(normal, n1 / n2) {
+                Some(direction) => {
+                    let refracted_ray = Ray {
+                        origin: hit.position - normal * SHADOW_EPSILON,
+                        direction,
+                        spread: ray.spread,
+                    };
+                    self.compute_color(scene, &refracted_ray, depth - 1)
+                }
+                None => Color::BLACK,
+            };
+            let transmitted_color =
+                reflected_color * reflectance + refracted_color * (1.0 - reflectance);
+
+            color = color * (1.0 - transparency) + transmitted_color * transparency;
+        }
 
         color
     }
 
-    pub fn render_pixel(&self, scene: &Scene, u: u32, v: u32, width: u32) -> Color {
-        // Compute the direction of the ray
-        let direction = Vector3::new(
-            (u as f32 / width as f32) - 0.5,
-            (v as f32 / width as f32) - 0.5,
-            1.0,
-        ) * scene.camera.view_port;
-        let ray = Ray {
-            origin: scene.camera.position,
-            direction: direction,
+    /**
+     * @brief Compute the color seen by a ray
+     *
+     * @param scene the scene
+     * @param ray the ray
+     * @param depth the remaining number of reflection and refraction bounces
+     */
+    pub fn compute_color(&self, scene: &Scene, ray: &Ray, depth: u32) -> Color {
+        let hit = match scene.root.hit(ray) {
+            Some(hit) => scene.texture_hit(ray, hit),
+            None => return Color::WHITE,
+        };
+        let material = hit.material;
+        let entering = ray.direction.dot(hit.normal) < 0.0;
+
+        let mut color = match &material.bsdf {
+            Some(model) => self.compute_bsdf_color(scene, ray, &hit, model.as_bsdf(), depth),
+            None => self.compute_phong_color(scene, ray, &hit, depth),
         };
 
-        self.compute_color(&scene, &ray)
+        // Attenuate the light that traveled inside the object with the Beer's law
+        if !entering {
+            let distance = hit.distance * ray.direction.length();
+            let absorption = material.absorption;
+            color = color
+                * Color::new(
+                    (-absorption.r * distance).exp(),
+                    (-absorption.g * distance).exp(),
+                    (-absorption.b * distance).exp(),
+                );
+        }
+
+        color
+    }
+
+    pub fn render_pixel(&self, scene: &Scene, u: u32, v: u32, width: u32, height: u32) -> Color {
+        self.sampling.sample_pixel(u, v, |x, y, _| {
+            let ray = scene.camera.ray(x, y, width, height);
+            self.compute_color(scene, &ray, scene.max_depth)
+        })
+    }
+}
+
+impl Default for SoftwareRenderer {
+    fn default() -> Self {
+        Self {
+            threads: default_thread_count(),
+            sampling: Sampling::default(),
+        }
     }
 }
 
 impl Renderer for SoftwareRenderer {
     fn render(&self, scene: &Scene, canvas: &mut dyn Canvas) {
-        let canvas_width = canvas.width();
-        let canvas_height = canvas.height();
-
-        // Draw each pixel of the canvas
-        for v in 0..canvas_height {
-            for u in 0..canvas_width {
-                // Draw the pixel
-                let color = self.render_pixel(&scene, u, v, canvas_width);
-                canvas.set_pixel(u, v, color);
-            }
-        }
+        let (width, height) = (canvas.width(), canvas.height());
+
+        render_tiles(canvas, self.threads, |u, v| {
+            self.render_pixel(scene, u, v, width, height)
+        });
     }
 }
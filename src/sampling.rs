@@ -0,0 +1,321 @@
+use std::f32::consts::PI;
+
+use crate::{color::Color, math::Vector3};
+
+/**
+ * @brief A small permuted congruential generator (PCG32)
+ *
+ * Fast and good enough for sampling, and deterministic for a given seed.
+ */
+#[derive(Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+    const INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+    pub fn new(seed: u64) -> Self {
+        let mut random = Self { state: 0 };
+        random.next_u32();
+        random.state = random.state.wrapping_add(seed);
+        random.next_u32();
+
+        random
+    }
+
+    /**
+     * @brief Create an independent generator for a pixel
+     *
+     * The pixels don't share a generator, so that the result doesn't depend
+     * on the order in which they are rendered.
+     */
+    pub fn for_pixel(seed: u64, x: u32, y: u32) -> Self {
+        // Scramble the coordinates with the SplitMix64 finalizer
+        let mut z = seed ^ (((x as u64) << 32) | y as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        Self::new(z ^ (z >> 31))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(Self::INCREMENT);
+
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        xor_shifted.rotate_right((old_state >> 59) as u32)
+    }
+
+    /**
+     * @brief Get a uniform number in [0, 1)
+     */
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /**
+     * @brief Get a random direction in a hemisphere, with a density proportional to the cosine
+     *
+     * @param normal the normalized axis of the hemisphere
+     */
+    pub fn cosine_hemisphere(&mut self, normal: Vector3) -> Vector3 {
+        // Project a uniform point of the disk onto the hemisphere
+        let radius = self.next_f32().sqrt();
+        let angle = 2.0 * PI * self.next_f32();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        let height = (1.0 - radius * radius).max(0.0).sqrt();
+
+        tangent * (radius * angle.cos()) + bitangent * (radius * angle.sin()) + normal * height
+    }
+}
+
+/**
+ * @brief A reconstruction filter, weighting the samples around the center of a pixel
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+    MitchellNetravali,
+}
+
+impl Filter {
+    /**
+     * @brief Get the filter from its name on the command line or in a scene
+     */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(Self::Box),
+            "tent" => Some(Self::Tent),
+            "gaussian" => Some(Self::Gaussian),
+            "mitchell" => Some(Self::MitchellNetravali),
+            _ => None,
+        }
+    }
+
+    /**
+     * @brief Get the half size of the support of the filter, in pixels
+     */
+    pub fn radius(&self) -> f32 {
+        match self {
+            Self::Box => 0.5,
+            Self::Tent => 1.0,
+            Self::Gaussian => 1.5,
+            Self::MitchellNetravali => 2.0,
+        }
+    }
+
+    /**
+     * @brief Evaluate the one dimensional filter
+     *
+     * @param x the offset from the center of the pixel, in pixels
+     */
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Self::Box => 1.0,
+            Self::Tent => (1.0 - x).max(0.0),
+            Self::Gaussian => {
+                // Standard deviation of half a pixel, shifted to reach 0 at the radius
+                let alpha = 2.0;
+                ((-alpha * x * x).exp() - (-alpha * self.radius().powi(2)).exp()).max(0.0)
+            }
+            Self::MitchellNetravali => {
+                // The B = C = 1/3 parameters recommended by Mitchell and Netravali
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let value = if x < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b)
+                } else if x < 2.0 {
+                    (-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)
+                } else {
+                    0.0
+                };
+                value / 6.0
+            }
+        }
+    }
+
+    /**
+     * @brief Evaluate the separable two dimensional filter
+     *
+     * @param dx the horizontal offset from the center of the pixel
+     * @param dy the vertical offset from the center of the pixel
+     */
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+}
+
+/**
+ * @brief How the pixels are sampled
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Sampling {
+    /**
+     * @brief Number of rays per pixel
+     */
+    pub samples: u32,
+
+    pub filter: Filter,
+
+    /**
+     * @brief Seed of the random numbers, the same seed gives the same image
+     */
+    pub seed: u64,
+}
+
+impl Sampling {
+    /**
+     * @brief Compute the color of a pixel from several samples
+     *
+     * The samples are stratified over the support of the filter, jittered
+     * inside their stratum, and averaged with the weights of the filter.
+     * The samples left over by the largest square grid of strata are
+     * jittered over the whole support, so that every stratum is sampled.
+     * A single sample with the box filter goes through the center of the pixel.
+     *
+     * @param x the column of the pixel
+     * @param y the row of the pixel
+     * @param sample compute the color at a continuous position of the canvas,
+     * with the random generator of the pixel
+     */
+    pub fn sample_pixel<F>(&self, x: u32, y: u32, mut sample: F) -> Color
+    where
+        F: FnMut(f32, f32, &mut Random) -> Color,
+    {
+        let mut random = Random::for_pixel(self.seed, x, y);
+        let (center_x, center_y) = (x as f32 + 0.5, y as f32 + 0.5);
+        if self.samples <= 1 && self.filter == Filter::Box {
+            return sample(center_x, center_y, &mut random);
+        }
+
+        let samples = self.samples.max(1);
+        let columns = (samples as f32).sqrt().floor() as u32;
+        let strata = columns * columns;
+        let radius = self.filter.radius();
+
+        let (mut color, mut total_weight) = (Color::BLACK, 0.0);
+        for i in 0..samples {
+            let (column, row, size) = if i < strata {
+                (i % columns, i / columns, columns)
+            } else {
+                (0, 0, 1)
+            };
+            let dx = ((column as f32 + random.next_f32()) / size as f32 * 2.0 - 1.0) * radius;
+            let dy = ((row as f32 + random.next_f32()) / size as f32 * 2.0 - 1.0) * radius;
+            let weight = self.filter.evaluate(dx, dy);
+            if weight == 0.0 {
+                continue;
+            }
+
+            color = color + sample(center_x + dx, center_y + dy, &mut random) * weight;
+            total_weight += weight;
+        }
+
+        // The negative lobes of a filter may cancel all the weights
+        if total_weight.abs() < 1e-6 {
+            return sample(center_x, center_y, &mut random);
+        }
+
+        (color * (1.0 / total_weight)).clamp(0.0, f32::INFINITY)
+    }
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            samples: 1,
+            filter: Filter::Box,
+            seed: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [Filter; 4] = [
+        Filter::Box,
+        Filter::Tent,
+        Filter::Gaussian,
+        Filter::MitchellNetravali,
+    ];
+
+    #[test]
+    fn same_seed_gives_the_same_pixel() {
+        let pixel = |seed| {
+            let sampling = Sampling {
+                samples: 6,
+                filter: Filter::MitchellNetravali,
+                seed,
+            };
+            let color = sampling.sample_pixel(3, 7, |x, y, _| Color::new(x, y, 0.0));
+            [color.r, color.g]
+        };
+
+        assert_eq!(pixel(1), pixel(1));
+        assert_ne!(pixel(1), pixel(2));
+    }
+
+    #[test]
+    fn filters_weight_the_center_the_most() {
+        for filter in FILTERS {
+            let center = filter.evaluate(0.0, 0.0);
+            assert!(center > 0.0, "{:?}", filter);
+            assert!(filter.evaluate(0.3, 0.2) <= center, "{:?}", filter);
+        }
+
+        // Nothing outside of the support
+        for filter in [Filter::Tent, Filter::Gaussian, Filter::MitchellNetravali] {
+            assert_eq!(filter.evaluate(filter.radius() + 0.01, 0.0), 0.0);
+        }
+
+        assert_eq!(Filter::Tent.evaluate(0.5, 0.0), 0.5);
+        let mitchell_center = (8.0f32 / 9.0).powi(2);
+        assert!((Filter::MitchellNetravali.evaluate(0.0, 0.0) - mitchell_center).abs() < 1e-6);
+    }
+
+    #[test]
+    fn weights_are_normalized() {
+        // A uniform image stays the same whatever the filter
+        for filter in FILTERS {
+            let sampling = Sampling {
+                samples: 9,
+                filter,
+                seed: 3,
+            };
+            let color = sampling.sample_pixel(0, 0, |_, _, _| Color::new(0.25, 0.5, 1.0));
+            assert!((color.r - 0.25).abs() < 1e-5, "{:?}", filter);
+            assert!((color.g - 0.5).abs() < 1e-5, "{:?}", filter);
+            assert!((color.b - 1.0).abs() < 1e-5, "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn every_stratum_is_sampled() {
+        // 5 samples fill the 2x2 strata of the support and add one anywhere
+        let sampling = Sampling {
+            samples: 5,
+            filter: Filter::Tent,
+            seed: 9,
+        };
+        let mut quadrants = [0; 4];
+        sampling.sample_pixel(0, 0, |x, y, _| {
+            quadrants[(x > 0.5) as usize + 2 * (y > 0.5) as usize] += 1;
+            Color::BLACK
+        });
+
+        assert!(quadrants.iter().all(|&count| count > 0), "{:?}", quadrants);
+    }
+}
@@ -1,20 +1,66 @@
+use crate::bsdf::BsdfModel;
+use crate::bvh::BvhNode;
 use crate::camera::Camera;
 use crate::color::Color;
+use crate::csg::{DifferenceNode, IntersectionNode};
+use crate::display::DisplayTransform;
+use crate::field::{
+    DisplacementNode, RepetitionNode, SmoothSubtractionNode, SmoothUnionNode, TwistNode,
+};
 use crate::light::{AmbiantLight, DirectionalLight, Light, OmniDirectionalLight};
-use crate::material::Material;
-use crate::math::Vector3;
+use crate::material::{Material, MaterialTextures};
+use crate::math::{Matrix4, Vector3};
+use crate::mesh::MeshNode;
 use crate::scene::Scene;
-use crate::sdf::{Node, SphereNode, UnionNode};
-use std::fs::File;
+use crate::sdf::{
+    BoxNode, ConeNode, CylinderNode, DiskNode, Node, PlaneNode, SphereNode, TorusNode, UnionNode,
+};
+use crate::texture::Texture;
+use crate::transform::{InstanceNode, TransformNode};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
 use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
 
-use super::Saver;
+use super::{SaveError, Saver};
 
-pub struct LispSaver {}
+#[derive(Default)]
+pub struct LispSaver {
+    /**
+     * @brief The names of the prototypes met so far, by the address of their node
+     */
+    prototype_names: RefCell<HashMap<usize, String>>,
+
+    /**
+     * @brief The names and the definitions of the prototypes, each one after the prototypes it uses
+     */
+    prototypes: RefCell<Vec<(String, Vec<u8>)>>,
+
+    /**
+     * @brief What couldn't be written, the scene isn't saved if there is any
+     */
+    errors: RefCell<Vec<String>>,
+}
 
 impl LispSaver {
+    /**
+     * @brief Record a part of the scene which can't be written
+     */
+    fn error(&self, message: String) {
+        self.errors.borrow_mut().push(message);
+    }
+
+    /**
+     * @brief Take the errors recorded so far
+     */
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.take()
+    }
+
     pub fn write_indent(&self, out: &mut dyn Write, level: u32) {
-        write!(out, "\n").unwrap();
+        writeln!(out).unwrap();
         for _ in 0..level {
             write!(out, "    ").unwrap();
         }
@@ -25,36 +71,277 @@ impl LispSaver {
         write!(out, "(vector3 {} {} {})", vector.x, vector.y, vector.z).unwrap();
     }
 
+    /**
+     * @brief Write a matrix, one row per line
+     */
+    pub fn write_matrix4(&self, out: &mut dyn Write, matrix: &Matrix4, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(matrix4").unwrap();
+        for row in &matrix.m {
+            self.write_indent(out, level + 1);
+            write!(out, "{} {} {} {}", row[0], row[1], row[2], row[3]).unwrap();
+        }
+        write!(out, ")").unwrap();
+    }
+
     pub fn write_camera(&self, out: &mut dyn Write, camera: &Camera, level: u32) {
         self.write_indent(out, level);
         write!(out, "(camera ").unwrap();
         self.write_vector3(out, &camera.position, level + 1);
         write!(out, " ").unwrap();
-        self.write_vector3(out, &camera.view_port, level + 1);
-        write!(out, ")").unwrap();
+
+        // Legacy cameras only have a view port
+        if let Some(view_port) = camera.view_port {
+            write!(
+                out,
+                "(view_port {} {} {}))",
+                view_port.x, view_port.y, view_port.z
+            )
+            .unwrap();
+            return;
+        }
+
+        self.write_vector3(out, &camera.direction, level + 1);
+        write!(out, " ").unwrap();
+        self.write_vector3(out, &camera.up, level + 1);
+        self.write_indent(out, level + 1);
+        write!(out, "{}", camera.fov).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{})", camera.roll).unwrap();
+    }
+
+    pub fn write_union_node(&self, out: &mut dyn Write, nodes: &[&dyn Node], level: u32) {
+        self.write_csg_node(out, "union", nodes, level);
+    }
+
+    pub fn write_csg_node(
+        &self,
+        out: &mut dyn Write,
+        operation: &str,
+        nodes: &[&dyn Node],
+        level: u32,
+    ) {
+        self.write_indent(out, level);
+        write!(out, "({} (list", operation).unwrap();
+        for node in nodes.iter() {
+            write!(out, " ").unwrap();
+            self.write_node(out, *node, level + 1);
+        }
+        write!(out, "))").unwrap();
     }
 
-    pub fn write_union_node(&self, out: &mut dyn Write, node: &UnionNode, level: u32) {
+    pub fn write_smooth_node(
+        &self,
+        out: &mut dyn Write,
+        operation: &str,
+        smoothness: f32,
+        nodes: &[Box<dyn Node>],
+        level: u32,
+    ) {
         self.write_indent(out, level);
-        write!(out, "(union (list").unwrap();
-        for node in node.nodes.iter() {
+        write!(out, "({} ", operation).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", smoothness).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "(list").unwrap();
+        for node in nodes.iter() {
             write!(out, " ").unwrap();
-            self.write_node(out, node, level + 1);
+            self.write_node(out, node.as_ref(), level + 2);
         }
         write!(out, "))").unwrap();
     }
 
+    pub fn write_transform_node(&self, out: &mut dyn Write, node: &TransformNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(transform ").unwrap();
+        self.write_matrix4(out, node.transform(), level + 1);
+        self.write_node(out, node.node.as_ref(), level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    /**
+     * @brief Get the name of a prototype, adding it to the prototypes the first time
+     */
+    pub fn prototype_name(&self, prototype: &Arc<dyn Node>) -> String {
+        let address = Arc::as_ptr(prototype) as *const () as usize;
+        if let Some(name) = self.prototype_names.borrow().get(&address) {
+            return name.clone();
+        }
+
+        // The prototypes used by this one are added while writing it
+        let mut definition = Vec::new();
+        self.write_node(&mut definition, prototype.as_ref(), 3);
+        let name = format!("prototype{}", self.prototype_names.borrow().len() + 1);
+        self.prototype_names
+            .borrow_mut()
+            .insert(address, name.clone());
+        self.prototypes
+            .borrow_mut()
+            .push((name.clone(), definition));
+
+        name
+    }
+
+    /**
+     * @brief Write the prototypes of the instances, each one once
+     */
+    pub fn write_prototypes(&self, out: &mut dyn Write, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(prototypes").unwrap();
+        for (name, definition) in self.prototypes.borrow().iter() {
+            self.write_indent(out, level + 1);
+            write!(out, "(prototype {}", name).unwrap();
+            out.write_all(definition).unwrap();
+            write!(out, ")").unwrap();
+        }
+        write!(out, ")").unwrap();
+    }
+
+    /**
+     * @brief Write an instance with the name of its prototype
+     */
+    pub fn write_instance_node(&self, out: &mut dyn Write, node: &InstanceNode, level: u32) {
+        let name = self.prototype_name(&node.prototype);
+
+        self.write_indent(out, level);
+        write!(out, "(instance ").unwrap();
+        self.write_matrix4(out, node.transform(), level + 1);
+        self.write_indent(out, level + 1);
+        write!(out, "{}", name).unwrap();
+        if let Some(material) = &node.material {
+            self.write_material(out, material, level + 1);
+        }
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_repetition_node(&self, out: &mut dyn Write, node: &RepetitionNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(repetition ").unwrap();
+        self.write_vector3(out, &node.period, level + 1);
+        self.write_node(out, node.node.as_ref(), level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_twist_node(&self, out: &mut dyn Write, node: &TwistNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(twist ").unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.amount).unwrap();
+        self.write_node(out, node.node.as_ref(), level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_displacement_node(
+        &self,
+        out: &mut dyn Write,
+        node: &DisplacementNode,
+        level: u32,
+    ) {
+        self.write_indent(out, level);
+        write!(out, "(displacement ").unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.amplitude).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.frequency).unwrap();
+        self.write_node(out, node.node.as_ref(), level + 1);
+        write!(out, ")").unwrap();
+    }
+
     pub fn write_color(&self, out: &mut dyn Write, color: &Color, level: u32) {
         self.write_indent(out, level);
         write!(out, "(color {} {} {})", color.r, color.g, color.b).unwrap();
     }
 
+    /**
+     * @brief Write a physically based material, as its type, its parameters and its absorption
+     */
+    pub fn write_bsdf_material(
+        &self,
+        out: &mut dyn Write,
+        model: &BsdfModel,
+        absorption: &Color,
+        textures: &MaterialTextures,
+        level: u32,
+    ) {
+        let (name, color, parameters) = match model {
+            BsdfModel::Lambertian(bsdf) => ("lambertian", bsdf.color, vec![]),
+            BsdfModel::Conductor(bsdf) => ("conductor", bsdf.color, vec![bsdf.roughness]),
+            BsdfModel::Dielectric(bsdf) => (
+                "dielectric",
+                bsdf.color,
+                vec![bsdf.roughness, bsdf.refractive_index],
+            ),
+            BsdfModel::Principled(bsdf) => (
+                "principled",
+                bsdf.color,
+                vec![
+                    bsdf.metallic,
+                    bsdf.roughness,
+                    bsdf.specular,
+                    bsdf.transmission,
+                    bsdf.refractive_index,
+                ],
+            ),
+        };
+
+        self.write_indent(out, level);
+        write!(out, "({} ", name).unwrap();
+        self.write_color(out, &color, level + 1);
+        for parameter in parameters {
+            self.write_indent(out, level + 1);
+            write!(out, "{}", parameter).unwrap();
+        }
+        self.write_color(out, absorption, level + 1);
+        self.write_material_textures(out, textures, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    /**
+     * @brief Write the indices of the textures of a material in the textures of the scene
+     */
+    pub fn write_material_textures(
+        &self,
+        out: &mut dyn Write,
+        textures: &MaterialTextures,
+        level: u32,
+    ) {
+        for (name, texture) in [
+            ("albedo_texture", textures.albedo),
+            ("specular_texture", textures.specular),
+            ("roughness_texture", textures.roughness),
+        ] {
+            if let Some(index) = texture {
+                self.write_indent(out, level);
+                write!(out, "({} {})", name, index).unwrap();
+            }
+        }
+    }
+
     pub fn write_material(&self, out: &mut dyn Write, material: &Material, level: u32) {
+        if let Some(model) = &material.bsdf {
+            return self.write_bsdf_material(
+                out,
+                model,
+                &material.absorption,
+                &material.textures,
+                level,
+            );
+        }
+
         self.write_indent(out, level);
         write!(out, "(material ").unwrap();
         self.write_color(out, &material.color, level + 1);
         self.write_indent(out, level + 1);
-        write!(out, "{})", material.specular).unwrap();
+        write!(out, "{}", material.specular).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", material.reflective).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", material.transparency).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", material.refractive_index).unwrap();
+        self.write_color(out, &material.absorption, level + 1);
+        self.write_material_textures(out, &material.textures, level + 1);
+        write!(out, ")").unwrap();
     }
 
     pub fn write_sphere_node(&self, out: &mut dyn Write, node: &SphereNode, level: u32) {
@@ -67,13 +354,147 @@ impl LispSaver {
         write!(out, ")").unwrap();
     }
 
-    pub fn write_node(&self, out: &mut dyn Write, node: &Box<dyn Node>, level: u32) {
-        match node.as_any().downcast_ref::<UnionNode>() {
-            Some(node) => self.write_union_node(out, node, level),
-            _ => match node.as_any().downcast_ref::<SphereNode>() {
-                Some(node) => self.write_sphere_node(out, node, level),
-                _ => {}
-            },
+    pub fn write_plane_node(&self, out: &mut dyn Write, node: &PlaneNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(plane ").unwrap();
+        self.write_vector3(out, &node.position, level + 1);
+        self.write_vector3(out, &node.normal, level + 1);
+        self.write_material(out, &node.material, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_disk_node(&self, out: &mut dyn Write, node: &DiskNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(disk ").unwrap();
+        self.write_vector3(out, &node.position, level + 1);
+        self.write_vector3(out, &node.normal, level + 1);
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.radius).unwrap();
+        self.write_material(out, &node.material, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_box_node(&self, out: &mut dyn Write, node: &BoxNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(box ").unwrap();
+        self.write_vector3(out, &node.position, level + 1);
+        self.write_vector3(out, &node.size, level + 1);
+        self.write_vector3(out, &node.rotation, level + 1);
+        self.write_material(out, &node.material, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_cylinder_node(&self, out: &mut dyn Write, node: &CylinderNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(cylinder ").unwrap();
+        self.write_vector3(out, &node.start, level + 1);
+        self.write_vector3(out, &node.end, level + 1);
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.radius).unwrap();
+        self.write_material(out, &node.material, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_cone_node(&self, out: &mut dyn Write, node: &ConeNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(cone ").unwrap();
+        self.write_vector3(out, &node.start, level + 1);
+        self.write_vector3(out, &node.end, level + 1);
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.radius).unwrap();
+        self.write_material(out, &node.material, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_torus_node(&self, out: &mut dyn Write, node: &TorusNode, level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(torus ").unwrap();
+        self.write_vector3(out, &node.position, level + 1);
+        self.write_vector3(out, &node.axis, level + 1);
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.major_radius).unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{}", node.minor_radius).unwrap();
+        self.write_material(out, &node.material, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_mesh_node(&self, out: &mut dyn Write, node: &MeshNode, level: u32) {
+        // Only the meshes loaded from a file can be saved, with an absolute
+        // path as the saved scene may be elsewhere
+        let Some(path) = &node.path else {
+            self.error(format!(
+                "a mesh of {} triangles wasn't loaded from a file",
+                node.triangles.len()
+            ));
+            return;
+        };
+        let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+        self.write_indent(out, level);
+        write!(out, "(mesh ").unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{:?}", path.to_string_lossy()).unwrap();
+        self.write_material(out, &node.material, level + 1);
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_node(&self, out: &mut dyn Write, node: &dyn Node, level: u32) {
+        if let Some(node) = node.as_any().downcast_ref::<UnionNode>() {
+            let nodes: Vec<&dyn Node> = node.nodes.iter().map(|node| node.as_ref()).collect();
+            self.write_union_node(out, &nodes, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<BvhNode>() {
+            // The hierarchy is rebuilt by the loader
+            let nodes: Vec<&dyn Node> = node
+                .unbounded_nodes
+                .iter()
+                .chain(node.nodes.iter())
+                .map(|node| node.as_ref())
+                .collect();
+            self.write_union_node(out, &nodes, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<IntersectionNode>() {
+            let nodes: Vec<&dyn Node> = node.nodes.iter().map(|node| node.as_ref()).collect();
+            self.write_csg_node(out, "intersection", &nodes, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<DifferenceNode>() {
+            let nodes: Vec<&dyn Node> = node.nodes.iter().map(|node| node.as_ref()).collect();
+            self.write_csg_node(out, "difference", &nodes, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<SmoothUnionNode>() {
+            self.write_smooth_node(out, "smooth_union", node.smoothness, &node.nodes, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<SmoothSubtractionNode>() {
+            self.write_smooth_node(
+                out,
+                "smooth_subtraction",
+                node.smoothness,
+                &node.nodes,
+                level,
+            );
+        } else if let Some(node) = node.as_any().downcast_ref::<RepetitionNode>() {
+            self.write_repetition_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<TwistNode>() {
+            self.write_twist_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<DisplacementNode>() {
+            self.write_displacement_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<TransformNode>() {
+            self.write_transform_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<InstanceNode>() {
+            self.write_instance_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<SphereNode>() {
+            self.write_sphere_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<PlaneNode>() {
+            self.write_plane_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<DiskNode>() {
+            self.write_disk_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<BoxNode>() {
+            self.write_box_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<CylinderNode>() {
+            self.write_cylinder_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<ConeNode>() {
+            self.write_cone_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<TorusNode>() {
+            self.write_torus_node(out, node, level);
+        } else if let Some(node) = node.as_any().downcast_ref::<MeshNode>() {
+            self.write_mesh_node(out, node, level);
+        } else {
+            self.error(String::from("a node of a type without a Lisp form"));
         }
     }
 
@@ -81,7 +502,9 @@ impl LispSaver {
         self.write_indent(out, level);
         write!(out, "(ambiant").unwrap();
         self.write_indent(out, level + 1);
-        write!(out, "{})", light.intensity).unwrap();
+        write!(out, "{}", light.intensity).unwrap();
+        self.write_color(out, &light.color, level + 1);
+        write!(out, ")").unwrap();
     }
 
     pub fn write_omnidirectional_light(
@@ -94,8 +517,10 @@ impl LispSaver {
         write!(out, "(omnidirectional").unwrap();
         self.write_indent(out, level + 1);
         write!(out, "{}", light.intensity).unwrap();
+        self.write_color(out, &light.color, level + 1);
         self.write_vector3(out, &light.position, level + 1);
-        write!(out, ")").unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{})", light.cast_shadows).unwrap();
     }
 
     pub fn write_directional_light(
@@ -108,48 +533,314 @@ impl LispSaver {
         write!(out, "(directional").unwrap();
         self.write_indent(out, level + 1);
         write!(out, "{}", light.intensity).unwrap();
+        self.write_color(out, &light.color, level + 1);
         self.write_vector3(out, &light.direction, level + 1);
-        write!(out, ")").unwrap();
+        self.write_indent(out, level + 1);
+        write!(out, "{})", light.cast_shadows).unwrap();
     }
 
-    pub fn write_light(&self, out: &mut dyn Write, light: &Box<dyn Light>, level: u32) {
-        match light.as_any().downcast_ref::<AmbiantLight>() {
-            Some(light) => self.write_ambiant_light(out, light, level + 1),
-            _ => match light.as_any().downcast_ref::<OmniDirectionalLight>() {
-                Some(light) => self.write_omnidirectional_light(out, light, level + 1),
-                _ => match light.as_any().downcast_ref::<DirectionalLight>() {
-                    Some(light) => self.write_directional_light(out, light, level + 1),
-                    _ => {}
-                },
-            },
+    pub fn write_light(&self, out: &mut dyn Write, light: &dyn Light, level: u32) {
+        if let Some(light) = light.as_any().downcast_ref::<AmbiantLight>() {
+            self.write_ambiant_light(out, light, level + 1);
+        } else if let Some(light) = light.as_any().downcast_ref::<OmniDirectionalLight>() {
+            self.write_omnidirectional_light(out, light, level + 1);
+        } else if let Some(light) = light.as_any().downcast_ref::<DirectionalLight>() {
+            self.write_directional_light(out, light, level + 1);
         }
     }
 
-    pub fn write_lights(&self, out: &mut dyn Write, lights: &Vec<Box<dyn Light>>, level: u32) {
+    pub fn write_lights(&self, out: &mut dyn Write, lights: &[Box<dyn Light>], level: u32) {
         self.write_indent(out, level);
         write!(out, "(list").unwrap();
         for light in lights.iter() {
             write!(out, " ").unwrap();
-            self.write_light(out, light, level);
+            self.write_light(out, light.as_ref(), level);
+        }
+        write!(out, ")").unwrap();
+    }
+
+    pub fn write_display(&self, out: &mut dyn Write, display: &DisplayTransform, level: u32) {
+        self.write_indent(out, level);
+        write!(
+            out,
+            "(display {} {} {})",
+            display.exposure,
+            display.tone_mapping.name(),
+            display.srgb
+        )
+        .unwrap();
+    }
+
+    pub fn write_textures(&self, out: &mut dyn Write, textures: &[Texture], level: u32) {
+        self.write_indent(out, level);
+        write!(out, "(textures").unwrap();
+        for (index, texture) in textures.iter().enumerate() {
+            // An absolute path, as the saved scene may be elsewhere
+            let Some(path) = &texture.path else {
+                self.error(format!("the texture {} wasn't loaded from a file", index));
+                continue;
+            };
+            let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+            self.write_indent(out, level + 1);
+            write!(
+                out,
+                "(texture {:?} {} {} {})",
+                path.to_string_lossy(),
+                texture.srgb,
+                texture.filter.name(),
+                texture.wrap.name()
+            )
+            .unwrap();
         }
         write!(out, ")").unwrap();
     }
 
     pub fn write_scene(&self, out: &mut dyn Write, scene: &Scene, level: u32) {
+        self.prototype_names.take();
+        self.prototypes.take();
+        self.errors.take();
+
+        // The prototypes are known once the nodes are written, and come before them
+        let mut root = Vec::new();
+        self.write_node(&mut root, scene.root.as_ref(), level + 1);
+
         self.write_indent(out, level);
         write!(out, "(scene ").unwrap();
         self.write_camera(out, &scene.camera, level + 1);
         write!(out, " ").unwrap();
-        self.write_node(out, &scene.root, level + 1);
+        if !self.prototypes.borrow().is_empty() {
+            self.write_prototypes(out, level + 1);
+        }
+        out.write_all(&root).unwrap();
         write!(out, " ").unwrap();
         self.write_lights(out, &scene.lights, level + 1);
+        self.write_indent(out, level + 1);
+        write!(out, "{}", scene.max_depth).unwrap();
+        self.write_display(out, &scene.display, level + 1);
+        if !scene.textures.is_empty() {
+            self.write_textures(out, &scene.textures, level + 1);
+        }
         write!(out, ")").unwrap();
     }
 }
 
 impl Saver for LispSaver {
-    fn save_scene_to_file(&self, scene: &crate::scene::Scene, path: &std::path::Path) {
-        let mut file = File::create(path).unwrap();
-        self.write_scene(&mut file, scene, 0);
+    fn save_scene_to_file(&self, scene: &Scene, path: &Path) -> Result<(), SaveError> {
+        let mut text = Vec::new();
+        self.write_scene(&mut text, scene, 0);
+
+        let errors = self.take_errors();
+        if !errors.is_empty() {
+            return Err(SaveError::Unsupported(path.to_path_buf(), errors));
+        }
+
+        fs::write(path, text).map_err(|error| SaveError::Io(path.to_path_buf(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Canvas, FrameBufferCanvas};
+    use crate::loader::{lisp::LispLoader, yaml::YamlLoader, Loader};
+    use crate::mesh::Triangle;
+    use crate::render::{software::SoftwareRenderer, Renderer};
+    use crate::sampling::Sampling;
+    use crate::texture::{TextureFilter, Wrap};
+    use image::{Rgb, RgbImage};
+    use std::path::{Path, PathBuf};
+    use std::{env, fs, process};
+
+    fn render(scene: &Scene) -> FrameBufferCanvas {
+        let mut canvas = FrameBufferCanvas::new(32, 24);
+        let renderer = SoftwareRenderer {
+            threads: 1,
+            sampling: Sampling::default(),
+        };
+        renderer.render(scene, &mut canvas);
+
+        canvas
+    }
+
+    /**
+     * @brief Save a YAML scene as a Lisp scene, and check that both render the same image
+     */
+    fn assert_round_trip(yaml_path: &Path, directory: &Path) {
+        let scene = YamlLoader::default()
+            .load_scene_from_file(yaml_path)
+            .unwrap_or_else(|error| panic!("{}", error));
+        let lisp_path = directory.join("round_trip.lisp");
+        LispSaver::default()
+            .save_scene_to_file(&scene, &lisp_path)
+            .unwrap_or_else(|error| panic!("{}", error));
+        let reloaded = LispLoader::default()
+            .load_scene_from_file(&lisp_path)
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        let (expected, actual) = (render(&scene), render(&reloaded));
+        let first = expected.get_pixel(0, 0);
+        let is_uniform = (0..expected.height())
+            .flat_map(|y| (0..expected.width()).map(move |x| (x, y)))
+            .all(|(x, y)| expected.get_pixel(x, y).r == first.r);
+        assert!(!is_uniform, "{}: the image is uniform", yaml_path.display());
+
+        for y in 0..expected.height() {
+            for x in 0..expected.width() {
+                let (a, b) = (expected.get_pixel(x, y), actual.get_pixel(x, y));
+                assert!(
+                    [a.r - b.r, a.g - b.g, a.b - b.b]
+                        .iter()
+                        .all(|d| d.abs() < 1e-5),
+                    "{}: the pixel {} {} differs",
+                    yaml_path.display(),
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    /**
+     * @brief Write the files of a test in a new directory
+     */
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("raytracer-{}-{}", test, process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for (name, text) in files {
+            fs::write(directory.join(name), text).unwrap();
+        }
+
+        directory
+    }
+
+    #[test]
+    fn scene1_round_trip() {
+        let directory = write_files("scene1-round-trip", &[]);
+        let yaml_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/scenes/scene1.yml");
+
+        assert_round_trip(&yaml_path, &directory);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn csg_transform_and_instance_round_trip() {
+        let directory = write_files(
+            "csg-round-trip",
+            &[(
+                "scene.yml",
+                "camera: {position: [0, 1, -6], direction: [0, -0.1, 1], fov: 60}
+prototypes:
+  pawn:
+    type: difference
+    nodes:
+      - {type: box, size: [1, 1, 1], material: {color: \"#3366cc\", specular: 50}}
+      - {type: sphere, position: [0, 0.5, 0], radius: 0.6}
+lights:
+  - {type: ambiant, intensity: 0.2}
+  - {type: omnidirectional, intensity: 0.6, position: [2, 4, -3]}
+root:
+  type: union
+  nodes:
+    - {type: plane, position: [0, -0.5, 0], normal: [0, 1, 0]}
+    - type: intersection
+      nodes:
+        - {type: sphere, position: [-1.5, 0.5, 0], material: {color: \"#cc3333\", reflective: 0.3}}
+        - {type: cylinder, start: [-1.5, -1, 0], end: [-1.5, 2, 0], radius: 0.4}
+    - type: transform
+      translation: [1.5, 0.5, 0]
+      rotation: {axis: [0, 1, 0], angle: 30}
+      scale: [1, 2, 1]
+      node: {type: torus, major_radius: 0.4, minor_radius: 0.1}
+    - {type: instance, prototype: pawn, translation: [0, 0, 1]}
+    - {type: instance, prototype: pawn, translation: [0, 0, 3], rotation: [0, 45, 0], material: {color: \"#33cc33\"}}
+",
+            )],
+        );
+
+        assert_round_trip(&directory.join("scene.yml"), &directory);
+        // Both instances use the same prototype, written once
+        let text = fs::read_to_string(directory.join("round_trip.lisp")).unwrap();
+        assert_eq!(text.matches("(prototype ").count(), 1);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn textured_scene_round_trip() {
+        let directory = write_files(
+            "texture-round-trip",
+            &[(
+                "scene.yml",
+                "camera: {position: [0, 1, -4], direction: [0, -0.2, 1]}
+root:
+  type: union
+  nodes:
+    - {type: plane, position: [0, -1, 0], normal: [0, 1, 0], material: {albedo_texture: checker.png}}
+    - type: sphere
+      material:
+        specular: 20
+        albedo_texture: {path: checker.png, filter: bilinear, wrap: clamp}
+",
+            )],
+        );
+        let checker = RgbImage::from_fn(8, 8, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([200, 40, 40])
+            }
+        });
+        checker.save(directory.join("checker.png")).unwrap();
+
+        assert_round_trip(&directory.join("scene.yml"), &directory);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn meshes_and_textures_without_a_file_are_not_saved() {
+        let directory = write_files("unsupported-save", &[]);
+        let mesh = MeshNode::new(
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![Triangle {
+                indices: [0, 1, 2],
+                material: None,
+            }],
+            Vec::new(),
+            Material::DEBUG_MATERIAL,
+        );
+        let scene = Scene {
+            camera: Camera::default(),
+            root: Box::new(mesh),
+            lights: Vec::new(),
+            max_depth: Scene::DEFAULT_MAX_DEPTH,
+            display: DisplayTransform::default(),
+            textures: vec![Texture::new(
+                1,
+                1,
+                vec![Color::WHITE],
+                TextureFilter::Bilinear,
+                Wrap::Repeat,
+            )],
+        };
+
+        let path = directory.join("unsupported.lisp");
+        match LispSaver::default().save_scene_to_file(&scene, &path) {
+            Err(SaveError::Unsupported(_, errors)) => assert_eq!(
+                errors,
+                [
+                    "a mesh of 1 triangles wasn't loaded from a file",
+                    "the texture 0 wasn't loaded from a file",
+                ]
+            ),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(()) => panic!("the scene was saved"),
+        }
+        assert!(!path.exists());
+        fs::remove_dir_all(directory).unwrap();
     }
 }
@@ -1,9 +1,54 @@
-use std::path::Path;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 
 use crate::scene::Scene;
 
 pub mod lisp;
 
 pub trait Saver {
-    fn save_scene_to_file(&self, scene: &Scene, path: &Path);
+    fn save_scene_to_file(&self, scene: &Scene, path: &Path) -> Result<(), SaveError>;
+}
+
+/**
+ * @brief The reason why a scene can't be saved
+ */
+#[derive(Debug)]
+pub enum SaveError {
+    /**
+     * @brief The file can't be written
+     */
+    Io(PathBuf, io::Error),
+
+    /**
+     * @brief Some parts of the scene can't be written in the format, the file isn't written
+     */
+    Unsupported(PathBuf, Vec<String>),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Unsupported(path, messages) => {
+                for (index, message) in messages.iter().enumerate() {
+                    if index > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}: {}", path.display(), message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, error) => Some(error),
+            _ => None,
+        }
+    }
 }
@@ -1,9 +1,48 @@
 use crate::camera::Camera;
+use crate::display::DisplayTransform;
 use crate::light::Light;
+use crate::ray::{Hit, Ray};
 use crate::sdf::Node;
+use crate::texture::Texture;
 
 pub struct Scene {
     pub camera: Camera,
     pub root: Box<dyn Node>,
     pub lights: Vec<Box<dyn Light>>,
+    pub max_depth: u32,
+
+    /**
+     * @brief How the render is shown and saved to 8-bit images
+     */
+    pub display: DisplayTransform,
+
+    /**
+     * @brief The textures referenced by the materials
+     */
+    pub textures: Vec<Texture>,
+}
+
+impl Scene {
+    /**
+     * @brief Default number of reflection bounces
+     */
+    pub const DEFAULT_MAX_DEPTH: u32 = 3;
+
+    /**
+     * @brief Apply the textures of the material of a hit
+     *
+     * @param ray the ray, whose spread gives the size of the pixel at the hit
+     * @param hit the hit of the ray
+     */
+    pub fn texture_hit(&self, ray: &Ray, hit: Hit) -> Hit {
+        if hit.material.textures.is_empty() {
+            return hit;
+        }
+
+        let footprint = hit.distance * ray.spread * hit.uv_density;
+        Hit {
+            material: hit.material.textured(&self.textures, hit.uv, footprint),
+            ..hit
+        }
+    }
 }
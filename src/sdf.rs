@@ -1,16 +1,104 @@
 use std::any::Any;
+use std::f32::consts::{PI, SQRT_2};
 
 use crate::{
+    aabb::Aabb,
+    csg::{pair_intervals, union_intervals},
     material::Material,
-    math::Vector3,
-    ray::{Hit, Ray},
+    math::{solve_quadratic, solve_quartic, Vector3},
+    ray::{Hit, Interval, Ray},
+    stats,
 };
 
-pub trait Node {
+pub trait Node: Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn hit(&self, ray: &Ray) -> Option<Hit>;
+
+    /**
+     * @brief Get the segments of the whole line of the ray inside the node
+     *
+     * Used by the constructive solid geometry. The surfaces without a
+     * volume have no interval.
+     *
+     * @return the sorted and disjoint intervals
+     */
+    fn intervals(&self, ray: &Ray) -> Vec<Interval>;
+
+    /**
+     * @brief Get the bounding box of the node
+     *
+     * @return the bounding box, Aabb::INFINITE for unbounded nodes
+     */
+    fn bounding_box(&self) -> Aabb;
+
+    /**
+     * @brief Get the signed distance field of the node
+     *
+     * @return the field, None if the node can't be raymarched
+     */
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        None
+    }
+}
+
+/**
+ * @brief A signed distance field, negative inside the volume
+ *
+ * The distance may be underestimated, but never overestimated, so that
+ * sphere tracing never steps over a surface.
+ */
+pub trait SdfNode: Send + Sync {
+    fn distance(&self, point: Vector3) -> f32;
+
+    /**
+     * @brief Get the material of the surface near a point
+     */
+    fn material(&self, point: Vector3) -> Material;
+}
+
+/**
+ * @brief Find the field of the nodes nearest to a point
+ *
+ * The nodes without a field are ignored.
+ *
+ * @return the field and its distance
+ */
+pub fn nearest_field<'a, I>(nodes: I, point: Vector3) -> Option<(&'a dyn SdfNode, f32)>
+where
+    I: Iterator<Item = &'a Box<dyn Node>>,
+{
+    nodes
+        .filter_map(|node| node.as_sdf())
+        .map(|field| (field, field.distance(point)))
+        .reduce(|acc, e| if acc.1 <= e.1 { acc } else { e })
+}
+
+/**
+ * @brief Distance to the nearest node, infinite without any field
+ */
+pub fn nearest_distance<'a, I>(nodes: I, point: Vector3) -> f32
+where
+    I: Iterator<Item = &'a Box<dyn Node>>,
+{
+    nearest_field(nodes, point).map_or(f32::INFINITY, |(_, distance)| distance)
+}
+
+/**
+ * @brief Material of the nearest node, the debug material without any field
+ */
+pub fn nearest_material<'a, I>(nodes: I, point: Vector3) -> Material
+where
+    I: Iterator<Item = &'a Box<dyn Node>>,
+{
+    nearest_field(nodes, point).map_or(Material::DEBUG_MATERIAL, |(field, _)| field.material(point))
 }
 
+/**
+ * @brief A group of nodes
+ *
+ * The hit is the nearest surface of the nodes, even inside an another node,
+ * while the intervals are the union of the volumes of the nodes.
+ */
 pub struct UnionNode {
     pub nodes: Vec<Box<dyn Node>>,
 }
@@ -26,6 +114,36 @@ impl Node for UnionNode {
             .filter_map(|node| node.hit(ray))
             .reduce(|acc, e| if acc.distance < e.distance { acc } else { e })
     }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        union_intervals(
+            self.nodes
+                .iter()
+                .flat_map(|node| node.intervals(ray))
+                .collect(),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .iter()
+            .map(|node| node.bounding_box())
+            .fold(Aabb::EMPTY, Aabb::union)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for UnionNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        nearest_distance(self.nodes.iter(), point)
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        nearest_material(self.nodes.iter(), point)
+    }
 }
 
 pub struct SphereNode {
@@ -34,12 +152,35 @@ pub struct SphereNode {
     pub material: Material,
 }
 
+impl SphereNode {
+    /**
+     * @brief Spherical texture coordinates, u around the Y axis and v from the bottom to the top
+     *
+     * @param normal the normal of the sphere at the point
+     */
+    pub fn uv(normal: Vector3) -> [f32; 2] {
+        [
+            0.5 + normal.z.atan2(normal.x) / (2.0 * PI),
+            0.5 + normal.y.clamp(-1.0, 1.0).asin() / PI,
+        ]
+    }
+
+    /**
+     * @brief The change of the texture coordinates per unit of length, averaged over u and v
+     */
+    fn uv_density(&self) -> f32 {
+        1.0 / (PI * SQRT_2 * self.radius)
+    }
+}
+
 impl Node for SphereNode {
     fn as_any(&self) -> &dyn Any {
         self
     }
 
     fn hit(&self, ray: &Ray) -> Option<Hit> {
+        stats::count_ray_primitive_test();
+
         let co = ray.origin - self.position;
         let a = ray.direction.dot(ray.direction);
         let b = co.dot(ray.direction) * 2.0;
@@ -50,21 +191,765 @@ impl Node for SphereNode {
             return None;
         }
 
-        let t1 = (-b + delta.sqrt()) / (2.0 * a);
-        let t2 = (-b - delta.sqrt()) / (2.0 * a);
-        let distance = t1.min(t2);
-        if distance < 0.0 {
+        // Take the nearest root in front of the ray, which is the far one
+        // if the ray starts inside the sphere
+        let t1 = (-b - delta.sqrt()) / (2.0 * a);
+        let t2 = (-b + delta.sqrt()) / (2.0 * a);
+        let distance = if t1 >= 0.0 {
+            t1
+        } else if t2 >= 0.0 {
+            t2
+        } else {
             return None;
-        }
+        };
 
         let hit_position = ray.origin + ray.direction * distance;
         let normal = (hit_position - self.position).normalize();
 
         Some(Hit {
             position: hit_position,
-            normal: normal,
-            distance: distance,
+            normal,
+            distance,
             material: self.material,
+            uv: Self::uv(normal),
+            uv_density: self.uv_density(),
+        })
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        stats::count_ray_primitive_test();
+
+        let co = ray.origin - self.position;
+        let a = ray.direction.dot(ray.direction) as f64;
+        let b = co.dot(ray.direction) as f64 * 2.0;
+        let c = (co.dot(co) - self.radius * self.radius) as f64;
+        let candidates = solve_quadratic(a, b, c).into_iter().map(|t| {
+            let distance = t as f32;
+            let normal = (ray.origin + ray.direction * distance - self.position).normalize();
+            (distance, normal)
+        });
+
+        let hits: Vec<Hit> = sorted_hits(ray, candidates, self.material)
+            .into_iter()
+            .map(|hit| Hit {
+                uv: Self::uv(hit.normal),
+                uv_density: self.uv_density(),
+                ..hit
+            })
+            .collect();
+
+        pair_intervals(&hits)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vector3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.position - radius, self.position + radius)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for SphereNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        (point - self.position).length() - self.radius
+    }
+
+    fn material(&self, _point: Vector3) -> Material {
+        self.material
+    }
+}
+
+/**
+ * @brief Orient the normal of a two-sided surface against the ray
+ */
+fn face_forward(normal: Vector3, ray: &Ray) -> Vector3 {
+    if normal.dot(ray.direction) > 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/**
+ * @brief Build the hits of all the candidates along the line of the ray
+ *
+ * @param ray the ray
+ * @param candidates the distances of the intersections and their normals
+ * @param material the material of the node
+ *
+ * @return the hits, sorted by distance
+ */
+fn sorted_hits(
+    ray: &Ray,
+    candidates: impl Iterator<Item = (f32, Vector3)>,
+    material: Material,
+) -> Vec<Hit> {
+    let mut hits: Vec<Hit> = candidates
+        .map(|(distance, normal)| Hit {
+            position: ray.origin + ray.direction * distance,
+            normal,
+            distance,
+            material,
+            uv: [0.0, 0.0],
+            uv_density: 0.0,
+        })
+        .collect();
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    hits
+}
+
+/**
+ * @brief Get the nearest hit in front of the ray
+ *
+ * @param hits the hits, sorted by distance
+ */
+fn nearest_hit(hits: &[Hit]) -> Option<Hit> {
+    hits.iter().find(|hit| hit.distance >= 0.0).copied()
+}
+
+/**
+ * @brief Intersect a ray with a plane
+ *
+ * @return the distance to the plane, possibly negative
+ */
+fn hit_plane(ray: &Ray, position: Vector3, normal: Vector3) -> Option<f32> {
+    let denominator = normal.dot(ray.direction);
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+
+    Some((position - ray.origin).dot(normal) / denominator)
+}
+
+/**
+ * @brief Bounding box of a disk
+ */
+fn disk_bounding_box(position: Vector3, normal: Vector3, radius: f32) -> Aabb {
+    let normal = normal.normalize();
+    let extent = Vector3::new(
+        (1.0 - normal.x * normal.x).max(0.0).sqrt(),
+        (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+        (1.0 - normal.z * normal.z).max(0.0).sqrt(),
+    ) * radius;
+
+    Aabb::new(position - extent, position + extent)
+}
+
+/**
+ * @brief An infinite plane
+ */
+pub struct PlaneNode {
+    pub position: Vector3,
+    pub normal: Vector3,
+    pub material: Material,
+}
+
+impl PlaneNode {
+    /**
+     * @brief Planar texture coordinates, the texture repeating every unit of length
+     */
+    fn uv(&self, point: Vector3) -> [f32; 2] {
+        let (tangent, bitangent) = self.normal.normalize().orthonormal_basis();
+        let offset = point - self.position;
+        [offset.dot(tangent), offset.dot(bitangent)]
+    }
+}
+
+impl Node for PlaneNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        stats::count_ray_primitive_test();
+
+        let normal = self.normal.normalize();
+        let distance = hit_plane(ray, self.position, normal)?;
+        nearest_hit(&sorted_hits(
+            ray,
+            std::iter::once((distance, face_forward(normal, ray))),
+            self.material,
+        ))
+        .map(|hit| Hit {
+            uv: self.uv(hit.position),
+            uv_density: 1.0,
+            ..hit
         })
     }
+
+    /**
+     * @brief The solid of a plane is the half-space behind its normal
+     */
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        stats::count_ray_primitive_test();
+
+        let normal = self.normal.normalize();
+        let at_infinity = |distance: f32| Hit {
+            position: ray.origin + ray.direction * distance,
+            normal,
+            distance,
+            material: self.material,
+            uv: [0.0, 0.0],
+            uv_density: 0.0,
+        };
+        match hit_plane(ray, self.position, normal) {
+            Some(distance) => {
+                let hit = at_infinity(distance);
+                let hit = Hit {
+                    uv: self.uv(hit.position),
+                    uv_density: 1.0,
+                    ..hit
+                };
+                if normal.dot(ray.direction) < 0.0 {
+                    vec![Interval {
+                        enter: hit,
+                        exit: at_infinity(f32::INFINITY),
+                    }]
+                } else {
+                    vec![Interval {
+                        enter: at_infinity(f32::NEG_INFINITY),
+                        exit: hit,
+                    }]
+                }
+            }
+            // Parallel ray, either fully inside or fully outside
+            None if (ray.origin - self.position).dot(normal) < 0.0 => vec![Interval {
+                enter: at_infinity(f32::NEG_INFINITY),
+                exit: at_infinity(f32::INFINITY),
+            }],
+            None => Vec::new(),
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::INFINITE
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for PlaneNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        (point - self.position).dot(self.normal.normalize())
+    }
+
+    fn material(&self, _point: Vector3) -> Material {
+        self.material
+    }
+}
+
+/**
+ * @brief A flat disk
+ */
+pub struct DiskNode {
+    pub position: Vector3,
+    pub normal: Vector3,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl Node for DiskNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        stats::count_ray_primitive_test();
+
+        let normal = self.normal.normalize();
+        let distance = hit_plane(ray, self.position, normal)?;
+        let offset = ray.origin + ray.direction * distance - self.position;
+        if offset.dot(offset) > self.radius * self.radius {
+            return None;
+        }
+
+        nearest_hit(&sorted_hits(
+            ray,
+            std::iter::once((distance, face_forward(normal, ray))),
+            self.material,
+        ))
+    }
+
+    /**
+     * @brief A disk has no volume
+     */
+    fn intervals(&self, _ray: &Ray) -> Vec<Interval> {
+        Vec::new()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        disk_bounding_box(self.position, self.normal, self.radius)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for DiskNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        // Unsigned, a disk has no inside
+        let normal = self.normal.normalize();
+        let offset = point - self.position;
+        let height = offset.dot(normal);
+        let radial = (offset - normal * height).length();
+        if radial <= self.radius {
+            height.abs()
+        } else {
+            ((radial - self.radius).powi(2) + height * height).sqrt()
+        }
+    }
+
+    fn material(&self, _point: Vector3) -> Material {
+        self.material
+    }
+}
+
+/**
+ * @brief A box, oriented by Euler angles
+ */
+pub struct BoxNode {
+    pub position: Vector3,
+    pub size: Vector3,
+    pub rotation: Vector3,
+    pub material: Material,
+    axes: [Vector3; 3],
+}
+
+impl BoxNode {
+    /**
+     * @brief Create a new box
+     *
+     * @param position the center of the box
+     * @param size the size of the box along each of its axes
+     * @param rotation the rotation of the box around X, then Y, then Z, in degrees
+     * @param material the material of the box
+     */
+    pub fn new(position: Vector3, size: Vector3, rotation: Vector3, material: Material) -> Self {
+        let axes = [
+            Vector3::new(1.0, 0.0, 0.0).rotate_euler(rotation),
+            Vector3::new(0.0, 1.0, 0.0).rotate_euler(rotation),
+            Vector3::new(0.0, 0.0, 1.0).rotate_euler(rotation),
+        ];
+
+        Self {
+            position,
+            size,
+            rotation,
+            material,
+            axes,
+        }
+    }
+
+    /**
+     * @brief Intersect the whole line of the ray with the surface
+     *
+     * @return the hits, sorted by distance
+     */
+    fn line_hits(&self, ray: &Ray) -> Vec<Hit> {
+        stats::count_ray_primitive_test();
+
+        // Slab test in the space of the box
+        let origin = ray.origin - self.position;
+        let half_size = self.size * 0.5;
+        let (mut t_near, mut t_far) = (f32::NEG_INFINITY, f32::INFINITY);
+        let (mut near_normal, mut far_normal) = (Vector3::default(), Vector3::default());
+        for (i, axis) in self.axes.iter().enumerate() {
+            let o = axis.dot(origin);
+            let d = axis.dot(ray.direction);
+            if d.abs() < 1e-8 {
+                if o.abs() > half_size[i] {
+                    return Vec::new();
+                }
+                continue;
+            }
+
+            let t0 = (-half_size[i] - o) / d;
+            let t1 = (half_size[i] - o) / d;
+            let (t0, t1, normal) = if t0 < t1 {
+                (t0, t1, -*axis)
+            } else {
+                (t1, t0, *axis)
+            };
+            if t0 > t_near {
+                t_near = t0;
+                near_normal = normal;
+            }
+            if t1 < t_far {
+                t_far = t1;
+                far_normal = -normal;
+            }
+        }
+        if t_near > t_far {
+            return Vec::new();
+        }
+
+        sorted_hits(
+            ray,
+            [(t_near, near_normal), (t_far, far_normal)].into_iter(),
+            self.material,
+        )
+    }
+}
+
+impl Node for BoxNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        nearest_hit(&self.line_hits(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        pair_intervals(&self.line_hits(ray))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let half_size = self.size * 0.5;
+        let extent = (0..3).fold(Vector3::default(), |acc, i| {
+            let axis = self.axes[i];
+            acc + Vector3::new(axis.x.abs(), axis.y.abs(), axis.z.abs()) * half_size[i]
+        });
+
+        Aabb::new(self.position - extent, self.position + extent)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for BoxNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        let offset = point - self.position;
+        let q = Vector3::new(
+            offset.dot(self.axes[0]).abs() - self.size.x * 0.5,
+            offset.dot(self.axes[1]).abs() - self.size.y * 0.5,
+            offset.dot(self.axes[2]).abs() - self.size.z * 0.5,
+        );
+
+        q.max(Vector3::default()).length() + q.x.max(q.y).max(q.z).min(0.0)
+    }
+
+    fn material(&self, _point: Vector3) -> Material {
+        self.material
+    }
+}
+
+/**
+ * @brief A capped cylinder between two points
+ */
+pub struct CylinderNode {
+    pub start: Vector3,
+    pub end: Vector3,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl CylinderNode {
+    /**
+     * @brief Intersect the whole line of the ray with the surface
+     *
+     * @return the hits, sorted by distance
+     */
+    fn line_hits(&self, ray: &Ray) -> Vec<Hit> {
+        stats::count_ray_primitive_test();
+
+        let height = (self.end - self.start).length();
+        let axis = (self.end - self.start) / height;
+        let oc = ray.origin - self.start;
+
+        // Side, with the components orthogonal to the axis
+        let d_perp = ray.direction - axis * axis.dot(ray.direction);
+        let oc_perp = oc - axis * axis.dot(oc);
+        let side = solve_quadratic(
+            d_perp.dot(d_perp) as f64,
+            2.0 * oc_perp.dot(d_perp) as f64,
+            (oc_perp.dot(oc_perp) - self.radius * self.radius) as f64,
+        )
+        .into_iter()
+        .map(|t| t as f32)
+        .filter_map(|t| {
+            let y = (oc + ray.direction * t).dot(axis);
+            if (0.0..=height).contains(&y) {
+                Some((t, (oc + ray.direction * t - axis * y).normalize()))
+            } else {
+                None
+            }
+        });
+
+        // Caps
+        let caps = [(self.start, -axis), (self.end, axis)]
+            .into_iter()
+            .filter_map(|(center, normal)| {
+                let t = hit_plane(ray, center, normal)?;
+                let offset = ray.origin + ray.direction * t - center;
+                if offset.dot(offset) <= self.radius * self.radius {
+                    Some((t, normal))
+                } else {
+                    None
+                }
+            });
+
+        sorted_hits(ray, side.chain(caps), self.material)
+    }
+}
+
+impl Node for CylinderNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        nearest_hit(&self.line_hits(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        pair_intervals(&self.line_hits(ray))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let axis = self.end - self.start;
+        disk_bounding_box(self.start, axis, self.radius).union(disk_bounding_box(
+            self.end,
+            axis,
+            self.radius,
+        ))
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for CylinderNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        let ba = self.end - self.start;
+        let pa = point - self.start;
+        let baba = ba.dot(ba);
+        let paba = pa.dot(ba);
+        let x = (pa * baba - ba * paba).length() - self.radius * baba;
+        let y = (paba - baba * 0.5).abs() - baba * 0.5;
+        let x2 = x * x;
+        let y2 = y * y * baba;
+        let d = if x.max(y) < 0.0 {
+            -x2.min(y2)
+        } else {
+            (if x > 0.0 { x2 } else { 0.0 }) + (if y > 0.0 { y2 } else { 0.0 })
+        };
+
+        d.signum() * d.abs().sqrt() / baba
+    }
+
+    fn material(&self, _point: Vector3) -> Material {
+        self.material
+    }
+}
+
+/**
+ * @brief A capped cone, from a base disk to an apex
+ */
+pub struct ConeNode {
+    pub start: Vector3,
+    pub end: Vector3,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl ConeNode {
+    /**
+     * @brief Intersect the whole line of the ray with the surface
+     *
+     * @return the hits, sorted by distance
+     */
+    fn line_hits(&self, ray: &Ray) -> Vec<Hit> {
+        stats::count_ray_primitive_test();
+
+        // Work from the apex, with the axis going toward the base
+        let height = (self.start - self.end).length();
+        let axis = (self.start - self.end) / height;
+        let k = 1.0 + (self.radius / height).powi(2);
+        let oc = ray.origin - self.end;
+        let dv = ray.direction.dot(axis);
+        let ov = oc.dot(axis);
+
+        let side = solve_quadratic(
+            (ray.direction.dot(ray.direction) - k * dv * dv) as f64,
+            (2.0 * (oc.dot(ray.direction) - k * dv * ov)) as f64,
+            (oc.dot(oc) - k * ov * ov) as f64,
+        )
+        .into_iter()
+        .map(|t| t as f32)
+        .filter_map(|t| {
+            let y = ov + dv * t;
+            if (0.0..=height).contains(&y) {
+                let p = oc + ray.direction * t;
+                Some((t, (p - axis * (k * y)).normalize()))
+            } else {
+                None
+            }
+        });
+
+        // Base
+        let base = hit_plane(ray, self.start, axis).and_then(|t| {
+            let offset = ray.origin + ray.direction * t - self.start;
+            if offset.dot(offset) <= self.radius * self.radius {
+                Some((t, axis))
+            } else {
+                None
+            }
+        });
+
+        sorted_hits(ray, side.chain(base), self.material)
+    }
+}
+
+impl Node for ConeNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        nearest_hit(&self.line_hits(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        pair_intervals(&self.line_hits(ray))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        disk_bounding_box(self.start, self.end - self.start, self.radius).grow(self.end)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for ConeNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        // Capped cone from the base radius to a null radius at the apex
+        let ba = self.end - self.start;
+        let pa = point - self.start;
+        let baba = ba.dot(ba);
+        let paba = pa.dot(ba) / baba;
+        let x = (pa.dot(pa) - paba * paba * baba).max(0.0).sqrt();
+        let cax = (x - if paba < 0.5 { self.radius } else { 0.0 }).max(0.0);
+        let cay = (paba - 0.5).abs() - 0.5;
+        let k = self.radius * self.radius + baba;
+        let f = ((-self.radius * (x - self.radius) + paba * baba) / k).clamp(0.0, 1.0);
+        let cbx = x - self.radius + f * self.radius;
+        let cby = paba - f;
+        let sign = if cbx < 0.0 && cay < 0.0 { -1.0 } else { 1.0 };
+
+        sign * (cax * cax + cay * cay * baba)
+            .min(cbx * cbx + cby * cby * baba)
+            .sqrt()
+    }
+
+    fn material(&self, _point: Vector3) -> Material {
+        self.material
+    }
+}
+
+/**
+ * @brief A torus, around an axis going through its center
+ */
+pub struct TorusNode {
+    pub position: Vector3,
+    pub axis: Vector3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: Material,
+}
+
+impl TorusNode {
+    /**
+     * @brief Intersect the whole line of the ray with the surface
+     *
+     * @return the hits, sorted by distance
+     */
+    fn line_hits(&self, ray: &Ray) -> Vec<Hit> {
+        stats::count_ray_primitive_test();
+
+        // Express the ray in the space of the torus, with the axis along Y
+        let axis = self.axis.normalize();
+        let (tangent, bitangent) = axis.orthonormal_basis();
+        let to_local = |v: Vector3| Vector3::new(v.dot(tangent), v.dot(axis), v.dot(bitangent));
+        let o = to_local(ray.origin - self.position);
+        let d = to_local(ray.direction);
+
+        // Solve (|p|^2 + R^2 - r^2)^2 = 4 R^2 (px^2 + pz^2)
+        let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+        let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+        let major2 = (self.major_radius as f64).powi(2);
+        let minor2 = (self.minor_radius as f64).powi(2);
+        let g = dx * dx + dy * dy + dz * dz;
+        let h = 2.0 * (ox * dx + oy * dy + oz * dz);
+        let i = ox * ox + oy * oy + oz * oz + major2 - minor2;
+        let a4 = g * g;
+        let a3 = 2.0 * g * h;
+        let a2 = h * h + 2.0 * g * i - 4.0 * major2 * (dx * dx + dz * dz);
+        let a1 = 2.0 * h * i - 8.0 * major2 * (ox * dx + oz * dz);
+        let a0 = i * i - 4.0 * major2 * (ox * ox + oz * oz);
+
+        let roots = solve_quartic(a3 / a4, a2 / a4, a1 / a4, a0 / a4);
+        let candidates = roots.into_iter().map(|t| {
+            let t = t as f32;
+            let p = o + d * t;
+            let s = p.dot(p) + self.major_radius.powi(2) - self.minor_radius.powi(2);
+            let k = 2.0 * self.major_radius.powi(2);
+            let n = Vector3::new(p.x * (s - k), p.y * s, p.z * (s - k));
+            let normal = (tangent * n.x + axis * n.y + bitangent * n.z).normalize();
+            (t, normal)
+        });
+
+        sorted_hits(ray, candidates, self.material)
+    }
+}
+
+impl Node for TorusNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        nearest_hit(&self.line_hits(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        pair_intervals(&self.line_hits(ray))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let minor = Vector3::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        let ring = disk_bounding_box(self.position, self.axis, self.major_radius);
+
+        Aabb::new(ring.min - minor, ring.max + minor)
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        Some(self)
+    }
+}
+
+impl SdfNode for TorusNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        let axis = self.axis.normalize();
+        let offset = point - self.position;
+        let height = offset.dot(axis);
+        let radial = (offset - axis * height).length() - self.major_radius;
+
+        (radial * radial + height * height).sqrt() - self.minor_radius
+    }
+
+    fn material(&self, _point: Vector3) -> Material {
+        self.material
+    }
 }
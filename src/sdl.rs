@@ -1,5 +1,4 @@
 use crate::canvas::Canvas;
-use crate::math::lerp_color8;
 use crate::render::Renderer;
 use crate::scene::Scene;
 use sdl2::event::Event;
@@ -58,7 +57,7 @@ pub fn sdl_main(
         }
 
         // Render the scene to our canvas
-        renderer.render(&scene, canvas);
+        renderer.render(scene, canvas);
 
         // Clear the SDL canvas with a nice magenta color for
         // catching undrawn pixels
@@ -71,12 +70,10 @@ pub fn sdl_main(
                 let (width, height) = (canvas.width(), canvas.height());
                 for y in 0..height {
                     for x in 0..width {
-                        let color = canvas.get_pixel(x, y);
+                        let color = scene.display.to_rgb8(canvas.get_pixel(x, y));
                         let offset_out = (height - 1 - y) as usize * pitch + x as usize * 3;
 
-                        buffer[offset_out] = lerp_color8(color.r);
-                        buffer[offset_out + 1] = lerp_color8(color.g);
-                        buffer[offset_out + 2] = lerp_color8(color.b);
+                        buffer[offset_out..offset_out + 3].copy_from_slice(&color);
                     }
                 }
             })
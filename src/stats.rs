@@ -0,0 +1,69 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/**
+ * @brief Counters of the intersection tests done during a render
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub ray_box_tests: u64,
+    pub ray_primitive_tests: u64,
+}
+
+static RAY_BOX_TESTS: AtomicU64 = AtomicU64::new(0);
+static RAY_PRIMITIVE_TESTS: AtomicU64 = AtomicU64::new(0);
+
+// The counters are accumulated per thread to avoid the contention on the
+// global counters, and merged by flush()
+thread_local! {
+    static LOCAL_STATS: Cell<Stats> = Cell::new(Stats::default());
+}
+
+/**
+ * @brief Count a ray/bounding box intersection test
+ */
+pub fn count_ray_box_test() {
+    LOCAL_STATS.with(|stats| {
+        let mut s = stats.get();
+        s.ray_box_tests += 1;
+        stats.set(s);
+    });
+}
+
+/**
+ * @brief Count a ray/primitive intersection test
+ */
+pub fn count_ray_primitive_test() {
+    LOCAL_STATS.with(|stats| {
+        let mut s = stats.get();
+        s.ray_primitive_tests += 1;
+        stats.set(s);
+    });
+}
+
+/**
+ * @brief Merge the counters of the current thread into the global counters
+ */
+pub fn flush() {
+    let local = LOCAL_STATS.with(|stats| stats.replace(Stats::default()));
+    RAY_BOX_TESTS.fetch_add(local.ray_box_tests, Ordering::Relaxed);
+    RAY_PRIMITIVE_TESTS.fetch_add(local.ray_primitive_tests, Ordering::Relaxed);
+}
+
+/**
+ * @brief Get the global counters
+ */
+pub fn get() -> Stats {
+    Stats {
+        ray_box_tests: RAY_BOX_TESTS.load(Ordering::Relaxed),
+        ray_primitive_tests: RAY_PRIMITIVE_TESTS.load(Ordering::Relaxed),
+    }
+}
+
+/**
+ * @brief Reset the global counters
+ */
+pub fn reset() {
+    RAY_BOX_TESTS.store(0, Ordering::Relaxed);
+    RAY_PRIMITIVE_TESTS.store(0, Ordering::Relaxed);
+}
@@ -0,0 +1,328 @@
+use std::path::{Path, PathBuf};
+
+use crate::{color::Color, display::srgb_decode};
+
+/**
+ * @brief How a texture is filtered between its texels and its levels
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    /**
+     * @brief Interpolate the 4 nearest texels of the nearest level
+     */
+    Bilinear,
+
+    /**
+     * @brief Interpolate between the two nearest levels too
+     */
+    Trilinear,
+}
+
+impl TextureFilter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bilinear" => Some(Self::Bilinear),
+            "trilinear" => Some(Self::Trilinear),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bilinear => "bilinear",
+            Self::Trilinear => "trilinear",
+        }
+    }
+}
+
+/**
+ * @brief How the texture coordinates outside of [0, 1] are mapped to the texture
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    /**
+     * @brief Tile the texture
+     */
+    Repeat,
+
+    /**
+     * @brief Extend the texels of the borders
+     */
+    Clamp,
+}
+
+impl Wrap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "repeat" => Some(Self::Repeat),
+            "clamp" => Some(Self::Clamp),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Repeat => "repeat",
+            Self::Clamp => "clamp",
+        }
+    }
+
+    fn texel(&self, index: i64, size: usize) -> usize {
+        match self {
+            Self::Repeat => index.rem_euclid(size as i64) as usize,
+            Self::Clamp => index.clamp(0, size as i64 - 1) as usize,
+        }
+    }
+}
+
+/**
+ * @brief A level of the mip-map of a texture, stored by rows from the top
+ */
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+}
+
+impl MipLevel {
+    /**
+     * @brief The level of half the size, each texel averaging 2x2 texels
+     */
+    fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let texel = |x: usize, y: usize| {
+            self.texels[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+        };
+
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let sum = texel(2 * x, 2 * y)
+                    + texel(2 * x + 1, 2 * y)
+                    + texel(2 * x, 2 * y + 1)
+                    + texel(2 * x + 1, 2 * y + 1);
+                texels.push(sum * 0.25);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    fn bilinear(&self, uv: [f32; 2], wrap: Wrap) -> Color {
+        // The texture coordinates start at the bottom left corner
+        let x = uv[0] * self.width as f32 - 0.5;
+        let y = (1.0 - uv[1]) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let texel = |dx: i64, dy: i64| {
+            let tx = wrap.texel(x0 as i64 + dx, self.width);
+            let ty = wrap.texel(y0 as i64 + dy, self.height);
+            self.texels[ty * self.width + tx]
+        };
+        let top = texel(0, 0) * (1.0 - fx) + texel(1, 0) * fx;
+        let bottom = texel(0, 1) * (1.0 - fx) + texel(1, 1) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+/**
+ * @brief An image mapped on the surfaces with their texture coordinates
+ */
+pub struct Texture {
+    /**
+     * @brief The file the texture was loaded from
+     */
+    pub path: Option<PathBuf>,
+
+    /**
+     * @brief Whether the file stores sRGB colors rather than linear values
+     */
+    pub srgb: bool,
+
+    pub filter: TextureFilter,
+    pub wrap: Wrap,
+
+    /**
+     * @brief The mip-map, from the full size to a single texel
+     */
+    levels: Vec<MipLevel>,
+}
+
+impl Texture {
+    /**
+     * @brief Create a texture and its mip-map
+     *
+     * @param width the width of the image
+     * @param height the height of the image
+     * @param texels the linear colors of the image, by rows from the top
+     */
+    pub fn new(
+        width: usize,
+        height: usize,
+        texels: Vec<Color>,
+        filter: TextureFilter,
+        wrap: Wrap,
+    ) -> Self {
+        let mut levels = vec![MipLevel {
+            width,
+            height,
+            texels,
+        }];
+        while let Some(level) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            levels.push(level.downsample());
+        }
+
+        Self {
+            path: None,
+            srgb: false,
+            filter,
+            wrap,
+            levels,
+        }
+    }
+
+    /**
+     * @brief Load a texture from a PNG or a JPEG file
+     *
+     * @param path the path of the image
+     * @param srgb decode the sRGB colors, false for the images storing values like a roughness
+     */
+    pub fn load(
+        path: &Path,
+        srgb: bool,
+        filter: TextureFilter,
+        wrap: Wrap,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_rgb8();
+        let decode = |channel: u8| {
+            let value = channel as f32 / 255.0;
+            if srgb {
+                srgb_decode(value)
+            } else {
+                value
+            }
+        };
+        let texels = image
+            .pixels()
+            .map(|pixel| Color::new(decode(pixel[0]), decode(pixel[1]), decode(pixel[2])))
+            .collect();
+
+        let mut texture = Self::new(
+            image.width() as usize,
+            image.height() as usize,
+            texels,
+            filter,
+            wrap,
+        );
+        texture.path = Some(path.to_path_buf());
+        texture.srgb = srgb;
+
+        Ok(texture)
+    }
+
+    /**
+     * @brief Filter the texture around a point
+     *
+     * @param uv the texture coordinates of the point
+     * @param footprint the size of the pixel in texture coordinates, to pick the level
+     */
+    pub fn sample(&self, uv: [f32; 2], footprint: f32) -> Color {
+        let size = self.levels[0].width.max(self.levels[0].height) as f32;
+        let max_level = (self.levels.len() - 1) as f32;
+        let level = (footprint * size).max(1.0).log2().min(max_level);
+
+        match self.filter {
+            TextureFilter::Bilinear => self.levels[level.round() as usize].bilinear(uv, self.wrap),
+            TextureFilter::Trilinear => {
+                let (first, t) = (level.floor() as usize, level.fract());
+                let color = self.levels[first].bilinear(uv, self.wrap);
+                if t > 0.0 {
+                    color * (1.0 - t) + self.levels[first + 1].bilinear(uv, self.wrap) * t
+                } else {
+                    color
+                }
+            }
+        }
+    }
+
+    /**
+     * @brief Filter the texture around a point, as a single value
+     */
+    pub fn sample_value(&self, uv: [f32; 2], footprint: f32) -> f32 {
+        let color = self.sample(uv, footprint);
+        (color.r + color.g + color.b) / 3.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * @brief A black texel and a white texel
+     */
+    fn gradient(wrap: Wrap) -> Texture {
+        Texture::new(
+            2,
+            1,
+            vec![Color::BLACK, Color::WHITE],
+            TextureFilter::Bilinear,
+            wrap,
+        )
+    }
+
+    #[test]
+    fn wrap_maps_the_texel_indices() {
+        assert_eq!(Wrap::Repeat.texel(-1, 4), 3);
+        assert_eq!(Wrap::Repeat.texel(5, 4), 1);
+        assert_eq!(Wrap::Clamp.texel(-1, 4), 0);
+        assert_eq!(Wrap::Clamp.texel(5, 4), 3);
+    }
+
+    #[test]
+    fn lookups_repeat_or_clamp_at_the_borders() {
+        // Halfway between the centers of the texels across the left border
+        let repeat = gradient(Wrap::Repeat).sample([0.0, 0.5], 0.0);
+        assert!((repeat.r - 0.5).abs() < 1e-6);
+        let clamp = gradient(Wrap::Clamp).sample([0.0, 0.5], 0.0);
+        assert_eq!(clamp.r, 0.0);
+
+        // The center of the white texel
+        assert_eq!(gradient(Wrap::Clamp).sample([0.75, 0.5], 0.0).r, 1.0);
+    }
+
+    #[test]
+    fn mip_map_goes_down_to_a_single_texel() {
+        let texture = Texture::new(
+            8,
+            2,
+            vec![Color::WHITE; 16],
+            TextureFilter::Trilinear,
+            Wrap::Repeat,
+        );
+        let sizes: Vec<(usize, usize)> = texture
+            .levels
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+        assert_eq!(sizes, [(8, 2), (4, 1), (2, 1), (1, 1)]);
+
+        // The last level averages the whole image
+        let checker = Texture::new(
+            2,
+            2,
+            vec![Color::BLACK, Color::WHITE, Color::WHITE, Color::BLACK],
+            TextureFilter::Bilinear,
+            Wrap::Repeat,
+        );
+        assert_eq!(checker.levels.len(), 2);
+        assert!((checker.sample([0.3, 0.8], 10.0).r - 0.5).abs() < 1e-6);
+    }
+}
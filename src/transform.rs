@@ -0,0 +1,290 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    math::{Matrix4, Vector3},
+    ray::{Hit, Interval, Ray},
+    sdf::{Node, SdfNode},
+};
+
+/**
+ * @brief An affine transform, with what is needed to move the rays and the hits
+ *
+ * The rays are moved into the space of the node without being normalized,
+ * so that the distances of the hits are the same in both spaces.
+ */
+struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+
+    /**
+     * @brief The inverse transposed, to move the normals out of the node
+     */
+    normal_matrix: Matrix4,
+
+    /**
+     * @brief The largest stretch of the inverse, to bound the distances of the field
+     */
+    inverse_scale: f32,
+}
+
+impl Transform {
+    fn new(matrix: Matrix4) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+
+        Some(Self {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            inverse_scale: inverse.max_scale(),
+        })
+    }
+
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.inverse.transform_point(ray.origin),
+            direction: self.inverse.transform_vector(ray.direction),
+            spread: ray.spread,
+        }
+    }
+
+    fn local_point(&self, point: Vector3) -> Vector3 {
+        self.inverse.transform_point(point)
+    }
+
+    /**
+     * @brief Move a hit in the space of the node back to the space of the scene
+     */
+    fn world_hit(&self, ray: &Ray, hit: Hit) -> Hit {
+        Hit {
+            position: ray.origin + ray.direction * hit.distance,
+            normal: self.normal_matrix.transform_vector(hit.normal).normalize(),
+            uv_density: hit.uv_density * self.inverse_scale,
+            ..hit
+        }
+    }
+
+    fn world_intervals(&self, ray: &Ray, intervals: Vec<Interval>) -> Vec<Interval> {
+        intervals
+            .into_iter()
+            .map(|interval| Interval {
+                enter: self.world_hit(ray, interval.enter),
+                exit: self.world_hit(ray, interval.exit),
+            })
+            .collect()
+    }
+
+    /**
+     * @brief The bounding box of the transformed corners of a box of the node
+     */
+    fn world_bounds(&self, bounds: Aabb) -> Aabb {
+        if bounds.is_empty() {
+            return Aabb::EMPTY;
+        }
+        if !bounds.is_finite() {
+            return Aabb::INFINITE;
+        }
+
+        let corners = [bounds.min, bounds.max];
+        (0..8).fold(Aabb::EMPTY, |acc, i| {
+            let corner = Vector3::new(corners[i & 1].x, corners[(i >> 1) & 1].y, corners[i >> 2].z);
+            acc.grow(self.matrix.transform_point(corner))
+        })
+    }
+
+    /**
+     * @brief The distance to a field in the space of the node, over the largest stretch of the inverse
+     *
+     * The distance is exact for the rigid transforms and the uniform scales,
+     * and a lower bound otherwise.
+     */
+    fn world_distance(&self, field: Option<&dyn SdfNode>, point: Vector3) -> f32 {
+        field.map_or(f32::INFINITY, |field| {
+            field.distance(self.local_point(point)) / self.inverse_scale
+        })
+    }
+
+    fn world_material(&self, field: Option<&dyn SdfNode>, point: Vector3) -> Material {
+        field.map_or(Material::DEBUG_MATERIAL, |field| {
+            field.material(self.local_point(point))
+        })
+    }
+}
+
+/**
+ * @brief A node moved, rotated and scaled by an affine transform
+ */
+pub struct TransformNode {
+    pub node: Box<dyn Node>,
+    transform: Transform,
+}
+
+impl TransformNode {
+    /**
+     * @brief Transform a node
+     *
+     * @return the node, or None if the transform can't be inverted
+     */
+    pub fn new(node: Box<dyn Node>, transform: Matrix4) -> Option<Self> {
+        Some(Self {
+            node,
+            transform: Transform::new(transform)?,
+        })
+    }
+
+    /**
+     * @brief The transform from the space of the node to the space of the scene
+     */
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform.matrix
+    }
+}
+
+impl Node for TransformNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.node
+            .hit(&self.transform.local_ray(ray))
+            .map(|hit| self.transform.world_hit(ray, hit))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let intervals = self.node.intervals(&self.transform.local_ray(ray));
+        self.transform.world_intervals(ray, intervals)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.transform.world_bounds(self.node.bounding_box())
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        self.node.as_sdf().map(|_| self as &dyn SdfNode)
+    }
+}
+
+impl SdfNode for TransformNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        self.transform.world_distance(self.node.as_sdf(), point)
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        self.transform.world_material(self.node.as_sdf(), point)
+    }
+}
+
+/**
+ * @brief A transformed copy of a prototype shared by many instances
+ *
+ * The memory grows with the number of prototypes rather than with the
+ * number of instances. Many instances are best stored in a BvhNode, the
+ * instances being the leaves of the hierarchy over the scene while each
+ * prototype, like a mesh, keeps its own hierarchy.
+ */
+pub struct InstanceNode {
+    pub prototype: Arc<dyn Node>,
+
+    /**
+     * @brief The material replacing the ones of the prototype, None to keep them
+     */
+    pub material: Option<Material>,
+
+    /**
+     * @brief The identifier of the instance, replacing the objects of the prototype
+     */
+    pub object_id: u32,
+
+    transform: Transform,
+}
+
+impl InstanceNode {
+    /**
+     * @brief Create an instance of a prototype
+     *
+     * @return the instance, or None if the transform can't be inverted
+     */
+    pub fn new(
+        prototype: Arc<dyn Node>,
+        transform: Matrix4,
+        material: Option<Material>,
+        object_id: u32,
+    ) -> Option<Self> {
+        Some(Self {
+            prototype,
+            material,
+            object_id,
+            transform: Transform::new(transform)?,
+        })
+    }
+
+    /**
+     * @brief The transform from the space of the prototype to the space of the scene
+     */
+    pub fn transform(&self) -> &Matrix4 {
+        &self.transform.matrix
+    }
+
+    fn replace_material(&self, material: Material) -> Material {
+        Material {
+            object_id: self.object_id,
+            ..self.material.unwrap_or(material)
+        }
+    }
+
+    fn replace_hit_material(&self, hit: Hit) -> Hit {
+        Hit {
+            material: self.replace_material(hit.material),
+            ..hit
+        }
+    }
+}
+
+impl Node for InstanceNode {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.prototype
+            .hit(&self.transform.local_ray(ray))
+            .map(|hit| self.replace_hit_material(self.transform.world_hit(ray, hit)))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let intervals = self.prototype.intervals(&self.transform.local_ray(ray));
+        self.transform
+            .world_intervals(ray, intervals)
+            .into_iter()
+            .map(|interval| Interval {
+                enter: self.replace_hit_material(interval.enter),
+                exit: self.replace_hit_material(interval.exit),
+            })
+            .collect()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.transform.world_bounds(self.prototype.bounding_box())
+    }
+
+    fn as_sdf(&self) -> Option<&dyn SdfNode> {
+        self.prototype.as_sdf().map(|_| self as &dyn SdfNode)
+    }
+}
+
+impl SdfNode for InstanceNode {
+    fn distance(&self, point: Vector3) -> f32 {
+        self.transform
+            .world_distance(self.prototype.as_sdf(), point)
+    }
+
+    fn material(&self, point: Vector3) -> Material {
+        self.replace_material(
+            self.transform
+                .world_material(self.prototype.as_sdf(), point),
+        )
+    }
+}